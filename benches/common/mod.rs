@@ -0,0 +1,192 @@
+// encode と decode の benchmark で共有する入れ子のメッセージと、以前の方法による encode/decode です
+#![allow(dead_code)]
+
+use protowirers::parser::Parser;
+use protowirers::wire::*;
+use protowirers::*;
+use std::io::Cursor;
+
+// LegacyEncode は encode_wire_binary を使った以前の encode 方法です
+// embedded message ごとに一時的な Vec<u8> に書き出し、親にコピーします
+pub trait LegacyEncode {
+    fn legacy_bytes(&self) -> protowirers::Result<Vec<u8>>;
+}
+
+// LegacyDecode は decode_wire_binary を使った以前の parse 方法です
+// Vec<WireStruct> を作った後、wire_type() で値を複製しながらフィールドに設定します
+pub trait LegacyDecode: Sized {
+    fn legacy_parse(bytes: &[u8]) -> protowirers::Result<Self>;
+}
+
+// Leaf は入れ子の最下層のメッセージです
+#[derive(Proto, Default, Clone, Debug, PartialEq)]
+pub struct Leaf {
+    #[def(field_num = 1, def_type = "uint64")]
+    pub id: u64,
+    #[def(field_num = 2, def_type = "string")]
+    pub name: String,
+    #[def(field_num = 3, def_type = "sint64", repeated, packed)]
+    pub values: Vec<i64>,
+}
+
+impl LegacyEncode for Leaf {
+    fn legacy_bytes(&self) -> protowirers::Result<Vec<u8>> {
+        let inputs = vec![
+            WireStruct::new(
+                1,
+                WireData::Varint(Parser::from(&self.id, TypeVairant::Uint64)?),
+            ),
+            WireStruct::new(
+                2,
+                WireData::LengthDelimited(Parser::from(
+                    &self.name,
+                    TypeLengthDelimited::WireString,
+                )?),
+            ),
+            WireStruct::new(
+                3,
+                WireData::LengthDelimited(Parser::from(
+                    &self.values,
+                    TypeLengthDelimited::PackedRepeatedFields(AllowedPakcedType::Variant(
+                        TypeVairant::Sint64,
+                    )),
+                )?),
+            ),
+        ];
+        let mut c = Cursor::new(Vec::new());
+        encode::encode_wire_binary(&mut c, inputs)?;
+        Ok(c.into_inner())
+    }
+}
+
+impl LegacyDecode for Leaf {
+    fn legacy_parse(bytes: &[u8]) -> protowirers::Result<Self> {
+        let mut c = Cursor::new(bytes);
+        let result = decode::decode_wire_binary(&mut c)?;
+        let mut id = None;
+        let mut name = None;
+        let mut values = None;
+        for sw in result {
+            match (sw.field_number(), sw.wire_type()) {
+                (1, WireData::Varint(v)) => id = Some(v.parse(TypeVairant::Uint64)?),
+                (2, WireData::LengthDelimited(v)) => {
+                    name = Some(v.parse(TypeLengthDelimited::WireString)?)
+                }
+                (3, WireData::LengthDelimited(v)) => {
+                    values = Some(v.parse(TypeLengthDelimited::PackedRepeatedFields(
+                        AllowedPakcedType::Variant(TypeVairant::Sint64),
+                    ))?)
+                }
+                _ => (),
+            }
+        }
+        Ok(Self {
+            id: id.unwrap_or_default(),
+            name: name.unwrap_or_default(),
+            values: values.unwrap_or_default(),
+        })
+    }
+}
+
+// nested は child を 1 段包んだメッセージを定義します
+macro_rules! nested {
+    ($name:ident, $child:ident) => {
+        #[derive(Proto, Default, Clone, Debug, PartialEq)]
+        pub struct $name {
+            #[def(field_num = 1, def_type = "uint64")]
+            id: u64,
+            #[def(field_num = 2, def_type = "string")]
+            name: String,
+            #[def(field_num = 3, def_type = "embedded")]
+            child: $child,
+        }
+
+        impl $name {
+            pub fn new(child: $child) -> Self {
+                Self {
+                    id: 1 << 40,
+                    name: "nested message".repeat(8),
+                    child,
+                }
+            }
+        }
+
+        impl LegacyEncode for $name {
+            fn legacy_bytes(&self) -> protowirers::Result<Vec<u8>> {
+                let inputs = vec![
+                    WireStruct::new(
+                        1,
+                        WireData::Varint(Parser::from(&self.id, TypeVairant::Uint64)?),
+                    ),
+                    WireStruct::new(
+                        2,
+                        WireData::LengthDelimited(Parser::from(
+                            &self.name,
+                            TypeLengthDelimited::WireString,
+                        )?),
+                    ),
+                    WireStruct::new(
+                        3,
+                        WireData::LengthDelimited(WireDataLengthDelimited::new(
+                            self.child.legacy_bytes()?,
+                        )),
+                    ),
+                ];
+                let mut c = Cursor::new(Vec::new());
+                encode::encode_wire_binary(&mut c, inputs)?;
+                Ok(c.into_inner())
+            }
+        }
+
+        impl LegacyDecode for $name {
+            fn legacy_parse(bytes: &[u8]) -> protowirers::Result<Self> {
+                let mut c = Cursor::new(bytes);
+                let result = decode::decode_wire_binary(&mut c)?;
+                let mut id = None;
+                let mut name = None;
+                let mut child = None;
+                for sw in result {
+                    match (sw.field_number(), sw.wire_type()) {
+                        (1, WireData::Varint(v)) => id = Some(v.parse(TypeVairant::Uint64)?),
+                        (2, WireData::LengthDelimited(v)) => {
+                            name = Some(v.parse(TypeLengthDelimited::WireString)?)
+                        }
+                        // 以前の from_length_delimited は Vec<u8> を受け取っていたため複製する
+                        (3, WireData::LengthDelimited(v)) => {
+                            child = Some($child::legacy_parse(&v.value.clone())?)
+                        }
+                        _ => (),
+                    }
+                }
+                Ok(Self {
+                    id: id.unwrap_or_default(),
+                    name: name.unwrap_or_default(),
+                    child: child.unwrap_or_default(),
+                })
+            }
+        }
+    };
+}
+
+nested!(Level1, Leaf);
+nested!(Level2, Level1);
+nested!(Level3, Level2);
+nested!(Level4, Level3);
+nested!(Level5, Level4);
+nested!(Level6, Level5);
+nested!(Level7, Level6);
+nested!(Level8, Level7);
+nested!(Level9, Level8);
+nested!(Level10, Level9);
+
+// sample は 10 段の入れ子のメッセージを返します
+pub fn sample() -> Level10 {
+    let leaf = Leaf {
+        id: 42,
+        name: "leaf".repeat(16),
+        values: (0..256).map(|v| v * 1000 - 50000).collect(),
+    };
+    Level10::new(Level9::new(Level8::new(Level7::new(Level6::new(
+        Level5::new(Level4::new(Level3::new(Level2::new(Level1::new(leaf))))),
+    )))))
+}
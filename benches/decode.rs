@@ -0,0 +1,43 @@
+mod common;
+
+use common::{sample, Leaf, LegacyDecode, Level10};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use protowirers::wire::Proto;
+
+fn bench_nested(c: &mut Criterion) {
+    let bytes = sample().bytes().unwrap();
+    assert_eq!(
+        Level10::parse(&bytes).unwrap(),
+        Level10::legacy_parse(&bytes).unwrap()
+    );
+
+    let mut group = c.benchmark_group("decode_nested_10");
+    group.bench_function("decode_wire_binary", |b| {
+        b.iter(|| Level10::legacy_parse(black_box(&bytes)).unwrap())
+    });
+    group.bench_function("parse", |b| {
+        b.iter(|| Level10::parse(black_box(&bytes)).unwrap())
+    });
+    group.finish();
+}
+
+fn bench_flat(c: &mut Criterion) {
+    let leaf = Leaf {
+        id: 42,
+        name: "leaf".repeat(256),
+        values: (0..1024).map(|v| v * 1000 - 50000).collect(),
+    };
+    let bytes = leaf.bytes().unwrap();
+
+    let mut group = c.benchmark_group("decode_flat");
+    group.bench_function("decode_wire_binary", |b| {
+        b.iter(|| Leaf::legacy_parse(black_box(&bytes)).unwrap())
+    });
+    group.bench_function("parse", |b| {
+        b.iter(|| Leaf::parse(black_box(&bytes)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_nested, bench_flat);
+criterion_main!(benches);
@@ -0,0 +1,21 @@
+mod common;
+
+use common::{sample, LegacyEncode};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use protowirers::wire::Proto;
+
+fn bench_nested(c: &mut Criterion) {
+    let x = sample();
+    assert_eq!(x.bytes().unwrap(), x.legacy_bytes().unwrap());
+
+    let mut group = c.benchmark_group("encode_nested_10");
+    group.bench_function("encode_wire_binary", |b| {
+        b.iter(|| black_box(&x).legacy_bytes().unwrap())
+    });
+    group.bench_function("bytes", |b| b.iter(|| black_box(&x).bytes().unwrap()));
+    group.bench_function("encoded_len", |b| b.iter(|| black_box(&x).encoded_len()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_nested);
+criterion_main!(benches);
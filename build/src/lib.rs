@@ -0,0 +1,706 @@
+use protowirers::schema::{
+    self, EnumDef, Field, FieldType, Label, Message, ProtoFile, ScalarType, Syntax,
+};
+use protowirers::text::Position;
+use protowirers::{Error, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+// 以下は .proto ファイルから #[derive(Proto)] を使う Rust のコードを生成する処理です
+// build.rs から compile_protos を呼び出し、生成されたファイルを include! して使います
+//
+// ```rust
+// // build.rs
+// protowirers_build::compile_protos(&["api/user.proto"], std::env::var("OUT_DIR").unwrap())?;
+// // src/lib.rs
+// include!(concat!(env!("OUT_DIR"), "/protos.rs"));
+// ```
+
+// OUTPUT_FILE は compile_protos が out_dir に書き込むファイル名です
+pub const OUTPUT_FILE: &str = "protos.rs";
+
+// compile_protos は protos とそれらが import するファイルを読み込み、out_dir/protos.rs に Rust のコードを書き込みます
+// 読み込んだファイルは cargo:rerun-if-changed として出力します
+pub fn compile_protos(protos: &[impl AsRef<Path>], out_dir: impl AsRef<Path>) -> Result<()> {
+    compile_protos_with_includes(protos, &[] as &[&Path], out_dir)
+}
+
+// compile_protos_with_includes は compile_protos と同じですが、import を includes のディレクトリから順に探します
+// protoc の `-I` と同じく、`import "common/types.proto";` は includes のいずれかからの相対パスです
+pub fn compile_protos_with_includes(
+    protos: &[impl AsRef<Path>],
+    includes: &[impl AsRef<Path>],
+    out_dir: impl AsRef<Path>,
+) -> Result<()> {
+    let (files, paths) = load_with_paths(protos, includes)?;
+    let code = generate(&files)?;
+    std::fs::write(out_dir.as_ref().join(OUTPUT_FILE), code)?;
+    for path in paths {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+    Ok(())
+}
+
+// load は protos とそれらが import するファイルを読み込みます
+// import は、import したファイルのディレクトリと protos の各ファイルのディレクトリから探します
+pub fn load(protos: &[impl AsRef<Path>]) -> Result<Vec<ProtoFile>> {
+    load_with_includes(protos, &[] as &[&Path])
+}
+
+// load_with_includes は load と同じですが、import をまず includes のディレクトリから順に探します
+pub fn load_with_includes(
+    protos: &[impl AsRef<Path>],
+    includes: &[impl AsRef<Path>],
+) -> Result<Vec<ProtoFile>> {
+    load_with_paths(protos, includes).map(|(files, _)| files)
+}
+
+fn load_with_paths(
+    protos: &[impl AsRef<Path>],
+    includes: &[impl AsRef<Path>],
+) -> Result<(Vec<ProtoFile>, Vec<PathBuf>)> {
+    let proto_dirs: Vec<PathBuf> = protos
+        .iter()
+        .map(|p| p.as_ref().parent().unwrap_or(Path::new("")).to_path_buf())
+        .collect();
+    let mut files = Vec::new();
+    let mut paths = Vec::new();
+    let mut seen = HashSet::new();
+    let mut queue: Vec<PathBuf> = protos.iter().map(|p| p.as_ref().to_path_buf()).collect();
+    queue.reverse();
+    while let Some(path) = queue.pop() {
+        if !seen.insert(std::fs::canonicalize(&path)?) {
+            continue;
+        }
+        let file = schema::parse_file(&path)?;
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        // includes、import したファイルのディレクトリ、protos の各ファイルのディレクトリの順に探す
+        let search: Vec<&Path> = includes
+            .iter()
+            .map(|d| d.as_ref())
+            .chain(std::iter::once(dir.as_path()))
+            .chain(proto_dirs.iter().map(|d| d.as_path()))
+            .collect();
+        for import in file.imports.iter().rev() {
+            let found = search
+                .iter()
+                .map(|d| d.join(&import.path))
+                .find(|p| p.is_file())
+                .ok_or_else(|| {
+                    error_at(
+                        &file.name,
+                        import.position,
+                        format!("cannot find import `{}`", import.path),
+                    )
+                })?;
+            queue.push(found);
+        }
+        files.push(file);
+        paths.push(path);
+    }
+    Ok((files, paths))
+}
+
+// generate は files の message と enum の Rust のコードを返します
+// package ごとに mod を入れ子にし、入れ子の型は message 名を snake_case にした mod に出力します
+pub fn generate(files: &[ProtoFile]) -> Result<String> {
+    let symbols = Symbols::collect(files);
+    let mut root = Module::default();
+    for file in files {
+        let package: Vec<String> = file
+            .package
+            .iter()
+            .flat_map(|p| p.split('.'))
+            .map(|s| s.to_string())
+            .collect();
+        let scope: String = package.iter().map(|s| format!(".{}", s)).collect();
+        let module: Vec<String> = package.iter().map(|s| rust_ident(s)).collect();
+        let g = Generator {
+            file,
+            symbols: &symbols,
+        };
+        let m = root.child(&module);
+        for e in &file.enums {
+            g.enum_def(m, e);
+        }
+        for msg in &file.messages {
+            g.message(m, msg, &scope, &module)?;
+        }
+    }
+    symbols.check_recursion(files)?;
+    let mut out = String::from("// @generated by protowirers-build. Do not edit.\n\n");
+    root.render(&mut out, 0);
+    Ok(out)
+}
+
+fn error_at(file: &str, position: Position, reason: impl Into<String>) -> Error {
+    Error::InvalidSchema {
+        file: file.to_string(),
+        line: position.line,
+        column: position.column,
+        reason: reason.into(),
+    }
+}
+
+// Module は生成するコードの mod です
+#[derive(Default)]
+struct Module {
+    items: Vec<String>,
+    children: Vec<(String, Module)>,
+}
+
+impl Module {
+    // child は path の mod を返します（ない場合は作成します）
+    fn child(&mut self, path: &[String]) -> &mut Module {
+        let Some((first, rest)) = path.split_first() else {
+            return self;
+        };
+        let i = match self.children.iter().position(|(n, _)| n == first) {
+            Some(i) => i,
+            None => {
+                self.children.push((first.clone(), Module::default()));
+                self.children.len() - 1
+            }
+        };
+        self.children[i].1.child(rest)
+    }
+
+    // render は items と子の mod を空行で区切って出力します
+    fn render(&self, out: &mut String, depth: usize) {
+        let indent = "    ".repeat(depth);
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            for line in item.lines() {
+                if !line.is_empty() {
+                    out.push_str(&indent);
+                    out.push_str(line);
+                }
+                out.push('\n');
+            }
+        }
+        for (i, (name, child)) in self.children.iter().enumerate() {
+            if i > 0 || !self.items.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&format!("{}pub mod {} {{\n", indent, name));
+            child.render(out, depth + 1);
+            out.push_str(&format!("{}}}\n", indent));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Message,
+    Enum,
+}
+
+// Symbol は message または enum の Rust での位置です
+struct Symbol {
+    kind: Kind,
+    module: Vec<String>,
+    ident: String,
+}
+
+// Symbols は完全修飾名（`.package.Message.Nested`）から Symbol への対応です
+struct Symbols {
+    symbols: HashMap<String, Symbol>,
+}
+
+impl Symbols {
+    fn collect(files: &[ProtoFile]) -> Self {
+        let mut symbols = HashMap::new();
+        for file in files {
+            let scope: String = file
+                .package
+                .iter()
+                .flat_map(|p| p.split('.'))
+                .map(|s| format!(".{}", s))
+                .collect();
+            let module: Vec<String> = file
+                .package
+                .iter()
+                .flat_map(|p| p.split('.'))
+                .map(rust_ident)
+                .collect();
+            Self::collect_types(&mut symbols, &scope, &module, &file.messages, &file.enums);
+        }
+        Symbols { symbols }
+    }
+
+    fn collect_types(
+        symbols: &mut HashMap<String, Symbol>,
+        scope: &str,
+        module: &[String],
+        messages: &[Message],
+        enums: &[EnumDef],
+    ) {
+        for e in enums {
+            symbols.insert(
+                format!("{}.{}", scope, e.name),
+                Symbol {
+                    kind: Kind::Enum,
+                    module: module.to_vec(),
+                    ident: rust_ident(&e.name),
+                },
+            );
+        }
+        for m in messages {
+            let full = format!("{}.{}", scope, m.name);
+            symbols.insert(
+                full.clone(),
+                Symbol {
+                    kind: Kind::Message,
+                    module: module.to_vec(),
+                    ident: rust_ident(&m.name),
+                },
+            );
+            let mut nested = module.to_vec();
+            nested.push(rust_ident(&snake_case(&m.name)));
+            for f in m.all_fields() {
+                if matches!(f.ty, FieldType::Map(..)) {
+                    symbols.insert(
+                        format!("{}.{}", full, map_entry_name(&f.name)),
+                        Symbol {
+                            kind: Kind::Message,
+                            module: nested.clone(),
+                            ident: map_entry_name(&f.name),
+                        },
+                    );
+                }
+            }
+            Self::collect_types(symbols, &full, &nested, &m.messages, &m.enums);
+        }
+    }
+
+    // resolve は scope の中で name が指す型の完全修飾名を返します
+    // protobuf の規則と同じく、内側の scope から順に探します
+    fn resolve(&self, name: &str, scope: &str) -> Option<(&str, &Symbol)> {
+        if name.starts_with('.') {
+            return self
+                .symbols
+                .get_key_value(name)
+                .map(|(k, v)| (k.as_str(), v));
+        }
+        let mut scope = scope;
+        loop {
+            let candidate = format!("{}.{}", scope, name);
+            if let Some((k, v)) = self.symbols.get_key_value(&candidate) {
+                return Some((k.as_str(), v));
+            }
+            scope = &scope[..scope.rfind('.')?];
+        }
+    }
+
+    // check_recursion は message が（repeated 以外の）embedded フィールドを通して自身を含んでいないかを検査します
+    // Rust の構造体は自身を直接含むことができません
+    fn check_recursion(&self, files: &[ProtoFile]) -> Result<()> {
+        // edges は message から、repeated 以外の embedded フィールドの型への辺です
+        let mut edges: HashMap<String, Vec<(String, &Field, &str)>> = HashMap::new();
+        for file in files {
+            let scope: String = file
+                .package
+                .iter()
+                .flat_map(|p| p.split('.'))
+                .map(|s| format!(".{}", s))
+                .collect();
+            let mut stack: Vec<(String, &Message)> = file
+                .messages
+                .iter()
+                .map(|m| (format!("{}.{}", scope, m.name), m))
+                .collect();
+            while let Some((full, m)) = stack.pop() {
+                for f in m.all_fields() {
+                    if let (FieldType::Named(name), false) = (&f.ty, f.label == Label::Repeated) {
+                        if let Some((target, s)) = self.resolve(name, &full) {
+                            if s.kind == Kind::Message {
+                                edges.entry(full.clone()).or_default().push((
+                                    target.to_string(),
+                                    f,
+                                    &file.name,
+                                ));
+                            }
+                        }
+                    }
+                }
+                stack.extend(
+                    m.messages
+                        .iter()
+                        .map(|n| (format!("{}.{}", full, n.name), n)),
+                );
+            }
+        }
+        let mut starts: Vec<&String> = edges.keys().collect();
+        starts.sort();
+        for start in starts {
+            // first は start から出る辺で、循環が見つかった場合はそのフィールドを報告する
+            let mut visited = HashSet::new();
+            let mut stack: Vec<(&str, &Field, &str)> = edges[start]
+                .iter()
+                .map(|(t, f, file)| (t.as_str(), *f, *file))
+                .collect();
+            while let Some((current, first, file)) = stack.pop() {
+                if current == start {
+                    return Err(error_at(
+                        file,
+                        first.position,
+                        format!(
+                            "field `{}` makes `{}` contain itself, which is not supported. use a repeated field instead",
+                            first.name,
+                            &start[1..]
+                        ),
+                    ));
+                }
+                if !visited.insert(current) {
+                    continue;
+                }
+                for (target, _, _) in edges.get(current).into_iter().flatten() {
+                    stack.push((target, first, file));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Generator<'a> {
+    file: &'a ProtoFile,
+    symbols: &'a Symbols,
+}
+
+impl Generator<'_> {
+    fn enum_def(&self, m: &mut Module, e: &EnumDef) {
+        let mut out = String::new();
+        out.push_str("#[derive(protowirers::Proto, Debug, Clone, Copy, PartialEq, Eq)]\n");
+        out.push_str("#[repr(i32)]\n");
+        out.push_str("#[allow(non_camel_case_types, clippy::upper_case_acronyms)]\n");
+        out.push_str(&format!("pub enum {} {{\n", rust_ident(&e.name)));
+        // allow_alias で同じ値を持つ要素は、最初の要素だけを出力する
+        let mut numbers = HashSet::new();
+        let mut names = HashSet::new();
+        let mut last = 0;
+        for v in e.values.iter().filter(|v| numbers.insert(v.number)) {
+            let ident = rust_ident(&v.name);
+            out.push_str(&format!("    {} = {},\n", ident, v.number));
+            names.insert(ident);
+            last = v.number;
+        }
+        // 最後の要素は未知の値を保持する要素（#[derive(Proto)] の規約）
+        let mut other = String::from("Unrecognized");
+        while names.contains(&other) {
+            other.push('_');
+        }
+        // 暗黙の値が他の要素と重なる場合は、使われていない値を明示する
+        match last.checked_add(1).filter(|n| !numbers.contains(n)) {
+            Some(_) => out.push_str(&format!("    {}(i32),\n", other)),
+            None => {
+                let unused = (i32::MIN..).find(|n| !numbers.contains(n)).unwrap();
+                out.push_str(&format!("    {}(i32) = {},\n", other, unused));
+            }
+        }
+        out.push_str("}\n");
+        m.items.push(out);
+    }
+
+    // message は msg の構造体と、入れ子の型の mod を出力します
+    // scope は msg を含む scope の完全修飾名、module は msg を出力する mod です
+    fn message(&self, m: &mut Module, msg: &Message, scope: &str, module: &[String]) -> Result<()> {
+        let full = format!("{}.{}", scope, msg.name);
+        let ident = rust_ident(&msg.name);
+        let mut nested_module = module.to_vec();
+        nested_module.push(rust_ident(&snake_case(&msg.name)));
+
+        // oneof のフィールドは通常のフィールドとして、宣言順に出力する
+        let mut fields: Vec<&Field> = msg.all_fields().collect();
+        fields.sort_by_key(|f| (f.position.line, f.position.column));
+        let fields_empty = fields.is_empty();
+
+        let mut nested = Module::default();
+        let mut out = String::new();
+        out.push_str("#[derive(protowirers::Proto, Debug, Clone, PartialEq, Default)]\n");
+        out.push_str(&format!("pub struct {} {{\n", ident));
+        for f in fields {
+            let (attr, ty) = match &f.ty {
+                FieldType::Map(key, value) => {
+                    let entry = map_entry_name(&f.name);
+                    self.map_entry(&mut nested, &entry, *key, value, &full, &nested_module)?;
+                    (
+                        "def_type = \"embedded\", repeated".to_string(),
+                        format!("Vec<{}::{}>", nested_module.last().unwrap(), entry),
+                    )
+                }
+                ty => {
+                    let (def_type, ty) = self.field_type(f, ty, &full, module)?;
+                    let mut attr = format!("def_type = \"{}\"", def_type);
+                    if f.label == Label::Repeated {
+                        attr.push_str(", repeated");
+                        if self.is_packed(f, def_type) {
+                            attr.push_str(", packed");
+                        }
+                        (attr, format!("Vec<{}>", ty))
+                    } else {
+                        (attr, ty)
+                    }
+                }
+            };
+            // JSON のフィールド名は derive の既定の名前に頼らず、常に .proto の規則で明示する
+            let json_name = format!(", json_name = \"{}\"", f.json_name().escape_default());
+            out.push_str(&format!(
+                "    #[def(field_num = {}, {}{})]\n",
+                f.number, attr, json_name
+            ));
+            out.push_str(&format!("    pub {}: {},\n", rust_ident(&f.name), ty));
+        }
+        out.push_str("}\n");
+        if fields_empty {
+            out = out.replace("{\n}", "{}");
+        }
+        m.items.push(out);
+
+        for e in &msg.enums {
+            self.enum_def(&mut nested, e);
+        }
+        for n in &msg.messages {
+            self.message(&mut nested, n, &full, &nested_module)?;
+        }
+        if !nested.items.is_empty() || !nested.children.is_empty() {
+            m.children
+                .push((nested_module.last().unwrap().clone(), nested));
+        }
+        Ok(())
+    }
+
+    // map_entry は map のフィールドを表す entry の構造体（key = 1, value = 2）を出力します
+    fn map_entry(
+        &self,
+        m: &mut Module,
+        name: &str,
+        key: ScalarType,
+        value: &FieldType,
+        scope: &str,
+        module: &[String],
+    ) -> Result<()> {
+        let key_type = scalar_rust_type(key);
+        let value_field = Field {
+            name: "value".to_string(),
+            number: 2,
+            label: Label::None,
+            ty: value.clone(),
+            options: Vec::new(),
+            position: Position { line: 0, column: 0 },
+        };
+        let (value_def_type, value_type) = self.field_type(&value_field, value, scope, module)?;
+        m.items.push(format!(
+            "#[derive(protowirers::Proto, Debug, Clone, PartialEq, Default)]\n\
+             pub struct {} {{\n    \
+             #[def(field_num = 1, def_type = \"{}\", json_name = \"key\")]\n    \
+             pub key: {},\n    \
+             #[def(field_num = 2, def_type = \"{}\", json_name = \"value\")]\n    \
+             pub value: {},\n\
+             }}\n",
+            name,
+            key.name(),
+            key_type,
+            value_def_type,
+            value_type
+        ));
+        Ok(())
+    }
+
+    // field_type は def_type と Rust の型（repeated の場合は要素の型）を返します
+    fn field_type(
+        &self,
+        f: &Field,
+        ty: &FieldType,
+        scope: &str,
+        module: &[String],
+    ) -> Result<(&'static str, String)> {
+        match ty {
+            FieldType::Scalar(s) => Ok((s.name(), scalar_rust_type(*s).to_string())),
+            FieldType::Named(name) => {
+                let (_, symbol) = self.symbols.resolve(name, scope).ok_or_else(|| {
+                    error_at(
+                        &self.file.name,
+                        f.position,
+                        format!("cannot resolve type `{}` of field `{}`", name, f.name),
+                    )
+                })?;
+                let def_type = match symbol.kind {
+                    Kind::Message => "embedded",
+                    Kind::Enum => "enum",
+                };
+                Ok((def_type, rust_path(module, symbol)))
+            }
+            FieldType::Map(..) => Err(error_at(
+                &self.file.name,
+                f.position,
+                "map value must not be a map",
+            )),
+        }
+    }
+
+    // is_packed は repeated のフィールドを packed で書き込むかどうかを返します
+    // proto3 は packed = false を指定しない限り packed、proto2 は packed = true を指定した場合のみ packed です
+    fn is_packed(&self, f: &Field, def_type: &str) -> bool {
+        if matches!(def_type, "string" | "bytes" | "embedded") {
+            return false;
+        }
+        match f.option("packed") {
+            Some(schema::Constant::Bool(packed)) => *packed,
+            _ => self.file.syntax == Syntax::Proto3,
+        }
+    }
+}
+
+fn scalar_rust_type(s: ScalarType) -> &'static str {
+    match s {
+        ScalarType::Double => "f64",
+        ScalarType::Float => "f32",
+        ScalarType::Int32 | ScalarType::Sint32 | ScalarType::Sfixed32 => "i32",
+        ScalarType::Int64 | ScalarType::Sint64 | ScalarType::Sfixed64 => "i64",
+        ScalarType::Uint32 | ScalarType::Fixed32 => "u32",
+        ScalarType::Uint64 | ScalarType::Fixed64 => "u64",
+        ScalarType::Bool => "bool",
+        ScalarType::String => "String",
+        ScalarType::Bytes => "Vec<u8>",
+    }
+}
+
+// rust_path は module から symbol への相対パスを返します
+fn rust_path(module: &[String], symbol: &Symbol) -> String {
+    let common = module
+        .iter()
+        .zip(&symbol.module)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut path: Vec<&str> = vec!["super"; module.len() - common];
+    path.extend(symbol.module[common..].iter().map(|s| s.as_str()));
+    path.push(&symbol.ident);
+    path.join("::")
+}
+
+// map_entry_name は map のフィールドの entry の型名を返します（protoc と同じく `FieldNameEntry`）
+fn map_entry_name(field: &str) -> String {
+    let mut out = String::new();
+    let mut upper = true;
+    for c in field.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out.push_str("Entry");
+    out
+}
+
+// snake_case は message 名から入れ子の型を出力する mod の名前を返します（`HTTPRequest` は `http_request`）
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower) {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+// rust_ident は Rust のキーワードと重なる名前を raw identifier にします
+// raw identifier にできないキーワードは末尾に `_` を付けます
+fn rust_ident(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern",
+        "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "static", "struct", "trait", "true", "type", "unsafe", "use",
+        "where", "while", "abstract", "become", "box", "do", "final", "gen", "macro", "override",
+        "priv", "try", "typeof", "unsized", "virtual", "yield",
+    ];
+    match name {
+        "self" | "Self" | "super" | "crate" | "_" => format!("{}_", name),
+        _ if KEYWORDS.contains(&name) => format!("r#{}", name),
+        _ => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate_source(source: &str) -> Result<String> {
+        generate(&[schema::parse("test.proto", source)?])
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(snake_case("User"), "user");
+        assert_eq!(snake_case("HTTPRequest"), "http_request");
+        assert_eq!(snake_case("Version2Info"), "version2_info");
+        assert_eq!(map_entry_name("user_tags"), "UserTagsEntry");
+        assert_eq!(rust_ident("type"), "r#type");
+        assert_eq!(rust_ident("self"), "self_");
+        let symbol = Symbol {
+            kind: Kind::Message,
+            module: vec!["a".to_string(), "b".to_string()],
+            ident: "X".to_string(),
+        };
+        assert_eq!(rust_path(&["a".to_string(), "b".to_string()], &symbol), "X");
+        assert_eq!(
+            rust_path(&["a".to_string(), "c".to_string()], &symbol),
+            "super::b::X"
+        );
+        assert_eq!(rust_path(&[], &symbol), "a::b::X");
+    }
+
+    #[test]
+    fn test_generate_enum() {
+        let got = generate_source(
+            "syntax = \"proto3\";\nenum E {\n  option allow_alias = true;\n  A = 0;\n  B = 2;\n  C = 2;\n  D = 1;\n}",
+        )
+        .unwrap();
+        assert_eq!(
+            got,
+            "// @generated by protowirers-build. Do not edit.\n\
+             \n\
+             #[derive(protowirers::Proto, Debug, Clone, Copy, PartialEq, Eq)]\n\
+             #[repr(i32)]\n\
+             #[allow(non_camel_case_types, clippy::upper_case_acronyms)]\n\
+             pub enum E {\n    \
+             A = 0,\n    \
+             B = 2,\n    \
+             D = 1,\n    \
+             Unrecognized(i32) = -2147483648,\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_error() {
+        for (source, want) in [
+            (
+                "syntax = \"proto3\";\nmessage A {\n  B b = 1;\n}",
+                "test.proto:3:5: cannot resolve type `B` of field `b`",
+            ),
+            (
+                "syntax = \"proto3\";\nmessage A {\n  B b = 1;\n}\nmessage B {\n  A a = 1;\n}",
+                "test.proto:3:5: field `b` makes `A` contain itself, which is not supported. use a repeated field instead",
+            ),
+            (
+                "syntax = \"proto3\";\nmessage A {\n  repeated A children = 1;\n  A parent = 2;\n}",
+                "test.proto:4:5: field `parent` makes `A` contain itself, which is not supported. use a repeated field instead",
+            ),
+        ] {
+            assert_eq!(generate_source(source).unwrap_err().to_string(), want);
+        }
+    }
+}
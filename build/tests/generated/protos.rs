@@ -0,0 +1,122 @@
+// @generated by protowirers-build. Do not edit.
+
+pub mod example {
+    pub mod v1 {
+        #[derive(protowirers::Proto, Debug, Clone, PartialEq, Default)]
+        pub struct User {
+            #[def(field_num = 1, def_type = "uint64", json_name = "id")]
+            pub id: u64,
+            #[def(field_num = 2, def_type = "string", json_name = "displayName")]
+            pub name: String,
+            #[def(field_num = 3, def_type = "string", repeated, json_name = "tags")]
+            pub tags: Vec<String>,
+            #[def(field_num = 4, def_type = "sint32", repeated, packed, json_name = "scores")]
+            pub scores: Vec<i32>,
+            #[def(field_num = 5, def_type = "fixed64", repeated, json_name = "hashes")]
+            pub hashes: Vec<u64>,
+            #[def(field_num = 6, def_type = "embedded", repeated, json_name = "addresses")]
+            pub addresses: Vec<user::AddressesEntry>,
+            #[def(field_num = 7, def_type = "enum", json_name = "status")]
+            pub status: user::Status,
+            #[def(field_num = 8, def_type = "embedded", json_name = "createdAt")]
+            pub created_at: super::common::Timestamp,
+            #[def(field_num = 9, def_type = "string", json_name = "email")]
+            pub email: String,
+            #[def(field_num = 10, def_type = "bytes", json_name = "phone")]
+            pub phone: Vec<u8>,
+            #[def(field_num = 11, def_type = "embedded", repeated, json_name = "friends")]
+            pub friends: Vec<User>,
+            #[def(field_num = 12, def_type = "double", json_name = "rating")]
+            pub rating: f64,
+            #[def(field_num = 13, def_type = "bool", json_name = "type")]
+            pub r#type: bool,
+        }
+
+        #[derive(protowirers::Proto, Debug, Clone, PartialEq, Default)]
+        pub struct ListUsersResponse {
+            #[def(field_num = 1, def_type = "embedded", repeated, json_name = "users")]
+            pub users: Vec<User>,
+            #[def(field_num = 2, def_type = "enum", repeated, packed, json_name = "statuses")]
+            pub statuses: Vec<user::Status>,
+            #[def(field_num = 3, def_type = "embedded", repeated, json_name = "labels")]
+            pub labels: Vec<list_users_response::LabelsEntry>,
+        }
+
+        #[derive(protowirers::Proto, Debug, Clone, PartialEq, Default)]
+        pub struct Empty {}
+
+        pub mod user {
+            #[derive(protowirers::Proto, Debug, Clone, PartialEq, Default)]
+            pub struct AddressesEntry {
+                #[def(field_num = 1, def_type = "string", json_name = "key")]
+                pub key: String,
+                #[def(field_num = 2, def_type = "embedded", json_name = "value")]
+                pub value: Address,
+            }
+
+            #[derive(protowirers::Proto, Debug, Clone, Copy, PartialEq, Eq)]
+            #[repr(i32)]
+            #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+            pub enum Status {
+                STATUS_UNSPECIFIED = 0,
+                ACTIVE = 1,
+                SUSPENDED = 3,
+                Unrecognized(i32),
+            }
+
+            #[derive(protowirers::Proto, Debug, Clone, PartialEq, Default)]
+            pub struct Address {
+                #[def(field_num = 1, def_type = "string", json_name = "city")]
+                pub city: String,
+                #[def(field_num = 2, def_type = "enum", json_name = "kind")]
+                pub kind: address::Kind,
+            }
+
+            pub mod address {
+                #[derive(protowirers::Proto, Debug, Clone, Copy, PartialEq, Eq)]
+                #[repr(i32)]
+                #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+                pub enum Kind {
+                    KIND_UNSPECIFIED = 0,
+                    HOME = 1,
+                    WORK = 2,
+                    Unrecognized(i32),
+                }
+            }
+        }
+
+        pub mod list_users_response {
+            #[derive(protowirers::Proto, Debug, Clone, PartialEq, Default)]
+            pub struct LabelsEntry {
+                #[def(field_num = 1, def_type = "int32", json_name = "key")]
+                pub key: i32,
+                #[def(field_num = 2, def_type = "string", json_name = "value")]
+                pub value: String,
+            }
+        }
+    }
+
+    pub mod common {
+        #[derive(protowirers::Proto, Debug, Clone, PartialEq, Default)]
+        pub struct Timestamp {
+            #[def(field_num = 1, def_type = "int64", json_name = "seconds")]
+            pub seconds: i64,
+            #[def(field_num = 2, def_type = "int32", json_name = "nanos")]
+            pub nanos: i32,
+        }
+    }
+}
+
+pub mod legacy {
+    #[derive(protowirers::Proto, Debug, Clone, PartialEq, Default)]
+    pub struct Item {
+        #[def(field_num = 1, def_type = "string", json_name = "name")]
+        pub name: String,
+        #[def(field_num = 2, def_type = "sint64", json_name = "delta")]
+        pub delta: i64,
+        #[def(field_num = 3, def_type = "int32", repeated, json_name = "ids")]
+        pub ids: Vec<i32>,
+        #[def(field_num = 4, def_type = "int32", repeated, packed, json_name = "packedIds")]
+        pub packed_ids: Vec<i32>,
+    }
+}
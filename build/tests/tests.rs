@@ -0,0 +1,133 @@
+mod protos {
+    include!("generated/protos.rs");
+}
+
+use protos::example::{common, v1};
+use protowirers::wire::Proto;
+
+const PROTOS: [&str; 2] = ["tests/protos/user.proto", "tests/protos/legacy.proto"];
+
+#[test]
+fn test_generated_is_up_to_date() {
+    // tests/generated/protos.rs は PROTOS から生成したコードです
+    let files = protowirers_build::load(&PROTOS).unwrap();
+    let got = protowirers_build::generate(&files).unwrap();
+    assert_eq!(
+        got,
+        include_str!("generated/protos.rs"),
+        "tests/generated/protos.rs is out of date"
+    );
+}
+
+#[test]
+fn test_compile_protos() {
+    let out_dir = std::env::temp_dir().join(format!("protowirers-build-{}", std::process::id()));
+    std::fs::create_dir_all(&out_dir).unwrap();
+    protowirers_build::compile_protos(&PROTOS, &out_dir).unwrap();
+    let got = std::fs::read_to_string(out_dir.join(protowirers_build::OUTPUT_FILE)).unwrap();
+    assert_eq!(got, include_str!("generated/protos.rs"));
+    std::fs::remove_dir_all(&out_dir).unwrap();
+
+    let e = protowirers_build::compile_protos(&["tests/protos/missing.proto"], "").unwrap_err();
+    assert!(matches!(e, protowirers::Error::Io(_)));
+}
+
+#[test]
+fn test_compile_protos_with_includes() {
+    // import は includes のディレクトリからの相対パスで探す
+    let protos = ["tests/protos/root/api/order.proto"];
+    let e = protowirers_build::load(&protos).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "tests/protos/root/api/order.proto:5:1: cannot find import `common/types.proto`"
+    );
+
+    let files = protowirers_build::load_with_includes(&protos, &["tests/protos/root"]).unwrap();
+    assert_eq!(files.len(), 2);
+    let got = protowirers_build::generate(&files).unwrap();
+    assert!(got.contains("pub total: super::common::Money,"), "{}", got);
+
+    let out_dir =
+        std::env::temp_dir().join(format!("protowirers-build-includes-{}", std::process::id()));
+    std::fs::create_dir_all(&out_dir).unwrap();
+    protowirers_build::compile_protos_with_includes(&protos, &["tests/protos/root"], &out_dir)
+        .unwrap();
+    let written = std::fs::read_to_string(out_dir.join(protowirers_build::OUTPUT_FILE)).unwrap();
+    assert_eq!(written, got);
+    std::fs::remove_dir_all(&out_dir).unwrap();
+}
+
+#[test]
+fn test_generated_messages() {
+    let user = v1::User {
+        id: 7,
+        name: "alice".to_string(),
+        tags: vec!["admin".to_string()],
+        scores: vec![-1, 2],
+        hashes: vec![3],
+        addresses: vec![v1::user::AddressesEntry {
+            key: "home".to_string(),
+            value: v1::user::Address {
+                city: "Tokyo".to_string(),
+                kind: v1::user::address::Kind::HOME,
+            },
+        }],
+        status: v1::user::Status::SUSPENDED,
+        created_at: common::Timestamp {
+            seconds: 1,
+            nanos: 0,
+        },
+        email: "a@example.com".to_string(),
+        friends: vec![v1::User {
+            id: 8,
+            ..Default::default()
+        }],
+        r#type: true,
+        ..Default::default()
+    };
+    let bytes = user.bytes().unwrap();
+    assert_eq!(v1::User::parse(&bytes).unwrap(), user);
+
+    // enum は .proto の値で encode される
+    assert_eq!(i32::from(v1::user::Status::SUSPENDED), 3);
+    assert_eq!(v1::user::Status::from(2), v1::user::Status::Unrecognized(2));
+    // packed = false の repeated は 1 要素ずつ書き込まれる
+    let hashes = v1::User {
+        hashes: vec![1, 2],
+        ..Default::default()
+    };
+    assert_eq!(
+        hashes.bytes().unwrap(),
+        vec![0x29, 1, 0, 0, 0, 0, 0, 0, 0, 0x29, 2, 0, 0, 0, 0, 0, 0, 0]
+    );
+
+    let json = user.to_json().unwrap();
+    assert!(json.contains(r#""displayName":"alice""#), "{}", json);
+    assert!(json.contains(r#""status":"SUSPENDED""#), "{}", json);
+    assert!(json.contains(r#""type":true"#), "{}", json);
+    assert!(json.contains(r#""createdAt":{"seconds":"1"}"#), "{}", json);
+    assert_eq!(v1::User::from_json(&json).unwrap(), user);
+
+    let response = v1::ListUsersResponse {
+        users: vec![user],
+        statuses: vec![v1::user::Status::ACTIVE],
+        labels: vec![v1::list_users_response::LabelsEntry {
+            key: 1,
+            value: "one".to_string(),
+        }],
+    };
+    let bytes = response.bytes().unwrap();
+    assert_eq!(v1::ListUsersResponse::parse(&bytes).unwrap(), response);
+    assert!(v1::Empty::parse(&[]).is_ok());
+
+    let item = protos::legacy::Item {
+        name: "x".to_string(),
+        delta: -5,
+        ids: vec![1, 2],
+        packed_ids: vec![1, 2],
+    };
+    assert_eq!(
+        item.bytes().unwrap(),
+        vec![0x0a, 1, b'x', 0x10, 9, 0x18, 1, 0x18, 2, 0x22, 2, 1, 2]
+    );
+}
@@ -1,47 +1,365 @@
 use quote::quote;
+use syn::spanned::Spanned;
 pub enum Input<'a> {
     Struct(Struct<'a>),
     Enum(Enum<'a>),
+    Transparent(Transparent<'a>),
 }
 
 impl<'a> Input<'a> {
     pub fn from_syn(node: &'a syn::DeriveInput) -> syn::Result<Self> {
+        let container = ContainerAttribute::from_syn(&node.attrs)?;
+        if container.view && container.transparent {
+            return Err(syn::Error::new_spanned(
+                node,
+                "#[proto(view)] is not supported with #[proto(transparent)]",
+            ));
+        }
         match &node.data {
-            syn::Data::Struct(data) => Struct::from_syn(node, data).map(Input::Struct),
-            syn::Data::Enum(data) => Ok(Input::Enum(Enum::from_syn(node, data))),
+            syn::Data::Struct(data) if container.transparent => {
+                Transparent::from_syn(node, data).map(Input::Transparent)
+            }
+            syn::Data::Struct(data) => {
+                Struct::from_syn(node, data, container.view).map(Input::Struct)
+            }
+            _ if container.transparent => Err(syn::Error::new_spanned(
+                node,
+                "#[proto(transparent)] is only supported on Struct",
+            )),
+            _ if container.view => Err(syn::Error::new_spanned(
+                node,
+                "#[proto(view)] is only supported on Struct",
+            )),
+            syn::Data::Enum(data) => Enum::from_syn(node, data).map(Input::Enum),
             _ => Err(syn::Error::new_spanned(node, "suport data is only Sturct")),
         }
     }
 }
 
+// ContainerAttribute は 構造体・列挙型に指定された #[proto(...)] を表します
+pub struct ContainerAttribute {
+    pub transparent: bool,
+    // view は 借用したバイト列から値を読み出す View 型を生成するかどうかです
+    pub view: bool,
+}
+
+impl ContainerAttribute {
+    fn from_syn(attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut transparent: Option<()> = None;
+        let mut view: Option<()> = None;
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("proto")) {
+            attr.parse_nested_meta(|nested_meta| {
+                let (flag, name) = match nested_meta.path.get_ident() {
+                    Some(ident) if ident == "transparent" => (&mut transparent, "transparent"),
+                    Some(ident) if ident == "view" => (&mut view, "view"),
+                    _ => return Err(nested_meta.error("unsuported meta data in #[proto(...)]. ")),
+                };
+                match flag {
+                    Some(_) => {
+                        Err(nested_meta.error(format!("{name} is duplicated in #[proto(...)].")))
+                    }
+                    None => {
+                        *flag = Some(());
+                        Ok(())
+                    }
+                }
+            })?;
+        }
+        Ok(Self {
+            transparent: transparent.is_some(),
+            view: view.is_some(),
+        })
+    }
+}
+
+// Transparent は #[proto(transparent)] が指定された、フィールドを1つだけ持つ構造体を表します
+// 内部の値と同じように encode/decode されます
+pub struct Transparent<'a> {
+    pub generics: &'a syn::Generics,
+    pub member: syn::Member,
+    pub original: &'a syn::Field,
+    pub attr: Attribute,
+}
+
+impl<'a> Transparent<'a> {
+    fn from_syn(node: &'a syn::DeriveInput, data: &'a syn::DataStruct) -> syn::Result<Self> {
+        let mut fields = data.fields.iter();
+        let f = match (fields.next(), fields.next()) {
+            (Some(f), None) => f,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    node,
+                    "#[proto(transparent)] requires exactly one field",
+                ))
+            }
+        };
+        let attr = Attribute::from_syn(&f.attrs, f, true)?;
+        if attr.with.is_some() {
+            return Err(syn::Error::new_spanned(
+                f,
+                "with is not supported in #[proto(transparent)]",
+            ));
+        }
+        if !attr.allows_rust_type(&f.ty) {
+            return Err(syn::Error::new_spanned(
+                &f.ty,
+                format!(
+                    "defined def_type `{:?}` does not match this Rust type",
+                    attr.def_type,
+                ),
+            ));
+        }
+        let member = match &f.ident {
+            Some(ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(0.into()),
+        };
+        Ok(Self {
+            generics: &node.generics,
+            member,
+            original: f,
+            attr,
+        })
+    }
+
+    // build_generics は 内部の型が型パラメータを含む場合に境界を追加したジェネリクスを組み立てます
+    pub fn build_generics(&self, trait_path: &proc_macro2::TokenStream) -> syn::Generics {
+        let mut generics = self.generics.clone();
+        let ty = &self.original.ty;
+        if contains_type_param(ty, self.generics) {
+            generics
+                .make_where_clause()
+                .predicates
+                .push(syn::parse_quote! { #ty: #trait_path });
+        }
+        generics
+    }
+
+    // build_def_type_check は 内部の型が def_type と一致することを検査する文を組み立てます
+    // 型パラメータを含む場合は、境界を build_def_type_generics で追加します
+    pub fn build_def_type_check(&self) -> proc_macro2::TokenStream {
+        let ty = &self.original.ty;
+        match self.attr.def_type.marker() {
+            Some(marker) if needs_marker_check(ty) => quote::quote_spanned! {ty.span()=>
+                protowirers::parser::assert_def_type::<#ty, #marker>();
+            },
+            _ => quote! {},
+        }
+    }
+
+    // build_def_type_generics は build_generics に、内部の型の def_type の marker の境界を追加します
+    pub fn build_def_type_generics(&self, trait_path: &proc_macro2::TokenStream) -> syn::Generics {
+        let mut generics = self.build_generics(trait_path);
+        let ty = &self.original.ty;
+        if let Some(marker) = self.attr.def_type.marker() {
+            if needs_marker_check(ty) && contains_type_param(ty, self.generics) {
+                generics
+                    .make_where_clause()
+                    .predicates
+                    .push(syn::parse_quote! { #ty: protowirers::parser::HasDefType<#marker> });
+            }
+        }
+        generics
+    }
+
+    // build_marker_impl は 構造体を def_type のフィールドに使えるようにする HasDefType の実装を組み立てます
+    pub fn build_marker_impl(&self, input_indent: &syn::Ident) -> proc_macro2::TokenStream {
+        let marker = match self.attr.def_type.marker() {
+            Some(marker) => marker,
+            None => return quote! {},
+        };
+        let trait_path = quote! {protowirers::parser::HasDefType<#marker>};
+        let mut generics = self.generics.clone();
+        let ty = &self.original.ty;
+        if needs_marker_check(ty) && contains_type_param(ty, self.generics) {
+            generics
+                .make_where_clause()
+                .predicates
+                .push(syn::parse_quote! { #ty: #trait_path });
+        }
+        let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+        quote! {
+            impl #impl_generics #trait_path for #input_indent #ty_generics #where_clause {}
+        }
+    }
+
+    // build_construct は 内部の値から構造体を組み立てます
+    pub fn build_construct(&self, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let member = &self.member;
+        quote! {
+            Self { #member: #value }
+        }
+    }
+
+    // wire_kind は 内部の値がどの wire 型として扱われるかを返します
+    pub fn wire_kind(&self) -> WireKind {
+        if self.attr.repeated && self.attr.packed {
+            return WireKind::LengthDelimited;
+        }
+        self.attr.def_type.wire_kind()
+    }
+
+    // is_scalar は JsonField と TextScalar を実装するかどうかを返します
+    // embedded message と packed な repeated は、JSON や text format で scalar として扱えないため実装しません
+    pub fn is_scalar(&self) -> bool {
+        !(self.attr.repeated && self.attr.packed)
+            && !matches!(self.attr.def_type, DefType::EmbeddedMessages)
+    }
+
+    // build_input_wire_data_type は 内部の値の encode/decode に使う型を組み立てます
+    pub fn build_input_wire_data_type(&self) -> proc_macro2::TokenStream {
+        let wdt = self.attr.def_type.to_input_wire_data_type();
+        if self.attr.repeated && self.attr.packed {
+            return quote! {
+                protowirers::wire::TypeLengthDelimited::PackedRepeatedFields(
+                    protowirers::wire::AllowedPakcedType::Variant(#wdt)
+                )
+            };
+        }
+        wdt
+    }
+}
+
 pub struct Enum<'a> {
     pub variants: Vec<&'a syn::Variant>,
+    // numbers は最後の要素以外の値です
+    // 明示的な値（`Value = 5`）がない場合は、前の値に 1 を足した値（先頭は 0）です
+    pub numbers: Vec<i32>,
 }
 
 impl<'a> Enum<'a> {
-    fn from_syn(_node: &'a syn::DeriveInput, data: &'a syn::DataEnum) -> Self {
+    fn from_syn(_node: &'a syn::DeriveInput, data: &'a syn::DataEnum) -> syn::Result<Self> {
+        use std::convert::TryFrom;
         use std::iter::FromIterator;
-        Enum {
-            variants: Vec::from_iter(&data.variants),
+        let variants = Vec::from_iter(&data.variants);
+        let mut numbers = Vec::new();
+        let mut next: i64 = 0;
+        for v in variants.iter().take(variants.len().saturating_sub(1)) {
+            let number = match &v.discriminant {
+                Some((_, expr)) => enum_discriminant(expr)?,
+                None => i32::try_from(next)
+                    .map_err(|_| syn::Error::new_spanned(v, "enum value is out of range of i32"))?,
+            };
+            numbers.push(number);
+            next = number as i64 + 1;
         }
+        Ok(Enum { variants, numbers })
     }
 }
 
+// enum_discriminant は列挙型の明示的な値（整数リテラル、または負の整数リテラル）を読み込みます
+fn enum_discriminant(expr: &syn::Expr) -> syn::Result<i32> {
+    use std::convert::TryFrom;
+    let (lit, negative) = match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(lit),
+            ..
+        }) => (lit, false),
+        syn::Expr::Unary(syn::ExprUnary {
+            op: syn::UnOp::Neg(_),
+            expr,
+            ..
+        }) => match &**expr {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Int(lit),
+                ..
+            }) => (lit, true),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    expr,
+                    "enum value must be an integer literal",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                expr,
+                "enum value must be an integer literal",
+            ))
+        }
+    };
+    let v: i64 = lit.base10_parse()?;
+    i32::try_from(if negative { -v } else { v })
+        .map_err(|_| syn::Error::new_spanned(expr, "enum value is out of range of i32"))
+}
+
 pub struct Struct<'a> {
+    pub vis: &'a syn::Visibility,
+    pub generics: &'a syn::Generics,
+    // view は #[proto(view)] が指定されているかどうかです
+    pub view: bool,
+    pub style: Style,
     pub fields: Vec<Field<'a>>,
 }
 
+// Style は 構造体の形式（名前付き・タプル・ユニット）です
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Named,
+    Tuple,
+    Unit,
+}
+
 impl<'a> Struct<'a> {
-    fn from_syn(_node: &'a syn::DeriveInput, data: &'a syn::DataStruct) -> syn::Result<Self> {
+    fn from_syn(
+        node: &'a syn::DeriveInput,
+        data: &'a syn::DataStruct,
+        view: bool,
+    ) -> syn::Result<Self> {
+        let style = match &data.fields {
+            syn::Fields::Named(_) => Style::Named,
+            syn::Fields::Unnamed(_) => Style::Tuple,
+            syn::Fields::Unit => Style::Unit,
+        };
+        // View のアクセサはフィールド名を使うため、名前付きフィールドの構造体に限る
+        if view && style != Style::Named {
+            return Err(syn::Error::new_spanned(
+                node,
+                "#[proto(view)] is only supported on Struct with named fields",
+            ));
+        }
+        if view && !node.generics.params.is_empty() {
+            return Err(syn::Error::new_spanned(
+                &node.generics,
+                "#[proto(view)] is not supported on generic Struct",
+            ));
+        }
         Ok(Struct {
+            vis: &node.vis,
+            generics: &node.generics,
+            view,
+            style,
             fields: Field::from_syns(&data.fields)?,
         })
     }
-    // build_struct_fields は パース結果の値を構造体にマッピング部を組み立てます
-    pub fn build_struct_fields(&self) -> proc_macro2::TokenStream {
+
+    // build_generics は 型パラメータを含むフィールドの型に必要な境界を追加したジェネリクスを組み立てます
+    pub fn build_generics(&self) -> syn::Generics {
+        let mut generics = self.generics.clone();
+        let bounds: Vec<syn::WherePredicate> = self
+            .fields
+            .iter()
+            .filter(|f| contains_type_param(&f.original.ty, self.generics))
+            .filter_map(|f| f.build_bound())
+            .collect();
+        if !bounds.is_empty() {
+            generics.make_where_clause().predicates.extend(bounds);
+        }
+        generics
+    }
+    // build_construct は パース結果の値を構造体にマッピング部を組み立てます
+    pub fn build_construct(&self) -> proc_macro2::TokenStream {
         let build_fields = self.fields.iter().map(|f| f.build_struct_fields());
-        quote! {
-            #(#build_fields,)*
+        match self.style {
+            Style::Named => {
+                let members = self.fields.iter().map(|f| &f.member);
+                quote! {
+                    Self { #(#members: #build_fields,)* }
+                }
+            }
+            Style::Tuple => quote! {
+                Self( #(#build_fields,)* )
+            },
+            Style::Unit => quote! { Self },
         }
     }
 
@@ -63,11 +381,107 @@ impl<'a> Struct<'a> {
         }
     }
 
-    #[allow(dead_code)]
-    pub fn build_gen_wirestructs(&self) -> proc_macro2::TokenStream {
-        let build_gen_wirestructs = self.fields.iter().map(|f| f.build_gen_wirestructs());
+    // build_def_type_checks は 組み込み以外のフィールドの型が def_type と一致するかを検査する処理を組み立てます
+    pub fn build_def_type_checks(&self) -> proc_macro2::TokenStream {
+        let checks = self.fields.iter().map(|f| f.build_def_type_check());
+        quote! {
+            #(#checks)*
+        }
+    }
+
+    // build_merge は merge の処理を組み立てます
+    pub fn build_merge(&self) -> proc_macro2::TokenStream {
+        if self.fields.is_empty() {
+            return quote! {
+                let _ = other;
+            };
+        }
+        let build_merge = self.fields.iter().map(|f| f.build_merge());
+        quote! {
+            #(#build_merge)*
+        }
+    }
+
+    // build_encoded_len は encode 後のバイト数を計算する処理を組み立てます
+    pub fn build_encoded_len(&self) -> proc_macro2::TokenStream {
+        let build_encoded_len = self.fields.iter().map(|f| f.build_encoded_len());
+        quote! {
+            0 #(+ #build_encoded_len)*
+        }
+    }
+
+    // build_descriptor_fields は 各フィールドの定義（FieldDescriptor）を組み立てます
+    pub fn build_descriptor_fields(&self) -> proc_macro2::TokenStream {
+        let fields = self.fields.iter().map(|f| f.build_descriptor());
+        quote! {
+            vec![#(#fields,)*]
+        }
+    }
+
+    // build_to_json は 各フィールドを JSON の object（__object）に追加する処理を組み立てます
+    pub fn build_to_json(&self) -> proc_macro2::TokenStream {
+        let fields = self.fields.iter().map(|f| f.build_to_json());
+        quote! {
+            #(#fields)*
+        }
+    }
+
+    // build_from_json_cases は JSON の key に対応するフィールドに値を設定する match の部分を組み立てます
+    pub fn build_from_json_cases(&self) -> proc_macro2::TokenStream {
+        let cases = self.fields.iter().map(|f| f.build_from_json_case());
         quote! {
-            #(#build_gen_wirestructs,)*
+            #(#cases)*
+        }
+    }
+
+    // build_write_text は 各フィールドを text format で書き込む処理を組み立てます
+    pub fn build_write_text(&self) -> proc_macro2::TokenStream {
+        let fields = self.fields.iter().map(|f| f.build_write_text());
+        quote! {
+            #(#fields)*
+        }
+    }
+
+    // build_from_text_cases は text format のフィールド名に対応するフィールドに値を設定する match の部分を組み立てます
+    pub fn build_from_text_cases(&self) -> proc_macro2::TokenStream {
+        let cases = self.fields.iter().map(|f| f.build_from_text_case());
+        quote! {
+            #(#cases)*
+        }
+    }
+
+    // build_view_accessors は View の各フィールドのアクセサを組み立てます
+    pub fn build_view_accessors(&self) -> proc_macro2::TokenStream {
+        let accessors = self.fields.iter().map(|f| f.build_view_accessor());
+        quote! {
+            #(#accessors)*
+        }
+    }
+
+    // build_encode_reversed は buf に逆順で書き込む処理を組み立てます
+    // 反転後にフィールドの定義順となるよう、後ろのフィールドから書き込みます
+    // options.sorts_by_field_number() の場合は field number の昇順となるように書き込みます
+    pub fn build_encode_reversed(&self) -> proc_macro2::TokenStream {
+        let build_encode_reversed = self.fields.iter().rev().map(|f| f.build_encode_reversed());
+        let mut sorted: Vec<&Field> = self.fields.iter().collect();
+        sorted.sort_by_key(|f| f.attr.filed_num);
+        // 定義順が field number の昇順であれば、書き込み順は変わらない
+        if sorted
+            .iter()
+            .zip(&self.fields)
+            .all(|(a, b)| a.attr.filed_num == b.attr.filed_num)
+        {
+            return quote! {
+                #(#build_encode_reversed)*
+            };
+        }
+        let build_sorted = sorted.iter().rev().map(|f| f.build_encode_reversed());
+        quote! {
+            if options.sorts_by_field_number() {
+                #(#build_sorted)*
+            } else {
+                #(#build_encode_reversed)*
+            }
         }
     }
 }
@@ -75,16 +489,24 @@ impl<'a> Struct<'a> {
 pub struct Field<'a> {
     pub original: &'a syn::Field,
     pub attr: Attribute,
+    // member は フィールドへのアクセスに使う名前（タプル構造体の場合は番号）です
+    pub member: syn::Member,
+    // binding は パース処理でフィールドの値を保持する変数名です
+    pub binding: syn::Ident,
 }
 impl<'a> Field<'a> {
     fn from_syns(data: &'a syn::Fields) -> syn::Result<Vec<Self>> {
-        data.iter().map(Field::from_syn).collect()
+        data.iter()
+            .enumerate()
+            .map(|(index, f)| Field::from_syn(index, f))
+            .collect()
     }
-    fn from_syn(f: &'a syn::Field) -> syn::Result<Self> {
+    fn from_syn(index: usize, f: &'a syn::Field) -> syn::Result<Self> {
         // TODO 番号がだぶってないかチェックする
-        let attr = Attribute::from_syn(&f.attrs, f)?;
+        let attr = Attribute::from_syn(&f.attrs, f, false)?;
         let ty = &f.ty;
-        if !attr.allows_rust_type(ty) {
+        // with が指定されている場合、型の変換は指定されたモジュールに任せる
+        if attr.with.is_none() && !attr.allows_rust_type(ty) {
             return Err(syn::Error::new_spanned(
                 ty,
                 format!(
@@ -93,78 +515,881 @@ impl<'a> Field<'a> {
                 ),
             ));
         }
-        Ok(Self { original: f, attr })
+        let (member, binding) = match &f.ident {
+            Some(ident) => (syn::Member::Named(ident.clone()), ident.clone()),
+            None => (
+                syn::Member::Unnamed(index.into()),
+                quote::format_ident!("field_{}", index),
+            ),
+        };
+        Ok(Self {
+            original: f,
+            attr,
+            member,
+            binding,
+        })
+    }
+    // repeated_element は 要素ごとに encode/decode する repeated フィールドの要素の型を返します
+    // with が指定されている場合や Vec 以外の型の場合は、フィールド全体を 1 つの値として扱うため None を返します
+    fn repeated_element(&self) -> Option<&'a syn::Type> {
+        if !self.attr.repeated || self.attr.with.is_some() {
+            return None;
+        }
+        vec_element(&self.original.ty)
+    }
+
+    // def_type_checked は def_type との対応を compile 時に検査する型を返します
+    // 組み込みの型は from_syn で検査済みのため、それ以外の型（#[proto(transparent)] な型など）を返します
+    fn def_type_checked(&self) -> Option<&'a syn::Type> {
+        if self.attr.with.is_some() {
+            return None;
+        }
+        let ty = if self.attr.repeated {
+            vec_element(&self.original.ty)?
+        } else {
+            &self.original.ty
+        };
+        needs_marker_check(ty).then_some(ty)
     }
+
+    // build_def_type_check は フィールドの型が def_type と一致することを検査する文を組み立てます
+    pub fn build_def_type_check(&self) -> proc_macro2::TokenStream {
+        match (self.def_type_checked(), self.attr.def_type.marker()) {
+            (Some(ty), Some(marker)) => quote::quote_spanned! {ty.span()=>
+                protowirers::parser::assert_def_type::<#ty, #marker>();
+            },
+            _ => quote! {},
+        }
+    }
+
+    // build_bound は フィールドの型が満たすべき境界を組み立てます
+    // with が指定されている場合、型の変換は指定されたモジュールに任せるため境界は不要です
+    fn build_bound(&self) -> Option<syn::WherePredicate> {
+        if self.attr.with.is_some() {
+            return None;
+        }
+        // repeated は要素の型に境界を追加する
+        // embedded message 以外は JSON と text format への変換に JsonField と TextScalar を使う
+        let json = if matches!(self.attr.def_type, DefType::EmbeddedMessages) {
+            quote! {}
+        } else {
+            quote! { + protowirers::json::JsonField + protowirers::text::TextScalar }
+        };
+        if let Some(elem) = self.repeated_element() {
+            let value_trait = self.attr.def_type.value_trait();
+            let marker = self.build_marker_bound();
+            return Some(syn::parse_quote! {
+                #elem: #value_trait #json #marker
+            });
+        }
+        let ty = &self.original.ty;
+        let (value_trait, json) = if self.attr.repeated && self.attr.packed {
+            (
+                quote! {protowirers::parser::LengthDelimitedToValue},
+                quote! {},
+            )
+        } else {
+            (self.attr.def_type.value_trait(), json)
+        };
+        // repeated, embedded 以外は merge 時に default 値と比較する
+        let compare =
+            if self.attr.repeated || matches!(self.attr.def_type, DefType::EmbeddedMessages) {
+                quote! {}
+            } else {
+                quote! { + PartialEq }
+            };
+        let marker = self.build_marker_bound();
+        Some(syn::parse_quote! {
+            #ty: #value_trait + Default #compare #json #marker
+        })
+    }
+
+    // build_marker_bound は 型パラメータを含むフィールドの型に def_type の marker の境界を組み立てます
+    fn build_marker_bound(&self) -> proc_macro2::TokenStream {
+        match (self.def_type_checked(), self.attr.def_type.marker()) {
+            (Some(_), Some(marker)) => quote! { + protowirers::parser::HasDefType<#marker> },
+            _ => quote! {},
+        }
+    }
+
     fn build_struct_fields(&self) -> proc_macro2::TokenStream {
-        let filed_indent = &self.original.ident;
+        let binding = &self.binding;
+        // with が指定されている場合、wire 上の default 値から変換する
+        if let Some(with) = &self.attr.with {
+            return quote! {
+                match #binding {
+                    Some(v) => v,
+                    None => #with::from_wire(Default::default())?,
+                }
+            };
+        }
         // すべてOptionalとして扱い、値が設定されていないフィールドはdefault値にする
         quote! {
-            #filed_indent: #filed_indent.unwrap_or_default()
+            #binding.unwrap_or_default()
         }
     }
+
+    // wrap_from_wire は with が指定されている場合、wire から変換した値を with のモジュールで変換します
+    fn wrap_from_wire(&self, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        match &self.attr.with {
+            Some(with) => quote! { #with::from_wire(#value)? },
+            None => value,
+        }
+    }
+
     fn build_declare_for_init(&self) -> proc_macro2::TokenStream {
-        let f = self.original;
-        let filed_indent = &f.ident;
-        let filed_ty = &f.ty;
+        let binding = &self.binding;
+        let filed_ty = &self.original.ty;
         // Noneで初期化
         quote! {
-            let mut #filed_indent: Option<#filed_ty> = None;
+            let mut #binding: Option<#filed_ty> = None;
         }
     }
 
+    // build_match_case は (field number, wire type) が一致した場合に、
+    // 入力から値を読み込みフィールドに設定する処理を組み立てます
     fn build_match_case(&self) -> proc_macro2::TokenStream {
-        let filed_indent = &self.original.ident;
+        let binding = &self.binding;
         let a = &self.attr;
         let fieild_num = a.filed_num as u128;
-        let wire_data_type = a.def_type.to_input_wire_data_type();
+        if self.repeated_element().is_some() {
+            return self.build_repeated_match_case();
+        }
+        let segment = self.build_segment();
+        let wdt = self.build_wire_data_type();
 
         // repeated & packed は LengthDelimited として扱う
-        if self.attr.repeated && self.attr.packed {
+        let kind = if a.repeated && a.packed {
+            WireKind::LengthDelimited
+        } else {
+            a.def_type.wire_kind()
+        };
+        let wire_type = kind.type_number();
+        let value = match kind {
+            WireKind::Varint => quote! {
+                protowirers::parser::VariantToValue::from_valint(__reader.read_varint()?, #wdt)?
+            },
+            WireKind::Bit64 => quote! {
+                protowirers::parser::Bit64ToValue::from_bit64(__reader.read_bit64()?, #wdt)?
+            },
+            WireKind::Bit32 => quote! {
+                protowirers::parser::Bit32ToValue::from_bit64(__reader.read_bit32()?, #wdt)?
+            },
+            WireKind::LengthDelimited => {
+                let value = self.wrap_from_wire(quote! {
+                    protowirers::parser::LengthDelimitedToValue::from_length_delimited(__v, #wdt)?
+                });
+                let set = self.build_set_length_delimited(value, wdt);
+                return quote! {
+                    (#fieild_num, #wire_type) => {
+                        #segment
+                        let __v = __reader.read_length_delimited()?;
+                        __value_offset = __reader.position() - __v.len();
+                        #set
+                    }
+                };
+            }
+        };
+        let value = self.wrap_from_wire(value);
+        quote! {
+            (#fieild_num, #wire_type) => {
+                #segment
+                #binding = Some(#value);
+            }
+        }
+    }
+
+    // name は エラーのパスや定義で使うフィールド名（タプル構造体の場合は番号）を返します
+    fn name(&self) -> String {
+        use syn::ext::IdentExt;
+        match &self.member {
+            syn::Member::Named(ident) => ident.unraw().to_string(),
+            syn::Member::Unnamed(index) => index.index.to_string(),
+        }
+    }
+
+    // build_descriptor は フィールドの定義（FieldDescriptor）を組み立てます
+    // embedded message は、フィールドの型（repeated の場合は要素の型）の定義を返す関数を持ちます
+    fn build_descriptor(&self) -> proc_macro2::TokenStream {
+        let a = &self.attr;
+        let name = self.name();
+        let number = a.filed_num as u128;
+        let ty = a.def_type.to_field_type();
+        let (repeated, packed) = (a.repeated, a.packed);
+        let message = match self.repeated_element() {
+            _ if a.with.is_some() || !matches!(a.def_type, DefType::EmbeddedMessages) => {
+                quote! { None }
+            }
+            Some(elem) => quote! { Some(<#elem as protowirers::wire::Proto>::descriptor) },
+            None => {
+                let ty = &self.original.ty;
+                quote! { Some(<#ty as protowirers::wire::Proto>::descriptor) }
+            }
+        };
+        quote! {
+            protowirers::descriptor::FieldDescriptor {
+                name: #name,
+                number: #number,
+                ty: #ty,
+                repeated: #repeated,
+                packed: #packed,
+                message: #message,
+            }
+        }
+    }
+
+    // json_name は JSON のフィールド名を返します
+    // json_name が指定されていない場合は、フィールド名を lowerCamelCase にしたものです
+    fn json_name(&self) -> String {
+        match &self.attr.json_name {
+            Some(name) => name.clone(),
+            None => lower_camel_case(&self.name()),
+        }
+    }
+
+    // is_json_array は JSON で array として扱うフィールドかどうかを返します
+    fn is_json_array(&self) -> bool {
+        self.repeated_element().is_some() || (self.attr.repeated && self.attr.packed)
+    }
+
+    // build_to_json は フィールドを JSON の値に変換して __object に追加する処理を組み立てます
+    // default 値のフィールド（空の repeated、すべてのフィールドが省略された embedded message を含む）は追加しません
+    fn build_to_json(&self) -> proc_macro2::TokenStream {
+        let member = &self.member;
+        let json_name = self.json_name();
+        let to_json = if matches!(self.attr.def_type, DefType::EmbeddedMessages) {
+            quote! { protowirers::wire::Proto::to_json_value }
+        } else {
+            quote! { protowirers::json::JsonField::to_json_value }
+        };
+        let value = match &self.attr.with {
+            Some(with) => quote! { &#with::to_wire(&self.#member)? },
+            None => quote! { &self.#member },
+        };
+        let push = quote! {
+            __object.push((#json_name.to_string(), __v));
+        };
+        let write = if self.is_json_array() {
+            quote! {
+                if value.into_iter().next().is_some() {
+                    let __v = protowirers::json::in_field(
+                        #json_name,
+                        protowirers::json::repeated_to_json(value, #to_json),
+                    )?;
+                    #push
+                }
+            }
+        } else if matches!(self.attr.def_type, DefType::EmbeddedMessages) {
+            quote! {
+                match protowirers::json::in_field(#json_name, #to_json(value))? {
+                    protowirers::json::JsonValue::Object(o) if o.is_empty() => (),
+                    __v => {
+                        #push
+                    }
+                }
+            }
+        } else {
+            quote! {
+                if !protowirers::json::JsonField::is_json_default(value) {
+                    let __v = protowirers::json::in_field(#json_name, #to_json(value))?;
+                    #push
+                }
+            }
+        };
+        quote! {
+            {
+                let value = #value;
+                #write
+            }
+        }
+    }
+
+    // build_from_json_case は JSON の key がフィールドの JSON の名前またはフィールド名と一致した場合に、
+    // 値を変換してフィールドに設定する処理を組み立てます
+    // null は値がないものとして扱います
+    fn build_from_json_case(&self) -> proc_macro2::TokenStream {
+        let binding = &self.binding;
+        let json_name = self.json_name();
+        let name = self.name();
+        let keys = if json_name == name {
+            quote! { #json_name }
+        } else {
+            quote! { #json_name | #name }
+        };
+        let from_json = if matches!(self.attr.def_type, DefType::EmbeddedMessages) {
+            quote! { protowirers::wire::Proto::from_json_value }
+        } else {
+            quote! { protowirers::json::JsonField::from_json_value }
+        };
+        let value = if self.is_json_array() {
+            quote! { protowirers::json::repeated_from_json(__value, #from_json) }
+        } else {
+            quote! { #from_json(__value) }
+        };
+        // with が指定されている場合、JSON から変換した wire の値を with のモジュールで変換する
+        let value = match &self.attr.with {
+            Some(with) => quote! {
+                (|| -> protowirers::Result<_> { #with::from_wire(#value?) })()
+            },
+            None => value,
+        };
+        quote! {
+            #keys => {
+                if !__value.is_null() {
+                    #binding = Some(protowirers::json::in_field(#json_name, #value)?);
+                }
+            }
+        }
+    }
+
+    // build_write_text は フィールドを text format で __w に書き込む処理を組み立てます
+    // repeated な scalar は `name: [v1, v2]`、repeated な embedded message は要素ごとに `name { ... }` と書き込みます
+    // default 値のフィールド（空の repeated、すべてのフィールドが省略された embedded message を含む）は書き込みません
+    fn build_write_text(&self) -> proc_macro2::TokenStream {
+        let member = &self.member;
+        let name = self.name();
+        let embedded = matches!(self.attr.def_type, DefType::EmbeddedMessages);
+        let value = match &self.attr.with {
+            Some(with) => quote! { &#with::to_wire(&self.#member)? },
+            None => quote! { &self.#member },
+        };
+        let write = match (self.is_json_array(), embedded) {
+            (true, true) => quote! {
+                for v in value {
+                    let __mark = __w.begin_message(#name);
+                    protowirers::wire::Proto::write_text(v, __w)?;
+                    __w.end_message(__mark, true);
+                }
+            },
+            (true, false) => quote! {
+                if value.into_iter().next().is_some() {
+                    __w.list(#name, value);
+                }
+            },
+            (false, true) => quote! {
+                let __mark = __w.begin_message(#name);
+                protowirers::wire::Proto::write_text(value, __w)?;
+                __w.end_message(__mark, false);
+            },
+            (false, false) => quote! {
+                if !protowirers::text::TextScalar::is_text_default(value) {
+                    __w.field(#name);
+                    protowirers::text::TextScalar::write_text(value, __w);
+                }
+            },
+        };
+        quote! {
+            {
+                let value = #value;
+                #write
+            }
+        }
+    }
+
+    // build_from_text_case は text format のフィールド名がフィールド名と一致した場合に、
+    // 値を変換してフィールドに設定する処理を組み立てます
+    // repeated は同じフィールドが複数回現れた場合に末尾に追加し、それ以外は置き換えます
+    fn build_from_text_case(&self) -> proc_macro2::TokenStream {
+        let binding = &self.binding;
+        let name = self.name();
+        let embedded = matches!(self.attr.def_type, DefType::EmbeddedMessages);
+        let from_value = |ty: proc_macro2::TokenStream| {
+            if embedded {
+                quote! {
+                    |v| protowirers::text::message(v, <#ty as protowirers::wire::Proto>::from_text_fields)
+                }
+            } else {
+                quote! { <#ty as protowirers::text::TextScalar>::from_text_value }
+            }
+        };
+        if let Some(elem) = self.repeated_element() {
+            let from_value = from_value(quote! { #elem });
             return quote! {
-                (#fieild_num, protowirers::wire::WireData::LengthDelimited(v)) => {
-                    // #filed_indent = Some(#def_type(v)?);
-                    let vv = protowirers::wire::TypeLengthDelimited::PackedRepeatedFields(
-                        protowirers::wire::AllowedPakcedType::Variant(#wire_data_type)
-                    );
-                    #filed_indent = Some(v.parse(vv)?);
+                #name => {
+                    #binding.get_or_insert_with(Default::default).extend(protowirers::text::at(
+                        __field.value_position,
+                        protowirers::text::repeated(&__field.value, #from_value),
+                    )?);
                 }
             };
         }
-
-        let mach_wire_type = a.def_type.to_corresponding_wire_type();
+        let from_value = from_value(quote! { _ });
+        let value = if self.is_json_array() {
+            quote! { protowirers::text::repeated(&__field.value, #from_value) }
+        } else {
+            quote! { (#from_value)(&__field.value) }
+        };
+        // with が指定されている場合、text format から変換した wire の値を with のモジュールで変換する
+        let value = match &self.attr.with {
+            Some(with) => quote! {
+                (|| -> protowirers::Result<_> { #with::from_wire(#value?) })()
+            },
+            None => value,
+        };
         quote! {
-            (#fieild_num, #mach_wire_type(v)) => {
-                // #filed_indent = Some(#def_type(v)?);
-                #filed_indent = Some(v.parse(#wire_data_type)?);
+            #name => {
+                #binding = Some(protowirers::text::at(__field.value_position, #value)?);
             }
         }
     }
 
-    fn build_gen_wirestructs(&self) -> proc_macro2::TokenStream {
-        let filed_indent = &self.original.ident;
+    // build_segment は 読み込み中のフィールドを記録する処理を組み立てます
+    // 記録したフィールドは、decode に失敗した場合にエラーのパスとして使われます
+    fn build_segment(&self) -> proc_macro2::TokenStream {
+        let name = self.name();
+        let number = self.attr.filed_num as u128;
+        quote! {
+            __field = Some(protowirers::error::FieldSegment { name: #name, number: #number });
+        }
+    }
+
+    // build_repeated_match_case は repeated フィールドの要素を読み込み、末尾に追加する処理を組み立てます
+    // scalar は packed と unpacked のどちらの形式でも読み込みます
+    fn build_repeated_match_case(&self) -> proc_macro2::TokenStream {
+        let binding = &self.binding;
         let a = &self.attr;
         let fieild_num = a.filed_num as u128;
-        let wt = a.def_type.to_corresponding_wire_type();
+        let segment = self.build_segment();
         let wdt = a.def_type.to_input_wire_data_type();
-        if self.attr.repeated && self.attr.packed {
+        let kind = a.def_type.wire_kind();
+        let wire_type = kind.type_number();
+        let values = quote! { #binding.get_or_insert_with(Default::default) };
+        let (value, read_packed) = match kind {
+            WireKind::Varint => (
+                quote! {
+                    protowirers::parser::VariantToValue::from_valint(__reader.read_varint()?, #wdt)?
+                },
+                quote! { protowirers::repeated::read_packed_varint },
+            ),
+            WireKind::Bit64 => (
+                quote! {
+                    protowirers::parser::Bit64ToValue::from_bit64(__reader.read_bit64()?, #wdt)?
+                },
+                quote! { protowirers::repeated::read_packed_bit64 },
+            ),
+            WireKind::Bit32 => (
+                quote! {
+                    protowirers::parser::Bit32ToValue::from_bit64(__reader.read_bit32()?, #wdt)?
+                },
+                quote! { protowirers::repeated::read_packed_bit32 },
+            ),
+            WireKind::LengthDelimited => {
+                return quote! {
+                    (#fieild_num, #wire_type) => {
+                        #segment
+                        let __v = __reader.read_length_delimited()?;
+                        __value_offset = __reader.position() - __v.len();
+                        #values.push(
+                            protowirers::parser::LengthDelimitedToValue::from_length_delimited(__v, #wdt)?,
+                        );
+                    }
+                };
+            }
+        };
+        quote! {
+            (#fieild_num, #wire_type) => {
+                #segment
+                let __v = #value;
+                #values.push(__v);
+            }
+            (#fieild_num, 2) => {
+                #segment
+                #read_packed(__reader.read_length_delimited()?, #wdt, #values)?;
+            }
+        }
+    }
+
+    // build_set_length_delimited は LengthDelimited の値を設定する処理を組み立てます
+    // 既に値がある場合（同じフィールドが複数回現れた場合）は統合します
+    fn build_set_length_delimited(
+        &self,
+        value: proc_macro2::TokenStream,
+        ty: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let binding = &self.binding;
+        // with が指定されている場合は置き換える
+        if self.attr.with.is_some() {
             return quote! {
-                protowirers::wire::WireStruct::new(
+                #binding = Some(#value);
+            };
+        }
+        quote! {
+            match #binding.as_mut() {
+                Some(x) => protowirers::parser::LengthDelimitedToValue::merge_length_delimited(x, __v, #ty)?,
+                None => #binding = Some(#value),
+            }
+        }
+    }
+
+    // build_merge は other のフィールドを self に統合する処理を組み立てます
+    fn build_merge(&self) -> proc_macro2::TokenStream {
+        let member = &self.member;
+        if let Some(with) = &self.attr.with {
+            // wire 上で default 値となる場合は値がないものとして扱う
+            return quote! {
+                match #with::to_wire(&other.#member) {
+                    Ok(w) if is_default(&w) => (),
+                    _ => self.#member = other.#member,
+                }
+            };
+        }
+        if self.attr.repeated {
+            return quote! {
+                self.#member.extend(other.#member);
+            };
+        }
+        if matches!(self.attr.def_type, DefType::EmbeddedMessages) {
+            return quote! {
+                protowirers::wire::Proto::merge(&mut self.#member, other.#member);
+            };
+        }
+        quote! {
+            if !is_default(&other.#member) {
+                self.#member = other.#member;
+            }
+        }
+    }
+
+    // build_encoded_len は フィールドの encode 後のバイト数を計算する処理を組み立てます
+    // 値の変換に失敗する場合は 0 とします（bytes ではエラーになります）
+    fn build_encoded_len(&self) -> proc_macro2::TokenStream {
+        let a = &self.attr;
+        let fieild_num = a.filed_num as u128;
+        let wdt = self.build_wire_data_type();
+        let packed = a.packed;
+        let len = if self.repeated_element().is_some() {
+            let wdt = a.def_type.to_input_wire_data_type();
+            match a.def_type.wire_kind() {
+                WireKind::Varint => quote! {
+                    protowirers::repeated::varint_elements_len(#fieild_num, value, #wdt, #packed)
+                },
+                WireKind::Bit64 => quote! {
+                    protowirers::repeated::bit64_elements_len(#fieild_num, value, #packed)
+                },
+                WireKind::Bit32 => quote! {
+                    protowirers::repeated::bit32_elements_len(#fieild_num, value, #packed)
+                },
+                WireKind::LengthDelimited => quote! {
+                    protowirers::repeated::length_delimited_elements_len(#fieild_num, value, #wdt)
+                },
+            }
+        } else if a.repeated && a.packed {
+            quote! {
+                protowirers::encode::length_delimited_field_len(
                     #fieild_num,
-                    protowirers::wire::WireData::LengthDelimited(protowirers::parser::Parser::from(
-                        self.#filed_indent.clone(),
-                        protowirers::wire::TypeLengthDelimited::PackedRepeatedFields(protowirers::wire::AllowedPakcedType::Variant(
-                            #wdt,
-                        )),
-                    )?),
+                    protowirers::parser::LengthDelimitedToValue::length_delimited_len(value, #wdt),
                 )
+            }
+        } else {
+            match a.def_type.wire_kind() {
+                WireKind::Varint => quote! {
+                    protowirers::encode::variant_field_len(
+                        #fieild_num,
+                        protowirers::parser::VariantToValue::to_variant(value, #wdt)
+                            .unwrap_or_default(),
+                    )
+                },
+                WireKind::Bit64 => quote! {
+                    protowirers::encode::bit64_field_len(
+                        #fieild_num,
+                        protowirers::parser::Bit64ToValue::to_bit64(value, #wdt)
+                            .unwrap_or_default(),
+                    )
+                },
+                WireKind::Bit32 => quote! {
+                    protowirers::encode::bit32_field_len(
+                        #fieild_num,
+                        protowirers::parser::Bit32ToValue::to_bit64(value, #wdt)
+                            .unwrap_or_default(),
+                    )
+                },
+                WireKind::LengthDelimited => quote! {
+                    protowirers::encode::length_delimited_field_len(
+                        #fieild_num,
+                        protowirers::parser::LengthDelimitedToValue::length_delimited_len(
+                            value, #wdt,
+                        ),
+                    )
+                },
+            }
+        };
+        let member = &self.member;
+        match &a.with {
+            Some(with) => quote! {
+                match #with::to_wire(&self.#member) {
+                    Ok(ref value) => #len,
+                    Err(_) => 0,
+                }
+            },
+            None => quote! {
+                {
+                    let value = &self.#member;
+                    #len
+                }
+            },
+        }
+    }
+
+    // build_view_accessor は View からフィールドの値を読み出すアクセサを組み立てます
+    // アクセサは呼び出されるたびに元のバイト列からフィールドを探して decode します
+    // string と bytes は元のバイト列を借用し、embedded は子メッセージの View を返します
+    // packed は要素を順に decode するイテレータを返します
+    fn build_view_accessor(&self) -> proc_macro2::TokenStream {
+        let a = &self.attr;
+        let vis = &self.original.vis;
+        let name = &self.binding;
+        let ty = &self.original.ty;
+        let fieild_num = a.filed_num as u128;
+        let wdt = self.build_wire_data_type();
+        if let Some(elem) = self.repeated_element() {
+            let (ret, body) = self.build_view_repeated(elem);
+            return quote! {
+                #vis fn #name(&self) -> protowirers::Result<#ret> {
+                    #body
+                }
             };
         }
-        // TODO 暫定として一律cloneするが、要検討。
+        let kind = if a.repeated && a.packed {
+            WireKind::LengthDelimited
+        } else {
+            a.def_type.wire_kind()
+        };
+
+        // with が指定されている場合、wire の値を decode した後に with のモジュールで変換する
+        if let Some(with) = &a.with {
+            let value = match kind {
+                WireKind::Varint => {
+                    quote! { protowirers::view::varint(&self.segments, #fieild_num, #wdt)? }
+                }
+                WireKind::Bit64 => {
+                    quote! { protowirers::view::bit64(&self.segments, #fieild_num, #wdt)? }
+                }
+                WireKind::Bit32 => {
+                    quote! { protowirers::view::bit32(&self.segments, #fieild_num, #wdt)? }
+                }
+                // parse と同様に、with の場合は最後の値で置き換える
+                WireKind::LengthDelimited => quote! {
+                    match protowirers::view::find_last(&self.segments, #fieild_num, 2)? {
+                        Some(protowirers::wire::WireDataRef::LengthDelimited(v)) => {
+                            protowirers::parser::LengthDelimitedToValue::from_length_delimited(v, #wdt)?
+                        }
+                        _ => Default::default(),
+                    }
+                },
+            };
+            return quote! {
+                #vis fn #name(&self) -> protowirers::Result<#ty> {
+                    #with::from_wire(#value)
+                }
+            };
+        }
+
+        let (ret, body) = match kind {
+            WireKind::Varint => (
+                quote! { #ty },
+                quote! { protowirers::view::varint(&self.segments, #fieild_num, #wdt) },
+            ),
+            WireKind::Bit64 => (
+                quote! { #ty },
+                quote! { protowirers::view::bit64(&self.segments, #fieild_num, #wdt) },
+            ),
+            WireKind::Bit32 => (
+                quote! { #ty },
+                quote! { protowirers::view::bit32(&self.segments, #fieild_num, #wdt) },
+            ),
+            _ if matches!(a.def_type, DefType::EmbeddedMessages) => (
+                quote! { <#ty as protowirers::view::HasView>::View<'a> },
+                quote! { protowirers::view::message(&self.segments, #fieild_num) },
+            ),
+            _ if matches!(a.def_type, DefType::String) && is_ident(ty, "String") => (
+                quote! { &'a str },
+                quote! { protowirers::view::find_str(&self.segments, #fieild_num) },
+            ),
+            _ if matches!(a.def_type, DefType::Bytes)
+                && vec_element(ty).is_some_and(|t| is_ident(t, "u8")) =>
+            {
+                (
+                    quote! { &'a [u8] },
+                    quote! { protowirers::view::find_bytes(&self.segments, #fieild_num) },
+                )
+            }
+            // #[proto(transparent)] な型などは、値を複製して decode する
+            _ => self.build_view_owned_length_delimited(),
+        };
         quote! {
-            protowirers::wire::WireStruct::new(
-                #fieild_num,
-                #wt(protowirers::parser::Parser::from(self.#filed_indent.clone(), #wdt)?),
+            #vis fn #name(&self) -> protowirers::Result<#ret> {
+                #body
+            }
+        }
+    }
+
+    // build_view_repeated は repeated フィールドの要素を順に decode するイテレータを返すアクセサの
+    // 戻り値の型と処理を組み立てます
+    fn build_view_repeated(
+        &self,
+        elem: &syn::Type,
+    ) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+        let a = &self.attr;
+        let fieild_num = a.filed_num as u128;
+        let wdt = a.def_type.to_input_wire_data_type();
+        let packed_iter = |kind: proc_macro2::TokenStream| {
+            (
+                quote! { protowirers::view::PackedIter<'a, #elem, #kind> },
+                quote! {
+                    Ok(protowirers::view::PackedIter::new(&self.segments, #fieild_num, #wdt))
+                },
             )
+        };
+        let (item, map) = match a.def_type.wire_kind() {
+            WireKind::Varint => return packed_iter(quote! { protowirers::wire::TypeVairant }),
+            WireKind::Bit64 => return packed_iter(quote! { protowirers::wire::TypeBit64 }),
+            WireKind::Bit32 => return packed_iter(quote! { protowirers::wire::TypeBit32 }),
+            _ if matches!(a.def_type, DefType::EmbeddedMessages) => (
+                quote! { <#elem as protowirers::view::HasView>::View<'a> },
+                quote! { protowirers::view::MessageView::new(v?) },
+            ),
+            _ if matches!(a.def_type, DefType::String) && is_ident(elem, "String") => {
+                (quote! { &'a str }, quote! { Ok(std::str::from_utf8(v?)?) })
+            }
+            _ if matches!(a.def_type, DefType::Bytes)
+                && vec_element(elem).is_some_and(|t| is_ident(t, "u8")) =>
+            {
+                (quote! { &'a [u8] }, quote! { v })
+            }
+            _ => (
+                quote! { #elem },
+                quote! {
+                    protowirers::parser::LengthDelimitedToValue::from_length_delimited(v?, #wdt)
+                },
+            ),
+        };
+        (
+            quote! { impl Iterator<Item = protowirers::Result<#item>> + 'a },
+            quote! {
+                Ok(protowirers::view::LengthDelimitedIter::new(&self.segments, #fieild_num).map(|v| #map))
+            },
+        )
+    }
+
+    // build_view_owned_length_delimited は LengthDelimited の値を所有する型として decode するアクセサの
+    // 戻り値の型と処理を組み立てます
+    fn build_view_owned_length_delimited(
+        &self,
+    ) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+        let ty = &self.original.ty;
+        let fieild_num = self.attr.filed_num as u128;
+        let wdt = self.build_wire_data_type();
+        (
+            quote! { #ty },
+            quote! { protowirers::view::length_delimited(&self.segments, #fieild_num, #wdt) },
+        )
+    }
+
+    // build_wire_data_type は encode/decode 時に値の変換で使う型を組み立てます
+    // repeated & packed は LengthDelimited として扱う
+    fn build_wire_data_type(&self) -> proc_macro2::TokenStream {
+        let wdt = self.attr.def_type.to_input_wire_data_type();
+        if self.attr.repeated && self.attr.packed {
+            return quote! {
+                protowirers::wire::TypeLengthDelimited::PackedRepeatedFields(
+                    protowirers::wire::AllowedPakcedType::Variant(#wdt),
+                )
+            };
+        }
+        wdt
+    }
+
+    // build_encode_reversed は フィールドを buf に逆順で書き込む処理を組み立てます
+    // LengthDelimited は値を書き込んだ後に長さを書き込むため、一時的なバッファを必要としません
+    fn build_encode_reversed(&self) -> proc_macro2::TokenStream {
+        let a = &self.attr;
+        let fieild_num = a.filed_num as u128;
+        let wdt = self.build_wire_data_type();
+        let member = &self.member;
+        // repeated は要素ごとに書き込み、scalar は options.packing に従って packed にする
+        if self.repeated_element().is_some() {
+            let packed = a.packed;
+            let wdt = a.def_type.to_input_wire_data_type();
+            let write = match a.def_type.wire_kind() {
+                WireKind::Varint => quote! {
+                    protowirers::repeated::write_varint_elements_reversed(
+                        buf, #fieild_num, value, #wdt, options.packing.is_packed(#packed),
+                    )?;
+                },
+                WireKind::Bit64 => quote! {
+                    protowirers::repeated::write_bit64_elements_reversed(
+                        buf, #fieild_num, value, #wdt, options.packing.is_packed(#packed),
+                    )?;
+                },
+                WireKind::Bit32 => quote! {
+                    protowirers::repeated::write_bit32_elements_reversed(
+                        buf, #fieild_num, value, #wdt, options.packing.is_packed(#packed),
+                    )?;
+                },
+                WireKind::LengthDelimited => quote! {
+                    protowirers::repeated::write_length_delimited_elements_reversed(
+                        buf, #fieild_num, value, #wdt, options,
+                    )?;
+                },
+            };
+            return quote! {
+                {
+                    let value = &self.#member;
+                    #write
+                }
+            };
+        }
+        let kind = if a.repeated && a.packed {
+            WireKind::LengthDelimited
+        } else {
+            a.def_type.wire_kind()
+        };
+        let write = match kind {
+            WireKind::Varint => quote! {
+                protowirers::encode::write_variant_field_reversed(
+                    buf,
+                    #fieild_num,
+                    protowirers::parser::VariantToValue::to_variant(value, #wdt)?,
+                    options,
+                );
+            },
+            WireKind::Bit64 => quote! {
+                protowirers::encode::write_bit64_field_reversed(
+                    buf,
+                    #fieild_num,
+                    protowirers::parser::Bit64ToValue::to_bit64(value, #wdt)?,
+                    options,
+                );
+            },
+            WireKind::Bit32 => quote! {
+                protowirers::encode::write_bit32_field_reversed(
+                    buf,
+                    #fieild_num,
+                    protowirers::parser::Bit32ToValue::to_bit64(value, #wdt)?,
+                    options,
+                );
+            },
+            WireKind::LengthDelimited => quote! {
+                protowirers::encode::write_length_delimited_field_reversed(buf, #fieild_num, options, |buf| {
+                    protowirers::parser::LengthDelimitedToValue::write_length_delimited_reversed_with_options(
+                        value, #wdt, buf, options,
+                    )
+                })?;
+            },
+        };
+        match &a.with {
+            Some(with) => quote! {
+                {
+                    let value = &#with::to_wire(&self.#member)?;
+                    #write
+                }
+            },
+            None => quote! {
+                {
+                    let value = &self.#member;
+                    #write
+                }
+            },
         }
     }
 }
@@ -173,10 +1398,19 @@ pub struct Attribute {
     pub def_type: DefType,
     pub repeated: bool,
     pub packed: bool,
+    // with は wire の値との変換を行うモジュールのパスです
+    pub with: Option<syn::Path>,
+    // json_name は JSON のフィールド名です（指定しない場合はフィールド名の lowerCamelCase）
+    pub json_name: Option<String>,
 }
 
 impl Attribute {
-    fn from_syn(attrs: &[syn::Attribute], with_field: &syn::Field) -> syn::Result<Self> {
+    // transparent が true の場合、field_num は指定できません
+    fn from_syn(
+        attrs: &[syn::Attribute],
+        with_field: &syn::Field,
+        transparent: bool,
+    ) -> syn::Result<Self> {
         let mut a: Vec<(&syn::Attribute, syn::MetaList)> = attrs
             .iter()
             .filter_map(|attr| match attr.meta {
@@ -185,10 +1419,11 @@ impl Attribute {
             })
             .collect();
         if a.is_empty() {
-            return Err(syn::Error::new_spanned(
-                &with_field.ident,
-                "#[def(...)] attribute is required",
-            ));
+            let message = "#[def(...)] attribute is required";
+            return Err(match &with_field.ident {
+                Some(ident) => syn::Error::new_spanned(ident, message),
+                None => syn::Error::new_spanned(with_field, message),
+            });
         } else if a.len() > 1 {
             return Err(syn::Error::new_spanned(
                 with_field,
@@ -202,8 +1437,13 @@ impl Attribute {
         let mut def_type: Option<DefType> = None;
         let mut repeated: Option<()> = None;
         let mut packed: Option<()> = None;
+        let mut with: Option<syn::Path> = None;
+        let mut json_name: Option<String> = None;
 
         meta_list.parse_nested_meta(|nested_meta| match nested_meta.path.get_ident() {
+            Some(ident) if ident == "field_num" && transparent => {
+                Err(nested_meta.error("field_num is not allowed in #[proto(transparent)]."))
+            }
             Some(ident) if ident == "field_num" => {
                 let value = nested_meta.value()?;
                 let v: syn::LitInt = value.parse().map_err(|e| {
@@ -258,11 +1498,57 @@ impl Attribute {
                     Ok(())
                 }
             },
+            Some(ident) if ident == "with" => {
+                let value = nested_meta.value()?;
+                let v: syn::LitStr = value.parse().or(Err(
+                    nested_meta.error("invalid value. value is string only.")
+                ))?;
+                match with {
+                    Some(_) => Err(nested_meta.error("with is duplicated in #[def(...)].")),
+                    None => {
+                        with = Some(v.parse::<syn::Path>().map_err(|_| {
+                            syn::Error::new(
+                                v.span(),
+                                format!("invalid module path. got=`{}`.", v.value()),
+                            )
+                        })?);
+                        Ok(())
+                    }
+                }
+            }
+            Some(ident) if ident == "json_name" && transparent => {
+                Err(nested_meta.error("json_name is not allowed in #[proto(transparent)]."))
+            }
+            Some(ident) if ident == "json_name" => {
+                let value = nested_meta.value()?;
+                let v: syn::LitStr = value.parse().or(Err(
+                    nested_meta.error("invalid value. value is string only.")
+                ))?;
+                match json_name {
+                    Some(_) => Err(nested_meta.error("json_name is duplicated in #[def(...)].")),
+                    None => {
+                        json_name = Some(v.value());
+                        Ok(())
+                    }
+                }
+            }
             _ => {
                 nested_meta.value()?.parse::<syn::Lit>()?;
                 Err(nested_meta.error("unsuported meta data in #[def(...)]. "))
             }
         })?;
+        // packed は scalar の repeated フィールドに限る
+        if packed.is_some() && def_type.is_some_and(|t| t.wire_kind() == WireKind::LengthDelimited)
+        {
+            return Err(syn::Error::new_spanned(
+                original,
+                "packed is only supported on scalar types in #[def(...)]",
+            ));
+        }
+        // transparent の場合、field_num は利用しない
+        if transparent {
+            filed_num = Some(0);
+        }
         match (filed_num, def_type) {
             (None, _) => Err(syn::Error::new_spanned(
                 original,
@@ -277,11 +1563,23 @@ impl Attribute {
                 def_type: def_type.unwrap(),
                 repeated: repeated.is_some(),
                 packed: packed.is_some(),
+                with,
+                json_name,
             }),
         }
     }
 
     fn allows_rust_type(&self, ty: &syn::Type) -> bool {
+        // repeated な Vec<T> は要素の型を検査する
+        if self.repeated {
+            if let Some(elem) = vec_element(ty) {
+                return self.allows_element_type(elem);
+            }
+        }
+        self.allows_element_type(ty)
+    }
+
+    fn allows_element_type(&self, ty: &syn::Type) -> bool {
         let p = match *ty {
             syn::Type::Path(ref p) => &p.path,
             _ => return false,
@@ -289,7 +1587,11 @@ impl Attribute {
         if let Some(ident) = p.get_ident() {
             return self.def_type.allows_rust_type(&ident.to_string());
         }
-        if !(self.def_type.is_allows_vec() || self.packed && self.repeated) {
+        // Vec 以外の型（#[proto(transparent)] な型など）は生成されたコードの型検査に任せる
+        if p.segments.last().is_some_and(|x| x.ident != "Vec") {
+            return true;
+        }
+        if !self.def_type.is_allows_vec() {
             return false;
         }
         p.segments
@@ -359,10 +1661,39 @@ impl DefType {
             _ => None,
         }
     }
+    // marker は def_type に対応する protowirers::parser::def の marker 型を返します
+    pub fn marker(self) -> Option<proc_macro2::TokenStream> {
+        let ident = match self {
+            DefType::Int32 => "Int32",
+            DefType::Int64 => "Int64",
+            DefType::Uint32 => "Uint32",
+            DefType::Uint64 => "Uint64",
+            DefType::Sint32 => "Sint32",
+            DefType::Sint64 => "Sint64",
+            DefType::Bool => "Bool",
+            DefType::Enum => "Enum",
+            DefType::Fixed64 => "Fixed64",
+            DefType::Sfixed64 => "Sfixed64",
+            DefType::Double => "Double",
+            DefType::String => "String",
+            DefType::Bytes => "Bytes",
+            DefType::Fixed32 => "Fixed32",
+            DefType::Sfixed32 => "Sfixed32",
+            DefType::Float => "Float",
+            DefType::EmbeddedMessages => return None,
+        };
+        let ident = quote::format_ident!("{}", ident);
+        Some(quote! { protowirers::parser::def::#ident })
+    }
+
     fn is_allows_vec(&self) -> bool {
         matches!(self, DefType::Bytes)
     }
     fn allows_rust_type(&self, rust_type: &str) -> bool {
+        // 組み込みの型以外（#[proto(transparent)] な型など）は生成されたコードの型検査に任せる
+        if !is_builtin_rust_type(rust_type) {
+            return true;
+        }
         let ty = match &self {
             DefType::Int32 => "i32",
             DefType::Int64 => "i64",
@@ -389,6 +1720,44 @@ impl DefType {
         rust_type == ty
     }
 
+    // value_trait は 値の変換に使うトレイトを返します
+    fn value_trait(self) -> proc_macro2::TokenStream {
+        match self.wire_kind() {
+            _ if matches!(self, DefType::EmbeddedMessages) => quote! {protowirers::wire::Proto},
+            WireKind::Varint => quote! {protowirers::parser::VariantToValue},
+            WireKind::Bit64 => quote! {protowirers::parser::Bit64ToValue},
+            WireKind::LengthDelimited => quote! {protowirers::parser::LengthDelimitedToValue},
+            WireKind::Bit32 => quote! {protowirers::parser::Bit32ToValue},
+        }
+    }
+
+    fn wire_kind(self) -> WireKind {
+        match &self {
+            DefType::Int32
+            | DefType::Int64
+            | DefType::Uint32
+            | DefType::Uint64
+            | DefType::Sint32
+            | DefType::Sint64
+            | DefType::Bool
+            | DefType::Enum => WireKind::Varint,
+            DefType::String | DefType::Bytes | DefType::EmbeddedMessages => {
+                WireKind::LengthDelimited
+            }
+            DefType::Fixed64 | DefType::Sfixed64 | DefType::Double => WireKind::Bit64,
+            DefType::Fixed32 | DefType::Sfixed32 | DefType::Float => WireKind::Bit32,
+        }
+    }
+
+    // to_field_type は 対応する protowirers::descriptor::FieldType を返します
+    fn to_field_type(self) -> proc_macro2::TokenStream {
+        let ident = match self {
+            DefType::EmbeddedMessages => quote::format_ident!("Message"),
+            _ => quote::format_ident!("{}", format!("{:?}", self)),
+        };
+        quote! {protowirers::descriptor::FieldType::#ident}
+    }
+
     fn to_input_wire_data_type(self) -> proc_macro2::TokenStream {
         match &self {
             DefType::Int32 => quote! {protowirers::wire::TypeVairant::Int32},
@@ -412,28 +1781,119 @@ impl DefType {
             DefType::Float => quote! {protowirers::wire::TypeBit32::Float},
         }
     }
+}
 
-    fn to_corresponding_wire_type(self) -> proc_macro2::TokenStream {
-        match &self {
-            DefType::Int32
-            | DefType::Int64
-            | DefType::Uint32
-            | DefType::Uint64
-            | DefType::Sint32
-            | DefType::Sint64
-            | DefType::Bool
-            | DefType::Enum => {
-                quote! {protowirers::wire::WireData::Varint}
-            }
-            DefType::String | DefType::Bytes | DefType::EmbeddedMessages => {
-                quote! {protowirers::wire::WireData::LengthDelimited}
-            }
-            DefType::Fixed64 | DefType::Sfixed64 | DefType::Double => {
-                quote! {protowirers::wire::WireData::Bit64}
-            }
-            DefType::Fixed32 | DefType::Sfixed32 | DefType::Float => {
-                quote! {protowirers::wire::WireData::Bit32}
-            }
+// WireKind は def_type が対応する wire の型です
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireKind {
+    Varint,
+    Bit64,
+    LengthDelimited,
+    Bit32,
+}
+
+impl WireKind {
+    // type_number は tag に含まれる wire type の値を返します
+    fn type_number(self) -> u128 {
+        match self {
+            WireKind::Varint => 0,
+            WireKind::Bit64 => 1,
+            WireKind::LengthDelimited => 2,
+            WireKind::Bit32 => 5,
+        }
+    }
+}
+
+// needs_marker_check は ty を def_type の marker の境界で検査する必要があるかを返します
+// 組み込みの型は allows_rust_type で検査するため不要です
+fn needs_marker_check(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(p) => !p
+            .path
+            .get_ident()
+            .is_some_and(|ident| is_builtin_rust_type(&ident.to_string())),
+        _ => true,
+    }
+}
+
+// is_builtin_rust_type は def_type との対応を検査できる組み込みの型かどうかを返します
+fn is_builtin_rust_type(rust_type: &str) -> bool {
+    matches!(
+        rust_type,
+        "i8" | "i16"
+            | "i32"
+            | "i64"
+            | "i128"
+            | "isize"
+            | "u8"
+            | "u16"
+            | "u32"
+            | "u64"
+            | "u128"
+            | "usize"
+            | "f32"
+            | "f64"
+            | "bool"
+            | "char"
+            | "str"
+            | "String"
+    )
+}
+
+// lower_camel_case は フィールド名を JSON の名前（lowerCamelCase）に変換します
+// proto3 の JSON mapping と同じく、`_` を取り除き、その次の文字を大文字にします
+fn lower_camel_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
         }
     }
+    out
+}
+
+// is_ident は 型がパスを含まない ident の型かどうかを返します
+fn is_ident(ty: &syn::Type, ident: &str) -> bool {
+    match ty {
+        syn::Type::Path(p) => p.qself.is_none() && p.path.is_ident(ident),
+        _ => false,
+    }
+}
+
+// vec_element は Vec<T> の T を返します
+fn vec_element(ty: &syn::Type) -> Option<&syn::Type> {
+    let p = match ty {
+        syn::Type::Path(p) if p.qself.is_none() => &p.path,
+        _ => return None,
+    };
+    let last = p.segments.last().filter(|x| x.ident == "Vec")?;
+    match &last.arguments {
+        syn::PathArguments::AngleBracketed(ab) => match ab.args.first() {
+            Some(syn::GenericArgument::Type(t)) if ab.args.len() == 1 => Some(t),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// contains_type_param は 型が構造体の型パラメータを含むかどうかを返します
+fn contains_type_param(ty: &syn::Type, generics: &syn::Generics) -> bool {
+    fn contains(tokens: proc_macro2::TokenStream, params: &[&syn::Ident]) -> bool {
+        tokens.into_iter().any(|tt| match tt {
+            proc_macro2::TokenTree::Ident(ident) => params.contains(&&ident),
+            proc_macro2::TokenTree::Group(group) => contains(group.stream(), params),
+            _ => false,
+        })
+    }
+    let params: Vec<&syn::Ident> = generics.type_params().map(|tp| &tp.ident).collect();
+    if params.is_empty() {
+        return false;
+    }
+    contains(quote! { #ty }, &params)
 }
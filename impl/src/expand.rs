@@ -1,5 +1,6 @@
-use crate::ast::{Enum, Input, Struct};
+use crate::ast::{Enum, Input, Struct, Transparent, WireKind};
 use quote::{format_ident, quote};
+use syn::ext::IdentExt;
 use syn::DeriveInput;
 
 pub fn derive(node: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
@@ -7,6 +8,7 @@ pub fn derive(node: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     match Input::from_syn(node)? {
         Input::Struct(data) => Ok(gen_struct(data, input_indent)),
         Input::Enum(data) => Ok(gen_enum(data, input_indent)),
+        Input::Transparent(data) => Ok(gen_transparent(data, input_indent)),
     }
 }
 
@@ -14,38 +16,348 @@ fn gen_struct(data: Struct, input_indent: syn::Ident) -> proc_macro2::TokenStrea
     // TODO エラーメッセージ改善
     // atribute自体がエラーの場合、() が表示されてしまう, など
     let init_fields = data.build_declare_for_init();
-    let build_fields = data.build_struct_fields();
+    let construct = data.build_construct();
     let build_parse_fields = data.build_match_case();
-    let build_gen_wirestructs = data.build_gen_wirestructs();
+    let def_type_checks = data.build_def_type_checks();
+    let build_encode_reversed = data.build_encode_reversed();
+    let build_merge = data.build_merge();
+    let build_encoded_len = data.build_encoded_len();
+    let build_descriptor_fields = data.build_descriptor_fields();
+    let build_to_json = data.build_to_json();
+    let build_from_json_cases = data.build_from_json_cases();
+    let build_write_text = data.build_write_text();
+    let build_from_text_cases = data.build_from_text_cases();
+    let generics = data.build_generics();
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let name = input_indent.to_string();
+    let view = if data.view {
+        gen_view(&data, &input_indent)
+    } else {
+        quote! {}
+    };
 
     quote! {
-        impl protowirers::wire::Proto for #input_indent{
+        #view
+        impl #impl_generics protowirers::wire::Proto for #input_indent #ty_generics #where_clause {
             fn parse(bytes: &[u8])-> protowirers::Result<Self>{
                 use protowirers::parser::*;
 
-                let mut c = std::io::Cursor::new(bytes);
-                let result = decode::decode_wire_binary(&mut c)?;
-
+                #def_type_checks
                 #init_fields
-                for sw in result {
-                    match (sw.field_number(), sw.wire_type()) {
-                        #build_parse_fields
-                        _ => (),
+                // 読み込み中のフィールドと位置は、decode に失敗した場合にエラーのパスと位置として使う
+                let mut __field: Option<protowirers::error::FieldSegment> = None;
+                let mut __offset = 0;
+                #[allow(unused_mut)]
+                let mut __value_offset = 0;
+                let mut __reader = protowirers::decode::WireReader::new(bytes);
+                let __parsed = (|| -> protowirers::Result<Self> {
+                    // 入力を tag ごとに読み進め、値を直接フィールドに設定する
+                    loop {
+                        __field = None;
+                        __offset = __reader.position();
+                        let (__field_number, __wire_type) = match __reader.read_tag()? {
+                            Some(tag) => tag,
+                            None => break,
+                        };
+                        match (__field_number, __wire_type) {
+                            #build_parse_fields
+                            _ => __reader.skip(__wire_type)?,
+                        }
                     }
-                }
-                Ok(Self {
-                    #build_fields
+                    Ok(#construct)
+                })();
+                __parsed.map_err(|e| {
+                    protowirers::error::DecodeError::wrap(e, #name, __field, __offset, __value_offset)
+                })
+            }
+            fn encode_reversed(&self, buf: &mut Vec<u8>) -> protowirers::Result<()> {
+                self.encode_reversed_with_options(buf, &protowirers::encode::EncodeOptions::default())
+            }
+            fn encode_reversed_with_options(
+                &self,
+                buf: &mut Vec<u8>,
+                options: &protowirers::encode::EncodeOptions,
+            ) -> protowirers::Result<()> {
+                let _ = options;
+                #build_encode_reversed
+                Ok(())
+            }
+            fn encoded_len(&self) -> usize {
+                #build_encoded_len
+            }
+            fn descriptor() -> Option<protowirers::descriptor::MessageDescriptor> {
+                Some(protowirers::descriptor::MessageDescriptor {
+                    name: #name,
+                    fields: #build_descriptor_fields,
                 })
             }
-            fn bytes(&self)-> protowirers::Result<Vec<u8>>{
+            fn to_json_value(&self) -> protowirers::Result<protowirers::json::JsonValue> {
+                #[allow(unused_mut)]
+                let mut __object: Vec<(String, protowirers::json::JsonValue)> = Vec::new();
+                #build_to_json
+                Ok(protowirers::json::JsonValue::Object(__object))
+            }
+            fn from_json_value(__json: &protowirers::json::JsonValue) -> protowirers::Result<Self> {
+                #init_fields
+                for (__key, __value) in protowirers::json::expect_object(__json)? {
+                    match __key.as_str() {
+                        #build_from_json_cases
+                        _ => return Err(protowirers::json::unknown_field(__key)),
+                    }
+                }
+                Ok(#construct)
+            }
+            fn write_text(&self, __w: &mut protowirers::text::TextWriter) -> protowirers::Result<()> {
+                #build_write_text
+                Ok(())
+            }
+            fn from_text_fields(__fields: &[protowirers::text::TextField]) -> protowirers::Result<Self> {
+                #init_fields
+                for __field in __fields {
+                    match __field.name.as_str() {
+                        #build_from_text_cases
+                        _ => return Err(protowirers::text::unknown_field(__field)),
+                    }
+                }
+                Ok(#construct)
+            }
+            fn merge(&mut self, other: Self) {
                 use protowirers::parser::*;
 
-                let inputs = vec![
-                    #build_gen_wirestructs
-                ];
-                let mut c = std::io::Cursor::new(Vec::new());
-                encode::encode_wire_binary(&mut c, inputs)?;
-                Ok(c.into_inner())
+                #build_merge
+            }
+        }
+    }
+}
+
+fn gen_view(data: &Struct, input_indent: &syn::Ident) -> proc_macro2::TokenStream {
+    // impl
+    // ```rust
+    // #[derive(Debug, Clone)]
+    // pub struct SampleView<'a> {
+    //     segments: protowirers::view::Segments<'a>,
+    // }
+    // impl<'a> SampleView<'a> {
+    //     pub fn new(bytes: &'a [u8]) -> protowirers::Result<Self> { ... }
+    //     pub fn s_string(&self) -> protowirers::Result<&'a str> { ... }
+    // }
+    // impl protowirers::view::HasView for Sample {
+    //     type View<'a> = SampleView<'a>;
+    // }
+    // ```
+    let vis = data.vis;
+    let view_ident = format_ident!("{}View", input_indent);
+    let doc = format!(
+        "`{input_indent}` のバイト列を借用し、アクセス時にフィールドを decode する View です。"
+    );
+    let accessors = data.build_view_accessors();
+    quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone)]
+        #vis struct #view_ident<'a> {
+            segments: protowirers::view::Segments<'a>,
+        }
+        impl<'a> #view_ident<'a> {
+            // new は bytes の wire format を検査し、View を返します
+            #vis fn new(bytes: &'a [u8]) -> protowirers::Result<Self> {
+                <Self as protowirers::view::MessageView<'a>>::new(bytes)
+            }
+            // as_bytes は View が参照するバイト列を返します
+            // embedded message が複数回現れた場合は、それらを連結したバイト列を返します
+            #vis fn as_bytes(&self) -> std::borrow::Cow<'a, [u8]> {
+                self.segments.to_bytes()
+            }
+            // to_message は すべてのフィールドを decode した値を返します
+            #vis fn to_message(&self) -> protowirers::Result<#input_indent> {
+                <#input_indent as protowirers::wire::Proto>::parse(&self.segments.to_bytes())
+            }
+            #accessors
+        }
+        impl<'a> protowirers::view::MessageView<'a> for #view_ident<'a> {
+            fn from_segments(segments: protowirers::view::Segments<'a>) -> protowirers::Result<Self> {
+                for bytes in segments.iter() {
+                    protowirers::view::validate(bytes)?;
+                }
+                Ok(Self { segments })
+            }
+            fn segments(&self) -> &protowirers::view::Segments<'a> {
+                &self.segments
+            }
+        }
+        impl protowirers::view::HasView for #input_indent {
+            type View<'a> = #view_ident<'a>;
+        }
+    }
+}
+
+fn gen_transparent(data: Transparent, input_indent: syn::Ident) -> proc_macro2::TokenStream {
+    // impl (def_type = "uint64" の場合)
+    // ```rust
+    // impl protowirers::parser::VariantToValue for UserId {
+    //     fn from_valint(input: u128, ty: protowirers::wire::TypeVairant) -> protowirers::Result<Self> {
+    //         // ty が def_type と一致するか確認する
+    //         Ok(Self { 0: <u64 as protowirers::parser::VariantToValue>::from_valint(input, ty)? })
+    //     }
+    //     fn to_variant(&self, ty: protowirers::wire::TypeVairant) -> protowirers::Result<u128> {
+    //         <u64 as protowirers::parser::VariantToValue>::to_variant(&self.0, ty)
+    //     }
+    // }
+    // ```
+    let inner_ty = &data.original.ty;
+    let member = &data.member;
+    let wdt = data.build_input_wire_data_type();
+    let (trait_path, ty, wire, from_fn, to_fn) = match data.wire_kind() {
+        WireKind::Varint => (
+            quote! {protowirers::parser::VariantToValue},
+            quote! {protowirers::wire::TypeVairant},
+            quote! {u128},
+            quote! {from_valint},
+            quote! {to_variant},
+        ),
+        WireKind::Bit64 => (
+            quote! {protowirers::parser::Bit64ToValue},
+            quote! {protowirers::wire::TypeBit64},
+            quote! {[u8; 8]},
+            quote! {from_bit64},
+            quote! {to_bit64},
+        ),
+        WireKind::LengthDelimited => (
+            quote! {protowirers::parser::LengthDelimitedToValue},
+            quote! {protowirers::wire::TypeLengthDelimited},
+            quote! {Vec<u8>},
+            quote! {from_length_delimited},
+            quote! {to_length_delimited},
+        ),
+        WireKind::Bit32 => (
+            quote! {protowirers::parser::Bit32ToValue},
+            quote! {protowirers::wire::TypeBit32},
+            quote! {[u8; 4]},
+            quote! {from_bit64},
+            quote! {to_bit64},
+        ),
+    };
+    // LengthDelimited は入力を借用して受け取る
+    let wire_in = match data.wire_kind() {
+        WireKind::LengthDelimited => quote! { &[u8] },
+        _ => wire.clone(),
+    };
+    let construct = data.build_construct(quote! {
+        <#inner_ty as #trait_path>::#from_fn(input, ty)?
+    });
+    let check_type = quote! {
+        if ty != #wdt {
+            return Err(protowirers::Error::UnexpectedType {
+                want: format!("{:?}", #wdt),
+                got: format!("{:?}", ty),
+            });
+        }
+    };
+    // LengthDelimited の場合、統合とバイト数の計算も内部の値に任せる
+    let merge = match data.wire_kind() {
+        WireKind::LengthDelimited => quote! {
+            fn merge_length_delimited(&mut self, input: #wire_in, ty: #ty) -> protowirers::Result<()> {
+                #check_type
+                <#inner_ty as #trait_path>::merge_length_delimited(&mut self.#member, input, ty)
+            }
+            fn length_delimited_len(&self, ty: #ty) -> usize {
+                if ty != #wdt {
+                    return 0;
+                }
+                <#inner_ty as #trait_path>::length_delimited_len(&self.#member, ty)
+            }
+            fn write_length_delimited_reversed(&self, ty: #ty, buf: &mut Vec<u8>) -> protowirers::Result<()> {
+                #check_type
+                <#inner_ty as #trait_path>::write_length_delimited_reversed(&self.#member, ty, buf)
+            }
+            fn write_length_delimited_reversed_with_options(
+                &self,
+                ty: #ty,
+                buf: &mut Vec<u8>,
+                options: &protowirers::encode::EncodeOptions,
+            ) -> protowirers::Result<()> {
+                #check_type
+                <#inner_ty as #trait_path>::write_length_delimited_reversed_with_options(&self.#member, ty, buf, options)
+            }
+        },
+        _ => quote! {},
+    };
+    let scalar = if data.is_scalar() {
+        let json = gen_transparent_json(&data, &input_indent);
+        let text = gen_transparent_text(&data, &input_indent);
+        quote! {
+            #json
+            #text
+        }
+    } else {
+        quote! {}
+    };
+    let def_type_check = data.build_def_type_check();
+    let marker_impl = data.build_marker_impl(&input_indent);
+    let generics = data.build_def_type_generics(&trait_path);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        #scalar
+        #marker_impl
+        impl #impl_generics #trait_path for #input_indent #ty_generics #where_clause {
+            fn #from_fn(input: #wire_in, ty: #ty) -> protowirers::Result<Self> {
+                #def_type_check
+                #check_type
+                Ok(#construct)
+            }
+            fn #to_fn(&self, ty: #ty) -> protowirers::Result<#wire> {
+                #check_type
+                <#inner_ty as #trait_path>::#to_fn(&self.#member, ty)
+            }
+            #merge
+        }
+    }
+}
+
+// gen_transparent_json は 内部の値と同じように JSON と変換する JsonField の実装を組み立てます
+fn gen_transparent_json(data: &Transparent, input_indent: &syn::Ident) -> proc_macro2::TokenStream {
+    let inner_ty = &data.original.ty;
+    let member = &data.member;
+    let trait_path = quote! {protowirers::json::JsonField};
+    let construct = data.build_construct(quote! {
+        <#inner_ty as #trait_path>::from_json_value(value)?
+    });
+    let generics = data.build_generics(&trait_path);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics #trait_path for #input_indent #ty_generics #where_clause {
+            fn to_json_value(&self) -> protowirers::Result<protowirers::json::JsonValue> {
+                <#inner_ty as #trait_path>::to_json_value(&self.#member)
+            }
+            fn from_json_value(value: &protowirers::json::JsonValue) -> protowirers::Result<Self> {
+                Ok(#construct)
+            }
+            fn is_json_default(&self) -> bool {
+                <#inner_ty as #trait_path>::is_json_default(&self.#member)
+            }
+        }
+    }
+}
+
+// gen_transparent_text は 内部の値と同じように text format と変換する TextScalar の実装を組み立てます
+fn gen_transparent_text(data: &Transparent, input_indent: &syn::Ident) -> proc_macro2::TokenStream {
+    let inner_ty = &data.original.ty;
+    let member = &data.member;
+    let trait_path = quote! {protowirers::text::TextScalar};
+    let construct = data.build_construct(quote! {
+        <#inner_ty as #trait_path>::from_text_value(value)?
+    });
+    let generics = data.build_generics(&trait_path);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics #trait_path for #input_indent #ty_generics #where_clause {
+            fn write_text(&self, w: &mut protowirers::text::TextWriter) {
+                <#inner_ty as #trait_path>::write_text(&self.#member, w)
+            }
+            fn from_text_value(value: &protowirers::text::TextValue) -> protowirers::Result<Self> {
+                Ok(#construct)
+            }
+            fn is_text_default(&self) -> bool {
+                <#inner_ty as #trait_path>::is_text_default(&self.#member)
             }
         }
     }
@@ -54,7 +366,16 @@ fn gen_struct(data: Struct, input_indent: syn::Ident) -> proc_macro2::TokenStrea
 fn gen_enum(data: Enum, input_indent: syn::Ident) -> proc_macro2::TokenStream {
     // impl
     // ```rust
-    // impl protowirers::parser::VariantEnum for Test {}
+    // impl protowirers::parser::VariantEnum for Test {
+    //     fn name(self) -> Option<&'static str> {
+    //         match self {
+    //             Test::Value1 => Some("Value1"),
+    //             ...
+    //             Test::ValueOther(_) => None,
+    //         }
+    //     }
+    //     fn from_name(name: &str) -> Option<Self> { ... }
+    // }
     // impl From<i32> for Test {
     //     fn from(i: i32) -> Self {
     //         match i {
@@ -85,7 +406,7 @@ fn gen_enum(data: Enum, input_indent: syn::Ident) -> proc_macro2::TokenStream {
     let last_index = data.variants.len() - 1;
     // TODO とりあえず unwrap
     // default は先頭要素
-    let first_ident = data.variants.first().unwrap();
+    let first_ident = &data.variants.first().unwrap().ident;
 
     let idents: Vec<(usize, &syn::Ident)> = data
         .variants
@@ -101,8 +422,8 @@ fn gen_enum(data: Enum, input_indent: syn::Ident) -> proc_macro2::TokenStream {
         if *index == last_index {
             quote! { i => #input_indent::#i(i)}
         } else {
-            let index = *index as i32;
-            quote! { #index => #input_indent::#i}
+            let number = data.numbers[*index];
+            quote! { #number => #input_indent::#i}
         }
     });
     let from = quote! {
@@ -112,15 +433,43 @@ fn gen_enum(data: Enum, input_indent: syn::Ident) -> proc_macro2::TokenStream {
         if *index == last_index {
             quote! { #input_indent::#i(i) => i }
         } else {
-            let index = *index as i32;
-            quote! { #input_indent::#i => #index }
+            let number = data.numbers[*index];
+            quote! { #input_indent::#i => #number }
         }
     });
     let to = quote! {
         #(#tos,)*
     };
+    // 引数を持たない値は、識別子を名前とする
+    let names = idents.iter().map(|(index, i)| {
+        if *index == last_index {
+            quote! { #input_indent::#i(_) => None }
+        } else {
+            let name = i.unraw().to_string();
+            quote! { #input_indent::#i => Some(#name) }
+        }
+    });
+    let from_names = idents
+        .iter()
+        .filter(|(index, _)| *index != last_index)
+        .map(|(_, i)| {
+            let name = i.unraw().to_string();
+            quote! { #name => Some(#input_indent::#i) }
+        });
     quote! {
-        impl protowirers::parser::VariantEnum for #input_indent {}
+        impl protowirers::parser::VariantEnum for #input_indent {
+            fn name(self) -> Option<&'static str> {
+                match self {
+                    #(#names,)*
+                }
+            }
+            fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    #(#from_names,)*
+                    _ => None,
+                }
+            }
+        }
         impl From<i32> for #input_indent {
             fn from(i: i32) -> Self {
                 match i {
@@ -0,0 +1,20 @@
+use crate::Result;
+
+// ProtoAdapter は `#[def(with = "protowirers::adapter")]` で、独自の型を def_type に対応する Rust の型（Wire）と相互に変換します
+// Wire は def_type に対応する型（uint64 なら u64, bytes なら Vec<u8> など）にします
+pub trait ProtoAdapter: Sized {
+    type Wire;
+    fn to_wire(&self) -> Result<Self::Wire>;
+    // フィールドが存在しない場合、Wire の default 値が渡されます
+    fn from_wire(wire: Self::Wire) -> Result<Self>;
+}
+
+// to_wire は `with` で指定されたモジュールとして呼び出される関数です
+pub fn to_wire<T: ProtoAdapter>(value: &T) -> Result<T::Wire> {
+    value.to_wire()
+}
+
+// from_wire は `with` で指定されたモジュールとして呼び出される関数です
+pub fn from_wire<T: ProtoAdapter>(wire: T::Wire) -> Result<T> {
+    T::from_wire(wire)
+}
@@ -1,8 +1,12 @@
+use std::collections::HashMap;
 use std::io::{Cursor, Read, Seek, SeekFrom};
 
 use crate::wire::*;
 use crate::{Error, Result};
 
+// diagnose は decode と異なり、問題があっても最後まで読み進めて問題をすべて報告します
+pub use crate::diagnose::{diagnose, diagnose_as};
+
 fn decode_variants<T: Read>(data: &mut T) -> Result<u128> {
     // iterate take_util とかでもできるよ
     let mut sum = 0;
@@ -27,6 +31,12 @@ fn decode_variants<T: Read>(data: &mut T) -> Result<u128> {
     }
 }
 
+// read_varint は r から varint を読み込みます
+// 値の途中で入力が終わっている場合は UnexpectedFormat になります
+pub fn read_varint<R: Read>(r: &mut R) -> Result<u128> {
+    decode_variants(r)
+}
+
 // decode_length_delimited decode variable length byte.
 // length to decode is first variants
 // this function used by `string`, `embedded messages`
@@ -51,11 +61,10 @@ fn decode_64bit(data: &mut Cursor<&[u8]>) -> Result<[u8; 8]> {
 }
 
 pub(crate) fn decode_variants_slice(data: &[u8]) -> Result<Vec<u128>> {
-    let mut data = data;
+    let mut r = WireReader::new(data);
     let mut v = Vec::new();
-    while !data.is_empty() {
-        let value = decode_variants(&mut data)?;
-        v.push(value);
+    while !r.is_empty() {
+        v.push(r.read_varint()?);
     }
     Ok(v)
 }
@@ -86,6 +95,403 @@ fn decode_struct(data: &mut Cursor<&[u8]>) -> Result<WireStruct> {
     Ok(WireStruct::new(field_num, wt))
 }
 
+// WireReader は入力を先頭から tag ごとに読み進めます
+// decode_wire_binary と異なり Vec<WireStruct> を作らず、length-delimited の値は入力を借用して返します
+pub struct WireReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> WireReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    // position は読み込んだバイト数を返します
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    // is_empty は読み込む値が残っていない場合に true を返します
+    pub fn is_empty(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    // read_tag は tag を読み込み、(field number, wire type) を返します
+    // 入力の末尾に達している場合は None を返します
+    pub fn read_tag(&mut self) -> Result<Option<(u128, u128)>> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let n = self.read_varint()?;
+        Ok(Some((n >> 3, n & 7)))
+    }
+
+    pub fn read_varint(&mut self) -> Result<u128> {
+        let mut sum = 0;
+        let mut shift = 0;
+        loop {
+            let Some(&b) = self.data.get(self.position) else {
+                return Err(Error::UnexpectedFormat);
+            };
+            // u128 に収まらない場合
+            if shift >= 128 {
+                return Err(Error::UnexpectedFormat);
+            }
+            self.position += 1;
+            sum |= ((b & 0b01111111) as u128) << shift;
+            shift += 7;
+            // MSB が 0 の場合、後続はない
+            if b & 0b10000000 == 0 {
+                return Ok(sum);
+            }
+        }
+    }
+
+    pub fn read_bit64(&mut self) -> Result<[u8; 8]> {
+        self.read_nbit()
+    }
+
+    pub fn read_bit32(&mut self) -> Result<[u8; 4]> {
+        self.read_nbit()
+    }
+
+    // read_length_delimited は長さを読み込み、その長さ分の入力をコピーせずに返します
+    pub fn read_length_delimited(&mut self) -> Result<&'a [u8]> {
+        let length = self.read_varint()?;
+        let length = usize::try_from(length)?;
+        self.take(length)
+    }
+
+    // read_value は wire type に応じて値を読み込みます
+    pub fn read_value(&mut self, wire_type: u128) -> Result<WireDataRef<'a>> {
+        match wire_type {
+            0 => self.read_varint().map(WireDataRef::Varint),
+            1 => self.read_bit64().map(WireDataRef::Bit64),
+            2 => self
+                .read_length_delimited()
+                .map(WireDataRef::LengthDelimited),
+            5 => self.read_bit32().map(WireDataRef::Bit32),
+            _ => Err(Error::UnexpectedWireDataValue(wire_type)),
+        }
+    }
+
+    // skip は wire type に応じて値を読み飛ばします
+    pub fn skip(&mut self, wire_type: u128) -> Result<()> {
+        self.read_value(wire_type).map(|_| ())
+    }
+
+    fn read_nbit<const SIZE: usize>(&mut self) -> Result<[u8; SIZE]> {
+        let mut buf = [0; SIZE];
+        buf.copy_from_slice(self.take(SIZE)?);
+        Ok(buf)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        let rest = &self.data[self.position..];
+        if rest.len() < length {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        self.position += length;
+        Ok(&rest[..length])
+    }
+}
+
+// Decoded は PushDecoder::next の結果です
+#[derive(Debug, PartialEq, Eq)]
+pub enum Decoded {
+    // Struct は decode できたフィールドです
+    Struct(WireStruct),
+    // NeedMore は入力が足りないことを表します。値は少なくとも必要なバイト数です
+    NeedMore(usize),
+}
+
+// PushState は PushDecoder がどこまで読み込んだかを表します
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PushState {
+    Tag,
+    Value { field_number: u128, wire_type: u128 },
+    Body { field_number: u128, len: usize },
+}
+
+// PushDecoder は分割して届く入力を decode します
+// feed で入力を追加し、next で decode できたフィールドを順に取り出します
+// フィールドの途中で入力が終わっている場合はエラーではなく Decoded::NeedMore を返し、
+// 読み込んだ tag や長さを保持して次の入力から再開します
+// length-delimited の長さが max_message_size を超える場合は、入力を待たずにエラーを返します
+#[derive(Debug)]
+pub struct PushDecoder {
+    buf: Vec<u8>,
+    position: usize,
+    state: PushState,
+    max_message_size: usize,
+}
+
+// DEFAULT_MAX_MESSAGE_SIZE は PushDecoder が受け付ける length-delimited の長さの既定の上限です
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+impl Default for PushDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PushDecoder {
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            position: 0,
+            state: PushState::Tag,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+
+    // with_max_message_size は length-delimited の長さの上限を設定します
+    pub fn with_max_message_size(mut self, max: usize) -> Self {
+        self.max_message_size = max;
+        self
+    }
+
+    // feed は入力を追加します
+    pub fn feed(&mut self, chunk: &[u8]) {
+        // decode 済みの入力は捨てる
+        if self.position > 0 {
+            self.buf.drain(..self.position);
+            self.position = 0;
+        }
+        self.buf.extend_from_slice(chunk);
+    }
+
+    // is_idle は フィールドの途中でない（入力がフィールドの境界で終わっている）場合に true を返します
+    pub fn is_idle(&self) -> bool {
+        self.state == PushState::Tag && self.position == self.buf.len()
+    }
+
+    // finish は 入力の終わりで呼び出し、フィールドの途中であればエラーを返します
+    pub fn finish(&self) -> Result<()> {
+        if !self.is_idle() {
+            return Err(Error::UnexpectedFormat);
+        }
+        Ok(())
+    }
+
+    // next は decode できたフィールドを 1 つ返します
+    // 入力が足りない場合は Decoded::NeedMore を返します
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Decoded> {
+        loop {
+            let rest = &self.buf[self.position..];
+            match self.state {
+                PushState::Tag => {
+                    let Some((n, size)) = peek_varint(rest)? else {
+                        return Ok(Decoded::NeedMore(1));
+                    };
+                    self.position += size;
+                    self.state = PushState::Value {
+                        field_number: n >> 3,
+                        wire_type: n & 7,
+                    };
+                }
+                PushState::Value {
+                    field_number,
+                    wire_type,
+                } => {
+                    let (data, size) = match wire_type {
+                        0 => {
+                            let Some((v, size)) = peek_varint(rest)? else {
+                                return Ok(Decoded::NeedMore(1));
+                            };
+                            (WireData::Varint(WireDataVarint::new(v)), size)
+                        }
+                        1 => {
+                            let Some(v) = rest.get(..8) else {
+                                return Ok(Decoded::NeedMore(8 - rest.len()));
+                            };
+                            let mut b = [0; 8];
+                            b.copy_from_slice(v);
+                            (WireData::Bit64(WireDataBit64::new(b)), 8)
+                        }
+                        2 => {
+                            let Some((len, size)) = peek_varint(rest)? else {
+                                return Ok(Decoded::NeedMore(1));
+                            };
+                            let len = match usize::try_from(len) {
+                                Ok(len) if len <= self.max_message_size => len,
+                                _ => {
+                                    return Err(Error::MessageTooLarge {
+                                        max: self.max_message_size,
+                                        got: len,
+                                    })
+                                }
+                            };
+                            self.position += size;
+                            self.state = PushState::Body { field_number, len };
+                            continue;
+                        }
+                        5 => {
+                            let Some(v) = rest.get(..4) else {
+                                return Ok(Decoded::NeedMore(4 - rest.len()));
+                            };
+                            let mut b = [0; 4];
+                            b.copy_from_slice(v);
+                            (WireData::Bit32(WireDataBit32::new(b)), 4)
+                        }
+                        _ => return Err(Error::UnexpectedWireDataValue(wire_type)),
+                    };
+                    self.position += size;
+                    self.state = PushState::Tag;
+                    return Ok(Decoded::Struct(WireStruct::new(field_number, data)));
+                }
+                PushState::Body { field_number, len } => {
+                    let Some(v) = rest.get(..len) else {
+                        return Ok(Decoded::NeedMore(len - rest.len()));
+                    };
+                    let data = WireData::LengthDelimited(WireDataLengthDelimited::new(v.to_vec()));
+                    self.position += len;
+                    self.state = PushState::Tag;
+                    return Ok(Decoded::Struct(WireStruct::new(field_number, data)));
+                }
+            }
+        }
+    }
+}
+
+// peek_varint は data の先頭の varint を読み込み、(値, バイト数) を返します
+// varint の途中で data が終わっている場合は None を返します
+fn peek_varint(data: &[u8]) -> Result<Option<(u128, usize)>> {
+    let mut r = WireReader::new(data);
+    match r.read_varint() {
+        Ok(v) => Ok(Some((v, r.position()))),
+        // MSB が立ったまま data が終わっている（u128 の varint は最大 19 バイト）
+        Err(Error::UnexpectedFormat)
+            if data.len() < 19 && data.iter().all(|b| b & 0b10000000 != 0) =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// Visit は WireVisitor のコールバックが走査の続け方を返す値です
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visit {
+    // Continue は走査を続けます。enter_message の場合は embedded message の中を走査します
+    Continue,
+    // Skip は enter_message の場合に embedded message の中を走査せず、bytes として扱います
+    // それ以外のコールバックでは Continue と同じです
+    Skip,
+    // Stop は走査をすぐに終了します
+    Stop,
+}
+
+// WireVisitor は visit で入力を走査する際に、フィールドごとに呼び出されるコールバックです
+// Vec<WireStruct> を作らずに、必要なフィールドだけを扱えます
+// length-delimited の値が embedded message かどうかは wire 上では区別できないため、
+// enter_message で中を走査するかどうかを決めます
+pub trait WireVisitor {
+    fn varint(&mut self, _field_number: u128, _value: u128) -> Visit {
+        Visit::Continue
+    }
+    fn fixed64(&mut self, _field_number: u128, _value: [u8; 8]) -> Visit {
+        Visit::Continue
+    }
+    fn fixed32(&mut self, _field_number: u128, _value: [u8; 4]) -> Visit {
+        Visit::Continue
+    }
+    // bytes は embedded message として走査しない length-delimited の値で呼び出されます
+    fn bytes(&mut self, _field_number: u128, _value: &[u8]) -> Visit {
+        Visit::Continue
+    }
+    // enter_message は length-delimited の値ごとに呼び出されます
+    // Continue を返すと embedded message として中を走査し、最後に leave_message が呼び出されます
+    // デフォルトでは Skip を返し、bytes として扱います
+    fn enter_message(&mut self, _field_number: u128) -> Visit {
+        Visit::Skip
+    }
+    fn leave_message(&mut self, _field_number: u128) -> Visit {
+        Visit::Continue
+    }
+}
+
+// MAX_VISIT_DEPTH は visit で走査する embedded message の入れ子の深さです
+const MAX_VISIT_DEPTH: usize = 100;
+
+// visit は bytes を先頭から走査し、フィールドごとに visitor のコールバックを呼び出します
+// visitor が Stop を返した場合は走査を終了し、false を返します
+// embedded message の入れ子が MAX_VISIT_DEPTH を超える場合は NestedTooDeeply を返します
+pub fn visit<V: WireVisitor + ?Sized>(bytes: &[u8], visitor: &mut V) -> Result<bool> {
+    visit_at(bytes, visitor, 0)
+}
+
+fn visit_at<V: WireVisitor + ?Sized>(bytes: &[u8], visitor: &mut V, depth: usize) -> Result<bool> {
+    if depth > MAX_VISIT_DEPTH {
+        return Err(Error::NestedTooDeeply(MAX_VISIT_DEPTH));
+    }
+    let mut r = WireReader::new(bytes);
+    while let Some((field_number, wire_type)) = r.read_tag()? {
+        let v = match wire_type {
+            0 => visitor.varint(field_number, r.read_varint()?),
+            1 => visitor.fixed64(field_number, r.read_bit64()?),
+            2 => {
+                let value = r.read_length_delimited()?;
+                match visitor.enter_message(field_number) {
+                    Visit::Continue => {
+                        if !visit_at(value, visitor, depth + 1)? {
+                            return Ok(false);
+                        }
+                        visitor.leave_message(field_number)
+                    }
+                    Visit::Skip => visitor.bytes(field_number, value),
+                    Visit::Stop => Visit::Stop,
+                }
+            }
+            5 => visitor.fixed32(field_number, r.read_bit32()?),
+            _ => return Err(Error::UnexpectedWireDataValue(wire_type)),
+        };
+        if v == Visit::Stop {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// WireIndex は入力を 1 度だけ走査し、field number ごとに値の位置を記録します
+// get / get_all は入力を借用した値を返すため、必要なフィールド以外を decode せずに済みます
+pub struct WireIndex<'a> {
+    fields: HashMap<u128, Vec<WireDataRef<'a>>>,
+}
+
+impl<'a> WireIndex<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        let mut fields: HashMap<u128, Vec<WireDataRef<'a>>> = HashMap::new();
+        let mut r = WireReader::new(bytes);
+        while let Some((field_number, wire_type)) = r.read_tag()? {
+            // length-delimited の値は長さ分読み飛ばし、位置だけを記録する
+            let value = r.read_value(wire_type)?;
+            fields.entry(field_number).or_default().push(value);
+        }
+        Ok(Self { fields })
+    }
+
+    // get は field number の値を返します
+    // 同じフィールドが複数回現れた場合は、protobuf と同様に最後の値を返します
+    pub fn get(&self, field_number: u128) -> Option<WireDataRef<'a>> {
+        self.get_all(field_number).last().copied()
+    }
+
+    // get_all は field number の値を現れた順にすべて返します
+    pub fn get_all(&self, field_number: u128) -> &[WireDataRef<'a>] {
+        self.fields
+            .get(&field_number)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn contains(&self, field_number: u128) -> bool {
+        self.fields.contains_key(&field_number)
+    }
+}
+
 // decode_wire_binary decode wire format. return Vec included red filed.
 pub fn decode_wire_binary(data: &mut Cursor<&[u8]>) -> Result<Vec<WireStruct>> {
     let mut v = Vec::new();
@@ -436,4 +842,334 @@ mod tests {
             println!("{:?}", c.seek(SeekFrom::End(0)));
         }
     }
+
+    #[test]
+    fn test_wire_reader() {
+        let bytes: &[u8] = &[
+            0b00001000, 0b10101100, 0b00000010, // 1: varint 300
+            0b00010010, 0b00000011, 0b01100001, 0b01100010, 0b01100011, // 2: "abc"
+            0b00011101, 0b00000001, 0b00000000, 0b00000000, 0b00000000, // 3: fixed32 1
+            0b00100001, 0b00000010, 0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00000000,
+            0b00000000, 0b00000000, // 4: fixed64 2
+        ];
+        let mut r = WireReader::new(bytes);
+        assert_eq!(r.read_tag().unwrap(), Some((1, 0)));
+        assert_eq!(r.read_varint().unwrap(), 300);
+        assert_eq!(r.read_tag().unwrap(), Some((2, 2)));
+        let v = r.read_length_delimited().unwrap();
+        assert_eq!(v, b"abc");
+        // 入力を借用している
+        assert!(std::ptr::eq(v.as_ptr(), bytes[5..].as_ptr()));
+        assert_eq!(r.position(), 8);
+        assert_eq!(r.read_tag().unwrap(), Some((3, 5)));
+        assert_eq!(r.read_bit32().unwrap(), [1, 0, 0, 0]);
+        assert_eq!(r.read_tag().unwrap(), Some((4, 1)));
+        r.skip(1).unwrap();
+        assert!(r.is_empty());
+        assert_eq!(r.read_tag().unwrap(), None);
+
+        // decode_wire_binary と同じ結果になる
+        let mut r = WireReader::new(bytes);
+        let mut c = Cursor::new(bytes);
+        for sw in decode_wire_binary(&mut c).unwrap() {
+            let (field_number, wire_type) = r.read_tag().unwrap().unwrap();
+            assert_eq!(field_number, sw.field_number());
+            assert_eq!(wire_type, sw.wire_type().type_number());
+            r.skip(wire_type).unwrap();
+        }
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn test_wire_reader_error() {
+        // MSB が立ったまま終わる
+        let mut r = WireReader::new(&[0b10000000]);
+        assert!(matches!(r.read_varint(), Err(Error::UnexpectedFormat)));
+        // u128 に収まらない
+        let mut r = WireReader::new(&[0xff; 20]);
+        assert!(matches!(r.read_varint(), Err(Error::UnexpectedFormat)));
+        // 長さに対して入力が足りない
+        let mut r = WireReader::new(&[0b00000011, 0b01100001]);
+        assert!(matches!(r.read_length_delimited(), Err(Error::Io(_))));
+        let mut r = WireReader::new(&[0, 0, 0]);
+        assert!(matches!(r.read_bit32(), Err(Error::Io(_))));
+        // group は未対応
+        let mut r = WireReader::new(&[]);
+        assert!(matches!(r.skip(3), Err(Error::UnexpectedWireDataValue(3))));
+    }
+
+    #[test]
+    fn test_push_decoder() {
+        let bytes: &[u8] = &[
+            0b00001000, 0b10101100, 0b00000010, // 1: varint 300
+            0b00010010, 0b00000011, 0b01100001, 0b01100010, 0b01100011, // 2: "abc"
+            0b00011101, 0b00000001, 0b00000000, 0b00000000, 0b00000000, // 3: fixed32 1
+            0b00100001, 0b00000010, 0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00000000,
+            0b00000000, 0b00000000, // 4: fixed64 2
+        ];
+        let mut c = Cursor::new(bytes);
+        let expected = decode_wire_binary(&mut c).unwrap();
+
+        // どのように分割しても同じ結果になる
+        for chunk_size in 1..=bytes.len() {
+            let mut d = PushDecoder::new();
+            let mut got = Vec::new();
+            for chunk in bytes.chunks(chunk_size) {
+                d.feed(chunk);
+                while let Decoded::Struct(sw) = d.next().unwrap() {
+                    got.push(sw);
+                }
+            }
+            assert!(d.is_idle());
+            d.finish().unwrap();
+            assert_eq!(got, expected);
+        }
+    }
+
+    #[test]
+    fn test_push_decoder_need_more() {
+        let mut d = PushDecoder::new();
+        assert_eq!(d.next().unwrap(), Decoded::NeedMore(1));
+        // tag の途中
+        d.feed(&[0b10010010]);
+        assert_eq!(d.next().unwrap(), Decoded::NeedMore(1));
+        assert!(d.finish().is_err());
+        // field 18, length-delimited, 長さ 3 のうち 1 バイト
+        d.feed(&[0b00000001, 0b00000011, 0b01100001]);
+        assert_eq!(d.next().unwrap(), Decoded::NeedMore(2));
+        d.feed(&[0b01100010]);
+        assert_eq!(d.next().unwrap(), Decoded::NeedMore(1));
+        d.feed(&[0b01100011]);
+        assert_eq!(
+            d.next().unwrap(),
+            Decoded::Struct(WireStruct::new(
+                18,
+                WireData::LengthDelimited(WireDataLengthDelimited::new(b"abc".to_vec()))
+            ))
+        );
+        // fixed64 は残りのバイト数を返す
+        d.feed(&[0b00001001, 1, 2, 3]);
+        assert_eq!(d.next().unwrap(), Decoded::NeedMore(5));
+        d.feed(&[0; 5]);
+        assert!(matches!(d.next().unwrap(), Decoded::Struct(_)));
+        assert!(d.is_idle());
+
+        // u128 に収まらない varint はエラー
+        let mut d2 = PushDecoder::new();
+        d2.feed(&[0xff; 19]);
+        assert!(matches!(d2.next(), Err(Error::UnexpectedFormat)));
+
+        // group は未対応
+        d.feed(&[0b00001011]);
+        assert!(matches!(d.next(), Err(Error::UnexpectedWireDataValue(3))));
+    }
+
+    #[test]
+    fn test_push_decoder_max_message_size() {
+        // field 1, length-delimited, 長さ 4
+        let bytes: &[u8] = &[0b00001010, 0b00000100, 1, 2, 3, 4];
+        let mut d = PushDecoder::new().with_max_message_size(4);
+        d.feed(bytes);
+        assert!(matches!(d.next().unwrap(), Decoded::Struct(_)));
+
+        // 長さが上限を超える場合は本体を待たずにエラーになり、状態は変わらない
+        let mut d = PushDecoder::new().with_max_message_size(3);
+        d.feed(&bytes[..2]);
+        assert!(matches!(
+            d.next(),
+            Err(Error::MessageTooLarge { max: 3, got: 4 })
+        ));
+        assert!(!d.is_idle());
+
+        // usize に収まらない長さもエラー
+        let mut d = PushDecoder::new();
+        d.feed(&[0b00001010]);
+        d.feed(&[0xff; 18]);
+        d.feed(&[0x01]);
+        assert!(matches!(d.next(), Err(Error::MessageTooLarge { .. })));
+
+        // 既定の上限
+        let mut d = PushDecoder::new();
+        d.feed(&[0b00001010, 0x80, 0x80, 0x80, 0x80, 0x01]);
+        assert!(matches!(
+            d.next(),
+            Err(Error::MessageTooLarge {
+                max: DEFAULT_MAX_MESSAGE_SIZE,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_visit() {
+        // 1: varint 300, 2: embedded { 1: varint 1, 2: "ab" }, 3: fixed32 1, 2: "c"
+        let bytes: &[u8] = &[
+            0b00001000, 0b10101100, 0b00000010, // 1
+            0b00010010, 0b00000110, // 2
+            0b00001000, 0b00000001, 0b00010010, 0b00000010, 0b01100001, 0b01100010, 0b00011101,
+            0b00000001, 0b00000000, 0b00000000, 0b00000000, // 3
+            0b00010010, 0b00000001, 0b01100011, // 2
+        ];
+
+        // events は呼び出されたコールバックを記録します
+        struct Recorder {
+            events: Vec<String>,
+            enter: Visit,
+            stop_at: Option<u128>,
+        }
+        impl WireVisitor for Recorder {
+            fn varint(&mut self, field_number: u128, value: u128) -> Visit {
+                self.events
+                    .push(format!("varint({}, {})", field_number, value));
+                if self.stop_at == Some(value) {
+                    return Visit::Stop;
+                }
+                Visit::Continue
+            }
+            fn fixed32(&mut self, field_number: u128, value: [u8; 4]) -> Visit {
+                self.events.push(format!(
+                    "fixed32({}, {})",
+                    field_number,
+                    u32::from_le_bytes(value)
+                ));
+                Visit::Continue
+            }
+            fn bytes(&mut self, field_number: u128, value: &[u8]) -> Visit {
+                self.events
+                    .push(format!("bytes({}, {:?})", field_number, value));
+                Visit::Continue
+            }
+            fn enter_message(&mut self, field_number: u128) -> Visit {
+                // 外側の field 2 のみ embedded message として走査する
+                if self.events.len() > 1 {
+                    return Visit::Skip;
+                }
+                self.events.push(format!("enter({})", field_number));
+                self.enter
+            }
+            fn leave_message(&mut self, field_number: u128) -> Visit {
+                self.events.push(format!("leave({})", field_number));
+                Visit::Continue
+            }
+        }
+        let new = |enter, stop_at| Recorder {
+            events: Vec::new(),
+            enter,
+            stop_at,
+        };
+        {
+            let mut v = new(Visit::Continue, None);
+            assert!(visit(bytes, &mut v).unwrap());
+            assert_eq!(
+                v.events,
+                vec![
+                    "varint(1, 300)",
+                    "enter(2)",
+                    "varint(1, 1)",
+                    "bytes(2, [97, 98])",
+                    "leave(2)",
+                    "fixed32(3, 1)",
+                    "bytes(2, [99])",
+                ]
+            );
+        }
+        {
+            // 中を走査しない
+            let mut v = new(Visit::Skip, None);
+            assert!(visit(bytes, &mut v).unwrap());
+            assert_eq!(
+                v.events,
+                vec![
+                    "varint(1, 300)",
+                    "enter(2)",
+                    "bytes(2, [8, 1, 18, 2, 97, 98])",
+                    "fixed32(3, 1)",
+                    "bytes(2, [99])",
+                ]
+            );
+        }
+        {
+            // embedded message の中で終了する
+            let mut v = new(Visit::Continue, Some(1));
+            assert!(!visit(bytes, &mut v).unwrap());
+            assert_eq!(v.events, vec!["varint(1, 300)", "enter(2)", "varint(1, 1)"]);
+        }
+        {
+            let mut v = new(Visit::Stop, None);
+            assert!(!visit(bytes, &mut v).unwrap());
+            assert_eq!(v.events, vec!["varint(1, 300)", "enter(2)"]);
+        }
+        {
+            // 途中で途切れている
+            let mut v = new(Visit::Continue, None);
+            assert!(visit(&bytes[..4], &mut v).is_err());
+        }
+    }
+
+    #[test]
+    fn test_visit_depth() {
+        // Deep は常に embedded message の中を走査します
+        struct Deep;
+        impl WireVisitor for Deep {
+            fn enter_message(&mut self, _field_number: u128) -> Visit {
+                Visit::Continue
+            }
+        }
+        // 1: { 1: { ... } } を後ろから組み立てる
+        let nested = |depth: usize| {
+            let mut buf = Vec::new();
+            for _ in 0..depth {
+                let len = buf.len();
+                crate::encode::push_variant_reversed(&mut buf, len as u128);
+                buf.push(0b00001010);
+            }
+            buf.reverse();
+            buf
+        };
+        assert!(visit(&nested(MAX_VISIT_DEPTH), &mut Deep).unwrap());
+        assert!(matches!(
+            visit(&nested(MAX_VISIT_DEPTH + 1), &mut Deep),
+            Err(Error::NestedTooDeeply(MAX_VISIT_DEPTH))
+        ));
+        assert!(visit(&nested(400_000), &mut Deep).is_err());
+    }
+
+    #[test]
+    fn test_wire_index() {
+        let bytes: &[u8] = &[
+            0b00001000, 0b10101100, 0b00000010, // 1: varint 300
+            0b00010010, 0b00000011, 0b01100001, 0b01100010, 0b01100011, // 2: "abc"
+            0b00011101, 0b00000001, 0b00000000, 0b00000000, 0b00000000, // 3: fixed32 1
+            0b00010010, 0b00000001, 0b01100100, // 2: "d"
+        ];
+        let index = WireIndex::new(bytes).unwrap();
+        assert_eq!(index.get(1), Some(WireDataRef::Varint(300)));
+        assert_eq!(index.get(3), Some(WireDataRef::Bit32([1, 0, 0, 0])));
+        // 最後の値を返す
+        assert_eq!(index.get(2), Some(WireDataRef::LengthDelimited(b"d")));
+        assert_eq!(
+            index.get_all(2),
+            &[
+                WireDataRef::LengthDelimited(b"abc"),
+                WireDataRef::LengthDelimited(b"d")
+            ]
+        );
+        assert!(index.contains(3));
+        assert!(!index.contains(4));
+        assert_eq!(index.get(4), None);
+        assert!(index.get_all(4).is_empty());
+
+        // 入力を借用している
+        let Some(WireDataRef::LengthDelimited(v)) = index.get_all(2).first().copied() else {
+            panic!("field 2 is not found");
+        };
+        assert!(std::ptr::eq(v.as_ptr(), bytes[5..].as_ptr()));
+        assert_eq!(
+            index.get(1).unwrap().to_wire_data(),
+            WireData::Varint(WireDataVarint::new(300))
+        );
+
+        // 途中で途切れている
+        assert!(WireIndex::new(&bytes[..6]).is_err());
+    }
 }
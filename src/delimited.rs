@@ -0,0 +1,90 @@
+use std::io::{ErrorKind, Read, Write};
+use std::marker::PhantomData;
+
+use crate::decode::read_varint;
+use crate::encode::push_variant_reversed;
+use crate::wire::Proto;
+use crate::{Error, Result};
+
+// write_delimited は 長さ（varint）を先頭に付けたメッセージを w に書き込みます
+// protobuf の writeDelimitedTo と同じ形式です
+pub fn write_delimited<T: Proto, W: Write>(value: &T, w: &mut W) -> Result<()> {
+    // 後ろから書き込むため、長さは値を書き込んだ後に付け足せる
+    let mut buf = Vec::new();
+    value.encode_reversed(&mut buf)?;
+    let len = buf.len();
+    push_variant_reversed(&mut buf, len as u128);
+    buf.reverse();
+    w.write_all(&buf)?;
+    Ok(())
+}
+
+// read_delimited は r から長さ付きのメッセージを 1 つ読み込みます
+// protobuf の parseDelimitedFrom と同じ形式です
+// レコードの先頭で入力が終わっている場合は None を、レコードの途中で終わっている場合はエラーを返します
+pub fn read_delimited<T: Proto, R: Read>(r: &mut R) -> Result<Option<T>> {
+    let mut first = [0; 1];
+    loop {
+        match r.read(&mut first) {
+            Ok(0) => return Ok(None),
+            Ok(_) => break,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let len = read_varint(&mut (&first[..]).chain(&mut *r))?;
+    let len = usize::try_from(len)?;
+    // 長さが不正に大きい場合に備え、先に確保はせず読み込んだ分だけ確保する
+    let mut buf = Vec::new();
+    r.by_ref().take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(Error::TruncatedRecord {
+            want: len,
+            got: buf.len(),
+        });
+    }
+    T::parse(&buf).map(Some)
+}
+
+// DelimitedReader は 長さ付きのメッセージが連続する入力を、メッセージごとに読み込むイテレータです
+// 入力の終わりで None を返し、エラーの後は None を返し続けます
+pub struct DelimitedReader<R, T> {
+    reader: R,
+    done: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<R: Read, T: Proto> DelimitedReader<R, T> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            done: false,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read, T: Proto> Iterator for DelimitedReader<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match read_delimited(&mut self.reader) {
+            Ok(Some(v)) => Some(Ok(v)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
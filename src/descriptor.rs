@@ -0,0 +1,113 @@
+use crate::parser::VariantToValue;
+use crate::wire::TypeVairant;
+use crate::Result;
+
+// MessageDescriptor は derive した Proto の型の定義（型名とフィールド）です
+// Proto::descriptor で取得し、decode::diagnose_as などスキーマを必要とする処理で使います
+#[derive(Debug, Clone)]
+pub struct MessageDescriptor {
+    pub name: &'static str,
+    pub fields: Vec<FieldDescriptor>,
+}
+
+impl MessageDescriptor {
+    // field は field number に対応するフィールドの定義を返します
+    pub fn field(&self, number: u128) -> Option<&FieldDescriptor> {
+        self.fields.iter().find(|f| f.number == number)
+    }
+}
+
+// FieldDescriptor は #[def(...)] で定義したフィールドの情報です
+#[derive(Debug, Clone)]
+pub struct FieldDescriptor {
+    pub name: &'static str,
+    pub number: u128,
+    pub ty: FieldType,
+    pub repeated: bool,
+    pub packed: bool,
+    // message は embedded message の型の定義を返す関数です
+    // 再帰的な型でも定義を組み立てられるよう、必要になった時に呼び出します
+    pub message: Option<fn() -> Option<MessageDescriptor>>,
+}
+
+// FieldType は def_type に対応するフィールドの型です
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+    Sint32,
+    Sint64,
+    Bool,
+    Enum,
+    Fixed64,
+    Sfixed64,
+    Double,
+    String,
+    Bytes,
+    Message,
+    Fixed32,
+    Sfixed32,
+    Float,
+}
+
+impl FieldType {
+    // wire_type は値の wire type を返します（packed の場合を除く）
+    pub fn wire_type(self) -> u128 {
+        match self {
+            FieldType::Int32
+            | FieldType::Int64
+            | FieldType::Uint32
+            | FieldType::Uint64
+            | FieldType::Sint32
+            | FieldType::Sint64
+            | FieldType::Bool
+            | FieldType::Enum => 0,
+            FieldType::Fixed64 | FieldType::Sfixed64 | FieldType::Double => 1,
+            FieldType::String | FieldType::Bytes | FieldType::Message => 2,
+            FieldType::Fixed32 | FieldType::Sfixed32 | FieldType::Float => 5,
+        }
+    }
+
+    // is_scalar は packed にできる型かどうかを返します
+    pub fn is_scalar(self) -> bool {
+        self.wire_type() != 2
+    }
+
+    // check_varint は varint の値がこの型の値として decode できるか検査します
+    pub fn check_varint(self, value: u128) -> Result<()> {
+        match self {
+            FieldType::Int32 => i32::from_valint(value, TypeVairant::Int32).map(|_| ()),
+            FieldType::Int64 => i64::from_valint(value, TypeVairant::Int64).map(|_| ()),
+            FieldType::Uint32 => u32::from_valint(value, TypeVairant::Uint32).map(|_| ()),
+            FieldType::Uint64 => u64::from_valint(value, TypeVairant::Uint64).map(|_| ()),
+            FieldType::Sint32 => i32::from_valint(value, TypeVairant::Sint32).map(|_| ()),
+            FieldType::Sint64 => i64::from_valint(value, TypeVairant::Sint64).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_type() {
+        assert_eq!(FieldType::Sint64.wire_type(), 0);
+        assert_eq!(FieldType::Double.wire_type(), 1);
+        assert_eq!(FieldType::Message.wire_type(), 2);
+        assert_eq!(FieldType::Float.wire_type(), 5);
+        assert!(FieldType::Fixed32.is_scalar());
+        assert!(!FieldType::String.is_scalar());
+
+        assert!(FieldType::Uint32.check_varint(u32::MAX as u128).is_ok());
+        assert!(FieldType::Uint32
+            .check_varint(u32::MAX as u128 + 1)
+            .is_err());
+        assert!(FieldType::Int32.check_varint(u64::MAX as u128).is_ok());
+        assert!(FieldType::Int64.check_varint(u64::MAX as u128 + 1).is_err());
+        assert!(FieldType::Bytes.check_varint(u128::MAX).is_ok());
+    }
+}
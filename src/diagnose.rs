@@ -0,0 +1,563 @@
+use std::fmt::{Display, Write};
+
+use crate::descriptor::{FieldDescriptor, FieldType, MessageDescriptor};
+use crate::encode::variant_len;
+use crate::wire::Proto;
+
+// MAX_DEPTH は diagnose_as で検査する embedded message の入れ子の深さです
+const MAX_DEPTH: usize = 100;
+
+// Diagnostic は diagnose で見つかった問題です
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    // offset は問題のある tag や値の、入力の先頭からのバイト位置です
+    pub offset: usize,
+    // path は問題のあるフィールドのパスです
+    // スキーマがある場合は `Sample.i_inner(14).s_int64(3)`、ない場合は `(3)` の形式で、メッセージ全体の問題は空になります
+    pub path: String,
+    pub kind: DiagnosticKind,
+}
+
+// DiagnosticKind は問題の種類です
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    // UnknownWireType は読み込めない wire type です。値の長さがわからないため、以降の skipped バイトは検査しません
+    UnknownWireType { wire_type: u128, skipped: usize },
+    // Truncated は値の途中で入力が終わっています。want は少なくとも必要なバイト数です
+    Truncated { want: usize, got: usize },
+    // NonCanonicalVarint は必要以上のバイト数で encode された varint です
+    NonCanonicalVarint { len: usize, canonical_len: usize },
+    // InvalidUtf8 は string の値が UTF-8 として正しくありません
+    InvalidUtf8 { valid_up_to: usize },
+    // TrailingGarbage は最後のフィールドの後に残った、フィールドとして読み込めないバイト列です
+    TrailingGarbage { len: usize },
+    // InvalidFieldNumber は field number が 0 です
+    InvalidFieldNumber,
+    // WireTypeMismatch はスキーマの型と wire type が一致しません
+    WireTypeMismatch { expected: u128, found: u128 },
+    // InvalidValue はスキーマの型の値として decode できません
+    InvalidValue { reason: String },
+    // NestedTooDeeply は embedded message の入れ子が MAX_DEPTH を超えています。その中は検査しません
+    NestedTooDeeply,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "at byte {}: {}", self.offset, self.kind)
+        } else {
+            write!(
+                f,
+                "at byte {} in `{}`: {}",
+                self.offset, self.path, self.kind
+            )
+        }
+    }
+}
+
+impl Display for DiagnosticKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiagnosticKind::UnknownWireType { wire_type, skipped } => write!(
+                f,
+                "unknown wire type {}. {} bytes after it are not checked",
+                wire_type, skipped
+            ),
+            DiagnosticKind::Truncated { want, got } => {
+                write!(f, "truncated value. got={}, want={}", got, want)
+            }
+            DiagnosticKind::NonCanonicalVarint { len, canonical_len } => write!(
+                f,
+                "non-canonical varint. len={}, canonical len={}",
+                len, canonical_len
+            ),
+            DiagnosticKind::InvalidUtf8 { valid_up_to } => {
+                write!(f, "invalid utf-8 after {} bytes", valid_up_to)
+            }
+            DiagnosticKind::TrailingGarbage { len } => {
+                write!(f, "{} bytes of trailing garbage", len)
+            }
+            DiagnosticKind::InvalidFieldNumber => write!(f, "field number 0 is not allowed"),
+            DiagnosticKind::WireTypeMismatch { expected, found } => {
+                write!(f, "unexpected wire type. got={}, want={}", found, expected)
+            }
+            DiagnosticKind::InvalidValue { reason } => write!(f, "invalid value. {}", reason),
+            DiagnosticKind::NestedTooDeeply => {
+                write!(f, "nested too deeply. the value is not checked")
+            }
+        }
+    }
+}
+
+// diagnose は bytes を最後まで読み進め、見つかった問題をすべて返します
+// decode と異なり、問題があっても読み進められる限りは続けます
+// スキーマがないため、length-delimited の値の中身は検査しません
+pub fn diagnose(bytes: &[u8]) -> Vec<Diagnostic> {
+    let mut d = Diagnoser::default();
+    d.message(bytes, 0, None, 0);
+    d.found
+}
+
+// diagnose_as は T の定義に従って bytes を検査します
+// diagnose の検査に加えて、wire type と値が型に合うか、string が UTF-8 として正しいかを検査し、
+// embedded message の中も再帰的に検査します
+pub fn diagnose_as<T: Proto>(bytes: &[u8]) -> Vec<Diagnostic> {
+    let descriptor = T::descriptor();
+    let mut d = Diagnoser::default();
+    if let Some(m) = &descriptor {
+        d.path.push_str(m.name);
+    }
+    d.message(bytes, 0, descriptor.as_ref(), 0);
+    d.found
+}
+
+// read_varint は bytes の先頭の varint を読み込み、値とバイト数を返します
+// MSB が 0 のバイトの前に入力が終わっている場合は、読み込んだバイト数をエラーとして返します
+// u128 に収まらない上位のビットは無視します
+fn read_varint(bytes: &[u8]) -> std::result::Result<(u128, usize), usize> {
+    let mut sum: u128 = 0;
+    for (i, b) in bytes.iter().enumerate() {
+        if i * 7 < 128 {
+            sum |= ((b & 0b01111111) as u128) << (i * 7);
+        }
+        if b & 0b10000000 == 0 {
+            return Ok((sum, i + 1));
+        }
+    }
+    Err(bytes.len())
+}
+
+#[derive(Default)]
+struct Diagnoser {
+    found: Vec<Diagnostic>,
+    // path は検査中のフィールドのパスです
+    // 入れ子ごとに String を作らないよう、フィールドに入る時に追加し、抜ける時に元の長さに戻します
+    path: String,
+}
+
+impl Diagnoser {
+    // report は検査中のフィールドの問題を記録します
+    fn report(&mut self, offset: usize, kind: DiagnosticKind) {
+        self.found.push(Diagnostic {
+            offset,
+            path: self.path.clone(),
+            kind,
+        });
+    }
+
+    // varint は varint を読み込み、必要以上のバイト数で encode されている場合は報告します
+    // 入力が途中で終わっている場合は報告し、None を返します
+    fn varint(&mut self, bytes: &[u8], offset: usize) -> Option<(u128, usize)> {
+        match read_varint(bytes) {
+            Ok((v, len)) => {
+                let canonical_len = variant_len(v);
+                if len != canonical_len {
+                    self.report(
+                        offset,
+                        DiagnosticKind::NonCanonicalVarint { len, canonical_len },
+                    );
+                }
+                Some((v, len))
+            }
+            Err(got) => {
+                self.report(offset, DiagnosticKind::Truncated { want: got + 1, got });
+                None
+            }
+        }
+    }
+
+    // message は bytes をメッセージとして検査します
+    // base は bytes の先頭の、入力全体の先頭からのバイト位置、depth は embedded message の入れ子の深さです
+    fn message(
+        &mut self,
+        bytes: &[u8],
+        base: usize,
+        descriptor: Option<&MessageDescriptor>,
+        depth: usize,
+    ) {
+        let path_len = self.path.len();
+        self.fields(bytes, base, descriptor, depth, path_len);
+        self.path.truncate(path_len);
+    }
+
+    // fields は message のフィールドを先頭から検査します
+    // path_len はメッセージのパスの長さで、フィールドごとにパスをそこまで戻します
+    fn fields(
+        &mut self,
+        bytes: &[u8],
+        base: usize,
+        descriptor: Option<&MessageDescriptor>,
+        depth: usize,
+        path_len: usize,
+    ) {
+        let mut pos = 0;
+        while pos < bytes.len() {
+            self.path.truncate(path_len);
+            let tag_offset = base + pos;
+            // tag が読み込めない場合、残りはフィールドではない
+            let (tag, len) = match read_varint(&bytes[pos..]) {
+                Ok(x) => x,
+                Err(got) => {
+                    self.report(tag_offset, DiagnosticKind::TrailingGarbage { len: got });
+                    return;
+                }
+            };
+            let (number, wire_type) = (tag >> 3, tag & 7);
+            let field = descriptor.and_then(|m| m.field(number));
+            if path_len > 0 {
+                self.path.push('.');
+            }
+            match field {
+                Some(f) => write!(self.path, "{}({})", f.name, number),
+                None => write!(self.path, "({})", number),
+            }
+            .unwrap_or_default();
+            let canonical_len = variant_len(tag);
+            if len != canonical_len {
+                self.report(
+                    tag_offset,
+                    DiagnosticKind::NonCanonicalVarint { len, canonical_len },
+                );
+            }
+            if number == 0 {
+                self.report(tag_offset, DiagnosticKind::InvalidFieldNumber);
+            }
+            pos += len;
+            let value_offset = base + pos;
+            let rest = &bytes[pos..];
+
+            if let Some(f) = field {
+                let packed = wire_type == 2 && f.repeated && f.ty.is_scalar();
+                if wire_type != f.ty.wire_type() && !packed && matches!(wire_type, 0 | 1 | 2 | 5) {
+                    self.report(
+                        tag_offset,
+                        DiagnosticKind::WireTypeMismatch {
+                            expected: f.ty.wire_type(),
+                            found: wire_type,
+                        },
+                    );
+                }
+            }
+
+            let size = match wire_type {
+                0 => match self.varint(rest, value_offset) {
+                    Some((v, len)) => {
+                        if let Some(f) = field.filter(|f| f.ty.wire_type() == 0) {
+                            self.check_varint(f, v, value_offset);
+                        }
+                        len
+                    }
+                    None => return,
+                },
+                1 | 5 => {
+                    let want = if wire_type == 1 { 8 } else { 4 };
+                    if rest.len() < want {
+                        self.report(
+                            value_offset,
+                            DiagnosticKind::Truncated {
+                                want,
+                                got: rest.len(),
+                            },
+                        );
+                        return;
+                    }
+                    want
+                }
+                2 => {
+                    let Some((len, len_size)) = self.varint(rest, value_offset) else {
+                        return;
+                    };
+                    let got = rest.len() - len_size;
+                    let len = match usize::try_from(len) {
+                        Ok(len) if len <= got => len,
+                        _ => {
+                            self.report(
+                                value_offset,
+                                DiagnosticKind::Truncated {
+                                    want: usize::try_from(len).unwrap_or(usize::MAX),
+                                    got,
+                                },
+                            );
+                            return;
+                        }
+                    };
+                    if let Some(f) = field {
+                        let value = &rest[len_size..len_size + len];
+                        self.length_delimited(f, value, value_offset + len_size, depth);
+                    }
+                    len_size + len
+                }
+                _ => {
+                    self.report(
+                        tag_offset,
+                        DiagnosticKind::UnknownWireType {
+                            wire_type,
+                            skipped: rest.len(),
+                        },
+                    );
+                    return;
+                }
+            };
+            pos += size;
+        }
+    }
+
+    fn check_varint(&mut self, f: &FieldDescriptor, value: u128, offset: usize) {
+        if let Err(e) = f.ty.check_varint(value) {
+            self.report(
+                offset,
+                DiagnosticKind::InvalidValue {
+                    reason: e.to_string(),
+                },
+            );
+        }
+    }
+
+    // length_delimited は スキーマに従って length-delimited の値の中身を検査します
+    // offset は値（長さの後）の、入力全体の先頭からのバイト位置です
+    fn length_delimited(&mut self, f: &FieldDescriptor, value: &[u8], offset: usize, depth: usize) {
+        match f.ty {
+            FieldType::String => {
+                if let Err(e) = std::str::from_utf8(value) {
+                    self.report(
+                        offset + e.valid_up_to(),
+                        DiagnosticKind::InvalidUtf8 {
+                            valid_up_to: e.valid_up_to(),
+                        },
+                    );
+                }
+            }
+            // 入れ子が深すぎる場合は報告し、中は検査しない
+            FieldType::Message if depth >= MAX_DEPTH => {
+                self.report(offset, DiagnosticKind::NestedTooDeeply);
+            }
+            FieldType::Message => {
+                let descriptor = f.message.and_then(|m| m());
+                self.message(value, offset, descriptor.as_ref(), depth + 1);
+            }
+            FieldType::Bytes => {}
+            ty if f.repeated => self.packed(f, ty, value, offset),
+            _ => {}
+        }
+    }
+
+    // packed は packed repeated fields の値を検査します
+    fn packed(&mut self, f: &FieldDescriptor, ty: FieldType, value: &[u8], offset: usize) {
+        match ty.wire_type() {
+            0 => {
+                let mut pos = 0;
+                while pos < value.len() {
+                    let Some((v, len)) = self.varint(&value[pos..], offset + pos) else {
+                        return;
+                    };
+                    self.check_varint(f, v, offset + pos);
+                    pos += len;
+                }
+            }
+            wire_type => {
+                let size = if wire_type == 1 { 8 } else { 4 };
+                let rest = value.len() % size;
+                if rest != 0 {
+                    self.report(
+                        offset + value.len() - rest,
+                        DiagnosticKind::Truncated {
+                            want: size,
+                            got: rest,
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(found: Vec<Diagnostic>) -> Vec<(usize, String, DiagnosticKind)> {
+        found
+            .into_iter()
+            .map(|d| (d.offset, d.path, d.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_diagnose() {
+        // 問題がない
+        assert!(diagnose(&[0b00001000, 0b10101100, 0b00000010, 0b00010010, 1, 0xff]).is_empty());
+
+        // 冗長な varint（値と tag）、field number 0 の後も読み進める
+        let bytes: &[u8] = &[
+            0b00001000, 0b10000001, 0b00000000, // 1: 1
+            0b10000000, 0b00000000, 0b00000001, // 0: 1 (tag が冗長)
+            0b00010101, 1, 2, // 2: fixed32 が途中で終わる
+        ];
+        assert_eq!(
+            kinds(diagnose(bytes)),
+            vec![
+                (
+                    1,
+                    "(1)".to_string(),
+                    DiagnosticKind::NonCanonicalVarint {
+                        len: 2,
+                        canonical_len: 1
+                    }
+                ),
+                (
+                    3,
+                    "(0)".to_string(),
+                    DiagnosticKind::NonCanonicalVarint {
+                        len: 2,
+                        canonical_len: 1
+                    }
+                ),
+                (3, "(0)".to_string(), DiagnosticKind::InvalidFieldNumber),
+                (
+                    7,
+                    "(2)".to_string(),
+                    DiagnosticKind::Truncated { want: 4, got: 2 }
+                ),
+            ]
+        );
+
+        // 未知の wire type の後は検査できない
+        assert_eq!(
+            kinds(diagnose(&[0b00001000, 1, 0b00001111, 1, 2])),
+            vec![(
+                2,
+                "(1)".to_string(),
+                DiagnosticKind::UnknownWireType {
+                    wire_type: 7,
+                    skipped: 2
+                }
+            )]
+        );
+
+        // length-delimited の長さが足りない
+        assert_eq!(
+            kinds(diagnose(&[0b00010010, 5, 1, 2])),
+            vec![(
+                1,
+                "(2)".to_string(),
+                DiagnosticKind::Truncated { want: 5, got: 2 }
+            )]
+        );
+
+        // 最後の tag が途中で終わっている
+        assert_eq!(
+            kinds(diagnose(&[0b00001000, 1, 0xff, 0xff])),
+            vec![(2, String::new(), DiagnosticKind::TrailingGarbage { len: 2 })]
+        );
+        assert_eq!(
+            diagnose(&[0b00001000, 1, 0xff])[0].to_string(),
+            "at byte 2: 1 bytes of trailing garbage"
+        );
+    }
+
+    #[test]
+    fn test_diagnose_as() {
+        struct Inner;
+        impl Proto for Inner {
+            fn parse(_bytes: &[u8]) -> crate::Result<Self> {
+                Ok(Inner)
+            }
+            fn encode_reversed(&self, _buf: &mut Vec<u8>) -> crate::Result<()> {
+                Ok(())
+            }
+            fn encoded_len(&self) -> usize {
+                0
+            }
+            fn merge(&mut self, _other: Self) {}
+            fn descriptor() -> Option<MessageDescriptor> {
+                Some(MessageDescriptor {
+                    name: "Inner",
+                    fields: vec![
+                        FieldDescriptor {
+                            name: "s_string",
+                            number: 1,
+                            ty: FieldType::String,
+                            repeated: false,
+                            packed: false,
+                            message: None,
+                        },
+                        FieldDescriptor {
+                            name: "r_uint32",
+                            number: 2,
+                            ty: FieldType::Uint32,
+                            repeated: true,
+                            packed: true,
+                            message: None,
+                        },
+                        FieldDescriptor {
+                            name: "i_inner",
+                            number: 3,
+                            ty: FieldType::Message,
+                            repeated: false,
+                            packed: false,
+                            message: Some(Inner::descriptor),
+                        },
+                    ],
+                })
+            }
+        }
+
+        let bytes: &[u8] = &[
+            0b00001010, 2, b'a', 0xff, // 1: 不正な UTF-8
+            0b00010010, 3, 1, 0xff, 0xff, // 2: packed の varint が途中で終わる
+            0b00011010, 8, 0b00001000, 1, // 3: 1 は wire type が異なる
+            0b00010000, 0x80, 0x80, 0x80, 0x80, 0x10, // 3: 2 は u32 に収まらない
+            0b00100000, 1, // 4: 定義されていない
+        ];
+        let mut found = diagnose_as::<Inner>(bytes);
+        assert!(matches!(
+            found.pop().unwrap(),
+            Diagnostic {
+                offset: 14,
+                kind: DiagnosticKind::InvalidValue { .. },
+                ..
+            }
+        ));
+        assert_eq!(
+            kinds(found.clone()),
+            vec![
+                (
+                    3,
+                    "Inner.s_string(1)".to_string(),
+                    DiagnosticKind::InvalidUtf8 { valid_up_to: 1 }
+                ),
+                (
+                    7,
+                    "Inner.r_uint32(2)".to_string(),
+                    DiagnosticKind::Truncated { want: 3, got: 2 }
+                ),
+                (
+                    11,
+                    "Inner.i_inner(3).s_string(1)".to_string(),
+                    DiagnosticKind::WireTypeMismatch {
+                        expected: 2,
+                        found: 0
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            found[0].to_string(),
+            "at byte 3 in `Inner.s_string(1)`: invalid utf-8 after 1 bytes"
+        );
+
+        // 入れ子が MAX_DEPTH を超える場合は報告し、中は検査しない
+        // 3: { 3: { ... } } を後ろから組み立てる
+        let mut buf = Vec::new();
+        for _ in 0..200_000 {
+            let len = buf.len();
+            crate::encode::push_variant_reversed(&mut buf, len as u128);
+            buf.push(0b00011010);
+        }
+        buf.reverse();
+        let found = diagnose_as::<Inner>(&buf);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, DiagnosticKind::NestedTooDeeply);
+        assert_eq!(found[0].path.matches("i_inner(3)").count(), MAX_DEPTH + 1);
+    }
+}
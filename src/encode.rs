@@ -4,14 +4,70 @@ use std::io::{Cursor, Write};
 
 use crate::wire::WireStruct;
 
+// EncodeOptions は Proto::bytes_with_options などで指定する encode の設定です
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncodeOptions {
+    // deterministic は 同じ値から常に同じバイト列を書き出すかどうかです
+    // フィールドを field number の昇順に書き出し、Lazy などが保持しているバイト列もそのまま使わずに encode し直します
+    pub deterministic: bool,
+    // emit_defaults は 値がゼロ（default）のフィールドも省略せずに書き出すかどうかです
+    // 古い proto2 の reader など、フィールドが明示的に書かれていることを期待する相手に使います
+    pub emit_defaults: bool,
+    // drop_defaults は WireMessage などスキーマなしで保持している値のうち、ゼロ（空）の値を書き出さないかどうかです
+    // 指定しない場合は decode した値をそのまま書き出します。emit_defaults が優先されます
+    pub drop_defaults: bool,
+    // packing は repeated な scalar フィールドを packed で書き出すかどうかです
+    pub packing: Packing,
+    // field_order はフィールドを書き出す順序です
+    pub field_order: FieldOrder,
+}
+
+impl EncodeOptions {
+    // sorts_by_field_number はフィールドを field number の昇順に書き出すかどうかを返します
+    pub fn sorts_by_field_number(&self) -> bool {
+        self.deterministic || self.field_order == FieldOrder::FieldNumber
+    }
+}
+
+// Packing は repeated な scalar フィールドの書き出し方です
+// decode 時はどの書き出し方でも読み込めます
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Packing {
+    // AsDeclared はフィールドの packed 属性に従います
+    #[default]
+    AsDeclared,
+    // Packed は常に packed（1 つの length-delimited）で書き出します
+    Packed,
+    // Unpacked は常に要素ごとに tag を付けて書き出します
+    Unpacked,
+}
+
+impl Packing {
+    // is_packed は packed 属性が declared のフィールドを packed で書き出すかどうかを返します
+    pub fn is_packed(self, declared: bool) -> bool {
+        match self {
+            Packing::AsDeclared => declared,
+            Packing::Packed => true,
+            Packing::Unpacked => false,
+        }
+    }
+}
+
+// FieldOrder はフィールドを書き出す順序です
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FieldOrder {
+    // Declaration は構造体での宣言順です
+    #[default]
+    Declaration,
+    // FieldNumber は field number の昇順です
+    FieldNumber,
+}
+
 // encode_variants decode varint format
 fn encode_variants<T: Write>(data: &mut T, input: u128) -> Result<()> {
-    let mut buf: Vec<u8> = Vec::with_capacity(calc_capacity(input));
+    let mut buf: Vec<u8> = Vec::with_capacity(variant_len(input));
     let mut input = input;
     loop {
-        if input == 0 {
-            break;
-        }
         // 下位7bitずつ読みすすめる
         let mut x: u8 = TryFrom::try_from(input & 0b01111111)?;
         input >>= 7;
@@ -20,13 +76,22 @@ fn encode_variants<T: Write>(data: &mut T, input: u128) -> Result<()> {
             x |= 0b10000000;
         }
         buf.push(x);
+        if input == 0 {
+            break;
+        }
     }
     data.write_all(buf.as_slice())?;
     Ok(())
 }
 
+// write_varint は varint を書き込みます
+// 0 も 1 バイトとして書き込みます
+pub fn write_varint<T: Write>(data: &mut T, input: u128) -> Result<()> {
+    encode_variants(data, input)
+}
+
 // calc_capacity return capacity of buffer
-fn calc_capacity(input: u128) -> usize {
+pub(crate) fn calc_capacity(input: u128) -> usize {
     let mut input = input;
     let mut capacity = 0;
     while input != 0 {
@@ -36,7 +101,147 @@ fn calc_capacity(input: u128) -> usize {
     capacity
 }
 
-pub(crate) fn encode_repeat<T: std::io::Write>(data: &mut T, input: Vec<u128>) -> Result<()> {
+// variant_len は varint として encode した場合のバイト数を返します
+// 0 も 1 バイトとして数えます
+pub fn variant_len(input: u128) -> usize {
+    calc_capacity(input).max(1)
+}
+
+// tag_len は tag を encode した場合のバイト数を返します
+fn tag_len(field_number: u128) -> usize {
+    variant_len(field_number << 3)
+}
+
+// 以下の *_field_len は tag を含むフィールドのバイト数を返します
+// encode_wire_binary と同様に、値がゼロの場合は書き出されないため 0 を返します
+
+pub fn variant_field_len(field_number: u128, input: u128) -> usize {
+    if input == 0 {
+        return 0;
+    }
+    tag_len(field_number) + variant_len(input)
+}
+
+pub fn bit64_field_len(field_number: u128, input: [u8; 8]) -> usize {
+    if input.iter().all(|v| *v == 0) {
+        return 0;
+    }
+    tag_len(field_number) + input.len()
+}
+
+pub fn bit32_field_len(field_number: u128, input: [u8; 4]) -> usize {
+    if input.iter().all(|v| *v == 0) {
+        return 0;
+    }
+    tag_len(field_number) + input.len()
+}
+
+pub fn length_delimited_field_len(field_number: u128, len: usize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    tag_len(field_number) + variant_len(len as u128) + len
+}
+
+// 以下の *_reversed は buf の末尾に逆順で書き込みます
+// upb と同様に後ろから書き込むことで、embedded message の長さを事前に計算せずに済みます
+// 書き込み後に該当範囲を反転させると、正しい順序のバイト列になります
+
+// push_variant_reversed は varint を逆順で書き込みます
+// encode_variants と同様に、0 も 1 バイトとして書き込みます
+pub fn push_variant_reversed(buf: &mut Vec<u8>, input: u128) {
+    let mut tmp = [0; 19];
+    let mut len = 0;
+    let mut input = input;
+    loop {
+        // 下位7bitずつ読みすすめる
+        let mut x = (input & 0b01111111) as u8;
+        input >>= 7;
+        if input != 0 {
+            // MSB を１にする. その他は据え置き
+            x |= 0b10000000;
+        }
+        tmp[len] = x;
+        len += 1;
+        if input == 0 {
+            break;
+        }
+    }
+    buf.extend(tmp[..len].iter().rev());
+}
+
+// push_bytes_reversed は input を逆順で書き込みます
+pub fn push_bytes_reversed(buf: &mut Vec<u8>, input: &[u8]) {
+    buf.extend(input.iter().rev());
+}
+
+// 以下の write_*_field_reversed は tag を含むフィールドを逆順で書き込みます
+// encode_wire_binary と同様に、値がゼロの場合は書き出しません
+// options.emit_defaults が true の場合は、ゼロでも書き出します
+
+pub fn write_variant_field_reversed(
+    buf: &mut Vec<u8>,
+    field_number: u128,
+    input: u128,
+    options: &EncodeOptions,
+) {
+    if input == 0 && !options.emit_defaults {
+        return;
+    }
+    push_variant_reversed(buf, input);
+    push_variant_reversed(buf, field_number << 3);
+}
+
+pub fn write_bit64_field_reversed(
+    buf: &mut Vec<u8>,
+    field_number: u128,
+    input: [u8; 8],
+    options: &EncodeOptions,
+) {
+    if input.iter().all(|v| *v == 0) && !options.emit_defaults {
+        return;
+    }
+    push_bytes_reversed(buf, &input);
+    push_variant_reversed(buf, (field_number << 3) + 1);
+}
+
+pub fn write_bit32_field_reversed(
+    buf: &mut Vec<u8>,
+    field_number: u128,
+    input: [u8; 4],
+    options: &EncodeOptions,
+) {
+    if input.iter().all(|v| *v == 0) && !options.emit_defaults {
+        return;
+    }
+    push_bytes_reversed(buf, &input);
+    push_variant_reversed(buf, (field_number << 3) + 5);
+}
+
+// write_length_delimited_field_reversed は write で値を逆順で書き込んだ後、長さと tag を書き込みます
+// 長さは書き込んだバイト数から求めるため、事前に計算する必要はありません
+pub fn write_length_delimited_field_reversed<F>(
+    buf: &mut Vec<u8>,
+    field_number: u128,
+    options: &EncodeOptions,
+    write: F,
+) -> Result<()>
+where
+    F: FnOnce(&mut Vec<u8>) -> Result<()>,
+{
+    let start = buf.len();
+    write(buf)?;
+    let len = buf.len() - start;
+    if len == 0 && !options.emit_defaults {
+        return Ok(());
+    }
+    push_variant_reversed(buf, len as u128);
+    push_variant_reversed(buf, (field_number << 3) + 2);
+    Ok(())
+}
+
+// encode_repeat は packed repeated fields の値（長さ部分を除く）を書き込みます
+pub fn encode_repeat<T: std::io::Write>(data: &mut T, input: Vec<u128>) -> Result<()> {
     for i in input {
         encode_variants(data, i)?;
     }
@@ -86,8 +291,18 @@ fn encode_struct(data: &mut Cursor<Vec<u8>>, input: WireStruct) -> Result<()> {
 
 // encode_wire_binary decode wire format. return Vec included red filed.
 pub fn encode_wire_binary(data: &mut Cursor<Vec<u8>>, inputs: Vec<WireStruct>) -> Result<()> {
+    encode_wire_binary_with_options(data, inputs, &EncodeOptions::default())
+}
+
+// encode_wire_binary_with_options は options に従って inputs を書き込みます
+// options.emit_defaults が true の場合は、値がゼロの WireStruct も書き出します
+pub fn encode_wire_binary_with_options(
+    data: &mut Cursor<Vec<u8>>,
+    inputs: Vec<WireStruct>,
+    options: &EncodeOptions,
+) -> Result<()> {
     for input in inputs {
-        if input.is_empty() {
+        if input.is_empty() && !options.emit_defaults {
             continue;
         }
         encode_struct(data, input)?;
@@ -101,6 +316,7 @@ mod tests {
     use crate::wire::{
         WireData, WireDataBit32, WireDataBit64, WireDataLengthDelimited, WireDataVarint,
     };
+    use crate::Error;
     #[test]
     fn test_encode_variants() {
         {
@@ -130,10 +346,10 @@ mod tests {
         {
             let mut c = Cursor::new(Vec::new());
             encode_variants(&mut c, 0).unwrap();
-            // assert_eq!(c.position(), 2);
+            assert_eq!(c.position(), 1);
 
             let x: Vec<u8> = c.into_inner();
-            assert_eq!(x, vec![]);
+            assert_eq!(x, vec![0]);
         }
         {
             {
@@ -416,6 +632,158 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_field_len() {
+        // 各関数の結果は encode_wire_binary の結果のバイト数と一致する
+        let check = |ws: WireStruct, got: usize| {
+            let mut c = Cursor::new(Vec::new());
+            encode_wire_binary(&mut c, vec![ws]).unwrap();
+            assert_eq!(c.into_inner().len(), got);
+        };
+        check(
+            WireStruct::new(1000, WireData::Varint(WireDataVarint::new(10467))),
+            variant_field_len(1000, 10467),
+        );
+        check(
+            WireStruct::new(1, WireData::Varint(WireDataVarint::new(0))),
+            variant_field_len(1, 0),
+        );
+        check(
+            WireStruct::new(
+                1,
+                WireData::Bit64(WireDataBit64::new([1, 0, 0, 0, 0, 0, 0, 0])),
+            ),
+            bit64_field_len(1, [1, 0, 0, 0, 0, 0, 0, 0]),
+        );
+        check(
+            WireStruct::new(1, WireData::Bit64(WireDataBit64::new([0; 8]))),
+            bit64_field_len(1, [0; 8]),
+        );
+        check(
+            WireStruct::new(16, WireData::Bit32(WireDataBit32::new([0, 0, 0, 64]))),
+            bit32_field_len(16, [0, 0, 0, 64]),
+        );
+        check(
+            WireStruct::new(
+                4,
+                WireData::LengthDelimited(WireDataLengthDelimited::new(vec![1; 200])),
+            ),
+            length_delimited_field_len(4, 200),
+        );
+        check(
+            WireStruct::new(
+                4,
+                WireData::LengthDelimited(WireDataLengthDelimited::new(vec![])),
+            ),
+            length_delimited_field_len(4, 0),
+        );
+    }
+
+    #[test]
+    fn test_write_field_reversed() {
+        // 反転させた結果は encode_wire_binary の結果と一致する
+        let check = |ws: WireStruct, mut got: Vec<u8>| {
+            let mut c = Cursor::new(Vec::new());
+            encode_wire_binary(&mut c, vec![ws]).unwrap();
+            got.reverse();
+            assert_eq!(c.into_inner(), got);
+        };
+        {
+            let mut buf = Vec::new();
+            write_variant_field_reversed(&mut buf, 1000, 10467, &EncodeOptions::default());
+            check(
+                WireStruct::new(1000, WireData::Varint(WireDataVarint::new(10467))),
+                buf,
+            );
+        }
+        {
+            let mut buf = Vec::new();
+            write_variant_field_reversed(&mut buf, 1, 0, &EncodeOptions::default());
+            check(
+                WireStruct::new(1, WireData::Varint(WireDataVarint::new(0))),
+                buf,
+            );
+        }
+        {
+            let mut buf = Vec::new();
+            write_bit64_field_reversed(
+                &mut buf,
+                1,
+                [1, 0, 0, 0, 0, 0, 0, 0],
+                &EncodeOptions::default(),
+            );
+            check(
+                WireStruct::new(
+                    1,
+                    WireData::Bit64(WireDataBit64::new([1, 0, 0, 0, 0, 0, 0, 0])),
+                ),
+                buf,
+            );
+        }
+        {
+            let mut buf = Vec::new();
+            write_bit32_field_reversed(&mut buf, 16, [0, 0, 0, 64], &EncodeOptions::default());
+            check(
+                WireStruct::new(16, WireData::Bit32(WireDataBit32::new([0, 0, 0, 64]))),
+                buf,
+            );
+        }
+        {
+            let value: Vec<u8> = (0..200).collect();
+            let mut buf = Vec::new();
+            write_length_delimited_field_reversed(&mut buf, 4, &EncodeOptions::default(), |b| {
+                push_bytes_reversed(b, &value);
+                Ok(())
+            })
+            .unwrap();
+            check(
+                WireStruct::new(
+                    4,
+                    WireData::LengthDelimited(WireDataLengthDelimited::new(value)),
+                ),
+                buf,
+            );
+        }
+        {
+            let mut buf = Vec::new();
+            write_length_delimited_field_reversed(&mut buf, 4, &EncodeOptions::default(), |_| {
+                Ok(())
+            })
+            .unwrap();
+            assert!(buf.is_empty());
+        }
+        {
+            let mut buf = Vec::new();
+            let r = write_length_delimited_field_reversed(
+                &mut buf,
+                4,
+                &EncodeOptions::default(),
+                |_| Err(Error::UnexpectedFormat),
+            );
+            assert!(matches!(r, Err(Error::UnexpectedFormat)));
+        }
+    }
+
+    #[test]
+    fn test_write_varint() {
+        let mut v = Vec::new();
+        write_varint(&mut v, 0).unwrap();
+        write_varint(&mut v, 300).unwrap();
+        assert_eq!(v, vec![0b00000000, 0b10101100, 0b00000010]);
+    }
+
+    #[test]
+    fn test_push_variant_reversed() {
+        for v in [0, 1, 300, 12323412, u64::MAX as u128, u128::MAX] {
+            let mut c = Cursor::new(Vec::new());
+            encode_variants(&mut c, v).unwrap();
+            let mut buf = Vec::new();
+            push_variant_reversed(&mut buf, v);
+            buf.reverse();
+            assert_eq!(c.into_inner(), buf);
+        }
+    }
+
     #[test]
     fn bit_lenght() {
         {
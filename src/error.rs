@@ -11,10 +11,48 @@ pub enum Error {
     UnexpectedRepeatSize(u128, u128),
     #[error("no expected type value. got={0}")]
     UnexpectedWireDataValue(u128),
+    #[error("truncated record. got={got}, want={want}")]
+    TruncatedRecord { want: usize, got: usize },
+    #[error("nested too deeply. max depth={0}")]
+    NestedTooDeeply(usize),
+    #[error("message too large. got={got}, max={max}")]
+    MessageTooLarge { max: usize, got: u128 },
+    // Decode は derive した parse で発生したエラーに、フィールドのパスと位置を付けたものです
+    #[error(transparent)]
+    Decode(Box<DecodeError>),
 
     // Parse/type mapping
     #[error("unexpected type. got={got}, want={want}")]
     UnexpectedType { want: String, got: String },
+    #[error("invalid path `{path}`. {reason}")]
+    InvalidPath { path: String, reason: &'static str },
+
+    // JSON
+    #[error("invalid json at byte {offset}. {reason}")]
+    InvalidJson { offset: usize, reason: &'static str },
+    #[error("invalid json value{}. {reason}", json_path(.path))]
+    InvalidJsonValue { path: String, reason: String },
+    #[error("json mapping is not supported for {0}")]
+    JsonUnsupported(&'static str),
+
+    // Text format
+    #[error("invalid text format at line {line}, column {column}. {reason}")]
+    InvalidText {
+        line: usize,
+        column: usize,
+        reason: String,
+    },
+    #[error("text format is not supported for {0}")]
+    TextUnsupported(&'static str),
+
+    // .proto schema
+    #[error("{file}:{line}:{column}: {reason}")]
+    InvalidSchema {
+        file: String,
+        line: usize,
+        column: usize,
+        reason: String,
+    },
 
     // Value constraints
     #[error("value too large for {ty}. max={max}")]
@@ -27,4 +65,162 @@ pub enum Error {
     IntConversion(#[from] std::num::TryFromIntError),
     #[error(transparent)]
     Utf8(#[from] std::string::FromUtf8Error),
+    #[error(transparent)]
+    Utf8Str(#[from] std::str::Utf8Error),
+}
+
+impl Error {
+    // cause は Decode の場合に元のエラーを、それ以外の場合は自身を返します
+    // derive した parse のエラーを、Decode で包まれる前の variant で match するために使います
+    pub fn cause(&self) -> &Error {
+        match self {
+            Error::Decode(e) => e.inner(),
+            e => e,
+        }
+    }
+}
+
+// json_path は InvalidJsonValue のメッセージに含めるフィールドのパスです
+fn json_path(path: &str) -> String {
+    if path.is_empty() {
+        return String::new();
+    }
+    format!(" at `{}`", path)
+}
+
+// FieldSegment は DecodeError のパスを構成するフィールドです
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldSegment {
+    pub name: &'static str,
+    pub number: u128,
+}
+
+// DecodeError は decode に失敗したフィールドのパスと、入力の先頭からのバイト位置を保持するエラーです
+// embedded message の中で発生したエラーは、外側のメッセージの parse を抜けるたびにパスの先頭へフィールドが追加されます
+#[derive(Debug)]
+pub struct DecodeError {
+    message: &'static str,
+    fields: Vec<FieldSegment>,
+    offset: usize,
+    source: Box<Error>,
+}
+
+impl DecodeError {
+    // wrap は message の parse で発生したエラーに、パスと位置を付けます
+    // field は値を読み込んでいたフィールド、offset はその tag の位置、value_offset は値の位置です
+    // source が embedded message の DecodeError の場合は、パスの先頭に field を追加し、位置を value_offset だけずらします
+    pub fn wrap(
+        source: Error,
+        message: &'static str,
+        field: Option<FieldSegment>,
+        offset: usize,
+        value_offset: usize,
+    ) -> Error {
+        match (source, field) {
+            (Error::Decode(mut e), Some(field)) => {
+                e.message = message;
+                e.fields.insert(0, field);
+                e.offset += value_offset;
+                Error::Decode(e)
+            }
+            (e @ Error::Decode(_), None) => e,
+            (source, field) => Error::Decode(Box::new(DecodeError {
+                message,
+                fields: field.into_iter().collect(),
+                offset,
+                source: Box::new(source),
+            })),
+        }
+    }
+
+    // message は最も外側のメッセージの型名を返します
+    pub fn message(&self) -> &'static str {
+        self.message
+    }
+
+    // fields は外側から順にフィールドを返します
+    pub fn fields(&self) -> &[FieldSegment] {
+        &self.fields
+    }
+
+    // path は `Sample.i_inner(14).s_int64(3)` の形式のパスを返します
+    pub fn path(&self) -> String {
+        self.fields
+            .iter()
+            .fold(self.message.to_string(), |mut acc, f| {
+                acc.push_str(&format!(".{}({})", f.name, f.number));
+                acc
+            })
+    }
+
+    // offset は失敗したフィールドの tag の、入力の先頭からのバイト位置を返します
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    // inner は元のエラーを返します
+    pub fn inner(&self) -> &Error {
+        &self.source
+    }
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "failed to decode `{}` at byte {}: {}",
+            self.path(),
+            self.offset,
+            self.source
+        )
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_error() {
+        let field = |name, number| Some(FieldSegment { name, number });
+        // Inner の 2 バイト目から始まる s_int64 で失敗した
+        let e = DecodeError::wrap(Error::UnexpectedFormat, "Inner", field("s_int64", 3), 2, 3);
+        // Inner は Sample の 10 バイト目から始まる
+        let e = DecodeError::wrap(e, "Sample", field("i_inner", 14), 8, 10);
+        let Error::Decode(d) = &e else {
+            panic!("unexpected error: {:?}", e);
+        };
+        assert_eq!(d.path(), "Sample.i_inner(14).s_int64(3)");
+        assert_eq!(d.offset(), 12);
+        assert!(matches!(d.inner(), Error::UnexpectedFormat));
+        assert!(matches!(e.cause(), Error::UnexpectedFormat));
+        assert!(matches!(
+            Error::UnexpectedFormat.cause(),
+            Error::UnexpectedFormat
+        ));
+        assert_eq!(
+            e.to_string(),
+            "failed to decode `Sample.i_inner(14).s_int64(3)` at byte 12: \
+             unexpected format. end came before MSB cleared"
+        );
+        assert!(std::error::Error::source(&e).is_some());
+
+        // フィールドの外で発生したエラーは、メッセージの型名のみ
+        let e = DecodeError::wrap(Error::UnexpectedFormat, "Sample", None, 5, 5);
+        assert_eq!(
+            e.to_string(),
+            "failed to decode `Sample` at byte 5: unexpected format. end came before MSB cleared"
+        );
+        // 既に DecodeError の場合はそのまま返す
+        let e = DecodeError::wrap(e, "Outer", None, 0, 0);
+        assert!(e
+            .to_string()
+            .starts_with("failed to decode `Sample` at byte 5"));
+    }
 }
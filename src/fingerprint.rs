@@ -0,0 +1,25 @@
+// FNV-1a 64-bit のパラメータです
+// http://www.isthe.com/chongo/tech/comp/fnv/
+const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const PRIME: u64 = 0x100000001b3;
+
+// fnv1a_64 は bytes の FNV-1a 64-bit ハッシュ値を返します
+// 標準ライブラリの Hasher と異なり、アルゴリズムが固定されているため、保存や署名に利用できます
+pub fn fnv1a_64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(OFFSET_BASIS, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fnv1a_64() {
+        // 参照実装のテストベクタ
+        assert_eq!(fnv1a_64(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a_64(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a_64(b"foobar"), 0x85944171f73967e8);
+    }
+}
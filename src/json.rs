@@ -0,0 +1,824 @@
+use crate::parser::VariantEnum;
+use crate::{Error, Result};
+use std::borrow::Cow;
+use std::fmt;
+
+// 以下は proto3 の JSON mapping を扱う処理です
+// derive で生成される to_json_value/from_json_value から使われます
+
+// MAX_DEPTH は JSON を読み込む際に許可する object/array の入れ子の深さです
+const MAX_DEPTH: usize = 100;
+
+// JsonValue は JSON の値です
+// Number は精度を落とさないよう、入力の表記のまま保持します
+// Object はキーの順序を保持します
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    // parse は input を JSON として読み込みます
+    // 値の後に空白以外が続く場合はエラーを返します
+    pub fn parse(input: &str) -> Result<Self> {
+        let mut p = JsonParser {
+            input: input.as_bytes(),
+            pos: 0,
+        };
+        let value = p.value(0)?;
+        p.whitespace();
+        if p.pos != p.input.len() {
+            return Err(p.error("unexpected trailing characters"));
+        }
+        Ok(value)
+    }
+
+    // get は object の key に対応する値を返します（同じ key が複数ある場合は最後の値）
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(o) => o.iter().rev().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, JsonValue::Null)
+    }
+
+    // kind はエラーメッセージで使う値の種類を返します
+    fn kind(&self) -> &'static str {
+        match self {
+            JsonValue::Null => "null",
+            JsonValue::Bool(_) => "bool",
+            JsonValue::Number(_) => "number",
+            JsonValue::String(_) => "string",
+            JsonValue::Array(_) => "array",
+            JsonValue::Object(_) => "object",
+        }
+    }
+}
+
+impl fmt::Display for JsonValue {
+    // fmt は空白を含まない JSON を書き込みます
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonValue::Null => f.write_str("null"),
+            JsonValue::Bool(b) => write!(f, "{}", b),
+            JsonValue::Number(n) => f.write_str(n),
+            JsonValue::String(s) => write_json_string(f, s),
+            JsonValue::Array(a) => {
+                f.write_str("[")?;
+                for (i, v) in a.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                f.write_str("]")
+            }
+            JsonValue::Object(o) => {
+                f.write_str("{")?;
+                for (i, (k, v)) in o.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write_json_string(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+fn write_json_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            '\u{08}' => f.write_str("\\b")?,
+            '\u{0c}' => f.write_str("\\f")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    f.write_str("\"")
+}
+
+struct JsonParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl JsonParser<'_> {
+    fn error(&self, reason: &'static str) -> Error {
+        Error::InvalidJson {
+            offset: self.pos,
+            reason,
+        }
+    }
+
+    fn whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.input.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &[u8], value: JsonValue) -> Result<JsonValue> {
+        if !self.input[self.pos..].starts_with(literal) {
+            return Err(self.error("unexpected character"));
+        }
+        self.pos += literal.len();
+        Ok(value)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<JsonValue> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.whitespace();
+        match self.input.get(self.pos) {
+            None => Err(self.error("unexpected end of input")),
+            Some(b'n') => self.expect(b"null", JsonValue::Null),
+            Some(b't') => self.expect(b"true", JsonValue::Bool(true)),
+            Some(b'f') => self.expect(b"false", JsonValue::Bool(false)),
+            Some(b'"') => self.string().map(JsonValue::String),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.whitespace();
+                if self.input.get(self.pos) == Some(&b']') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                loop {
+                    items.push(self.value(depth + 1)?);
+                    self.whitespace();
+                    match self.input.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(JsonValue::Array(items));
+                        }
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut entries = Vec::new();
+                self.whitespace();
+                if self.input.get(self.pos) == Some(&b'}') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(entries));
+                }
+                loop {
+                    self.whitespace();
+                    if self.input.get(self.pos) != Some(&b'"') {
+                        return Err(self.error("expected string key"));
+                    }
+                    let key = self.string()?;
+                    self.whitespace();
+                    if self.input.get(self.pos) != Some(&b':') {
+                        return Err(self.error("expected `:`"));
+                    }
+                    self.pos += 1;
+                    entries.push((key, self.value(depth + 1)?));
+                    self.whitespace();
+                    match self.input.get(self.pos) {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(JsonValue::Object(entries));
+                        }
+                        _ => return Err(self.error("expected `,` or `}`")),
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => {
+                let len = number_len(&self.input[self.pos..]);
+                if len == 0 {
+                    return Err(self.error("invalid number"));
+                }
+                // number_len は ASCII のみを受け付けるため、UTF-8 として正しい
+                let n = String::from_utf8_lossy(&self.input[self.pos..self.pos + len]);
+                self.pos += len;
+                Ok(JsonValue::Number(n.into_owned()))
+            }
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    // string は `"` から始まる文字列を読み込み、エスケープを解除した値を返します
+    fn string(&mut self) -> Result<String> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let start = self.pos;
+            while let Some(&b) = self.input.get(self.pos) {
+                if b == b'"' || b == b'\\' || b < 0x20 {
+                    break;
+                }
+                self.pos += 1;
+            }
+            // input は &str から作られるため、区切り文字（ASCII）の間は UTF-8 として正しい
+            out.push_str(std::str::from_utf8(&self.input[start..self.pos])?);
+            match self.input.get(self.pos) {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let c = match self.input.get(self.pos) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{08}',
+                        Some(b'f') => '\u{0c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            let c = self.unicode_escape()?;
+                            out.push(c);
+                            continue;
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    self.pos += 1;
+                    out.push(c);
+                }
+                Some(_) => return Err(self.error("control character in string")),
+            }
+        }
+    }
+
+    // unicode_escape は `\u` に続く 16 進数 4 桁（サロゲートペアの場合は 2 つ）を読み込みます
+    fn unicode_escape(&mut self) -> Result<char> {
+        let high = self.hex4()?;
+        if !(0xd800..0xdc00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("invalid unicode escape"));
+        }
+        if !self.input[self.pos..].starts_with(b"\\u") {
+            return Err(self.error("unpaired surrogate"));
+        }
+        self.pos += 2;
+        let low = self.hex4()?;
+        if !(0xdc00..0xe000).contains(&low) {
+            return Err(self.error("unpaired surrogate"));
+        }
+        char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+            .ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32> {
+        let digits = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        let mut v = 0;
+        for &d in digits {
+            v = v * 16
+                + (d as char)
+                    .to_digit(16)
+                    .ok_or_else(|| self.error("invalid unicode escape"))?;
+        }
+        self.pos += 4;
+        Ok(v)
+    }
+}
+
+// number_len は input の先頭にある JSON の数値のバイト数を返します（数値でない場合は 0）
+fn number_len(input: &[u8]) -> usize {
+    let digits = |from: usize| {
+        input[from..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+    let mut i = 0;
+    if input.first() == Some(&b'-') {
+        i += 1;
+    }
+    match input.get(i) {
+        Some(b'0') => i += 1,
+        Some(b'1'..=b'9') => i += digits(i),
+        _ => return 0,
+    }
+    if input.get(i) == Some(&b'.') {
+        let n = digits(i + 1);
+        if n == 0 {
+            return 0;
+        }
+        i += 1 + n;
+    }
+    if let Some(b'e' | b'E') = input.get(i) {
+        i += 1;
+        if let Some(b'+' | b'-') = input.get(i) {
+            i += 1;
+        }
+        let n = digits(i);
+        if n == 0 {
+            return 0;
+        }
+        i += n;
+    }
+    i
+}
+
+// is_json_number は s 全体が JSON の数値の表記かどうかを返します
+fn is_json_number(s: &str) -> bool {
+    !s.is_empty() && number_len(s.as_bytes()) == s.len()
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// encode_base64 は input を padding 付きの標準の base64 に変換します
+pub fn encode_base64(input: &[u8]) -> String {
+    let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// decode_base64 は base64 の文字列を変換します
+// proto3 の JSON mapping に従い、標準と URL-safe のどちらのアルファベットも、padding の省略も受け付けます
+pub fn decode_base64(input: &str) -> Result<Vec<u8>> {
+    let invalid = || invalid_value("invalid base64");
+    let trimmed = input.trim_end_matches('=');
+    if input.len() - trimmed.len() > 2 || trimmed.len() % 4 == 1 {
+        return Err(invalid());
+    }
+    let mut out = Vec::with_capacity(trimmed.len() * 3 / 4);
+    let mut n: u32 = 0;
+    let mut bits = 0;
+    for c in trimmed.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err(invalid()),
+        };
+        n = n << 6 | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+            n &= (1 << bits) - 1;
+        }
+    }
+    Ok(out)
+}
+
+fn invalid_value(reason: impl Into<String>) -> Error {
+    Error::InvalidJsonValue {
+        path: String::new(),
+        reason: reason.into(),
+    }
+}
+
+// in_field は フィールドの値の変換で発生したエラーのパスの先頭に name を追加します
+// embedded message の中で発生したエラーは、外側のフィールドを抜けるたびにパスが伸びます
+pub fn in_field<T>(name: &str, result: Result<T>) -> Result<T> {
+    result.map_err(|e| match e {
+        Error::InvalidJsonValue { path, reason } => Error::InvalidJsonValue {
+            path: if path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}.{}", name, path)
+            },
+            reason,
+        },
+        e => Error::InvalidJsonValue {
+            path: name.to_string(),
+            reason: e.to_string(),
+        },
+    })
+}
+
+// expect_object は message の JSON である object の要素を返します
+pub fn expect_object(value: &JsonValue) -> Result<&[(String, JsonValue)]> {
+    match value {
+        JsonValue::Object(o) => Ok(o),
+        v => Err(invalid_value(format!("expected object, got {}", v.kind()))),
+    }
+}
+
+// unknown_field は message に定義されていない key のエラーを返します
+pub fn unknown_field(key: &str) -> Error {
+    invalid_value(format!("unknown field `{}`", key))
+}
+
+// repeated_to_json は repeated フィールドの要素をそれぞれ変換し、array にします
+pub fn repeated_to_json<'a, T: 'a, I, F>(values: I, f: F) -> Result<JsonValue>
+where
+    I: IntoIterator<Item = &'a T>,
+    F: Fn(&'a T) -> Result<JsonValue>,
+{
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(i, v)| in_field(&i.to_string(), f(v)))
+        .collect::<Result<_>>()
+        .map(JsonValue::Array)
+}
+
+// repeated_from_json は array の要素をそれぞれ変換します
+pub fn repeated_from_json<T, F>(value: &JsonValue, f: F) -> Result<Vec<T>>
+where
+    F: Fn(&JsonValue) -> Result<T>,
+{
+    match value {
+        JsonValue::Array(a) => a
+            .iter()
+            .enumerate()
+            .map(|(i, v)| in_field(&i.to_string(), f(v)))
+            .collect(),
+        v => Err(invalid_value(format!("expected array, got {}", v.kind()))),
+    }
+}
+
+// JsonField は embedded message 以外のフィールドの値と JSON の値を相互に変換します
+// 変換は proto3 の JSON mapping に従います
+pub trait JsonField: Sized {
+    fn to_json_value(&self) -> Result<JsonValue>;
+    fn from_json_value(value: &JsonValue) -> Result<Self>;
+    // is_json_default は JSON に書き込む際に省略する default 値かどうかを返します
+    fn is_json_default(&self) -> bool;
+}
+
+// integer_text は 整数として受け付ける JSON の値（数値または文字列）の表記を返します
+fn integer_text(value: &JsonValue) -> Result<&str> {
+    match value {
+        JsonValue::Number(n) => Ok(n),
+        JsonValue::String(s) if is_json_number(s) => Ok(s),
+        v => Err(invalid_value(format!("expected integer, got {}", v.kind()))),
+    }
+}
+
+// parse_integer は 整数を読み込みます
+// 指数表記などの整数値となる数値（`1e3`, `2.0`）も受け付けます
+fn parse_integer<T: TryFrom<i128>>(value: &JsonValue) -> Result<T> {
+    let text = integer_text(value)?;
+    let n = match text.parse::<i128>() {
+        Ok(n) => n,
+        Err(_) => {
+            let f: f64 = text
+                .parse()
+                .map_err(|_| invalid_value(format!("invalid integer `{}`", text)))?;
+            if f.fract() != 0.0 || f.abs() >= i128::MAX as f64 {
+                return Err(invalid_value(format!("invalid integer `{}`", text)));
+            }
+            f as i128
+        }
+    };
+    T::try_from(n).map_err(|_| invalid_value(format!("integer out of range `{}`", text)))
+}
+
+macro_rules! json_number {
+    ($($t:ty),*) => {$(
+        impl JsonField for $t {
+            fn to_json_value(&self) -> Result<JsonValue> {
+                Ok(JsonValue::Number(self.to_string()))
+            }
+            fn from_json_value(value: &JsonValue) -> Result<Self> {
+                parse_integer(value)
+            }
+            fn is_json_default(&self) -> bool {
+                *self == 0
+            }
+        }
+    )*};
+}
+
+// 64-bit の整数は、JavaScript の数値で精度が落ちないよう文字列にします
+macro_rules! json_number_string {
+    ($($t:ty),*) => {$(
+        impl JsonField for $t {
+            fn to_json_value(&self) -> Result<JsonValue> {
+                Ok(JsonValue::String(self.to_string()))
+            }
+            fn from_json_value(value: &JsonValue) -> Result<Self> {
+                parse_integer(value)
+            }
+            fn is_json_default(&self) -> bool {
+                *self == 0
+            }
+        }
+    )*};
+}
+
+json_number!(i32, u32);
+json_number_string!(i64, u64);
+
+// float_to_json は 有限でない値を "NaN", "Infinity", "-Infinity" の文字列にします
+fn float_to_json(v: f64, text: String) -> JsonValue {
+    if v.is_nan() {
+        JsonValue::String("NaN".to_string())
+    } else if v.is_infinite() && v > 0.0 {
+        JsonValue::String("Infinity".to_string())
+    } else if v.is_infinite() {
+        JsonValue::String("-Infinity".to_string())
+    } else {
+        JsonValue::Number(text)
+    }
+}
+
+fn parse_float(value: &JsonValue) -> Result<f64> {
+    let text = match value {
+        JsonValue::String(s) if s == "NaN" => return Ok(f64::NAN),
+        JsonValue::String(s) if s == "Infinity" => return Ok(f64::INFINITY),
+        JsonValue::String(s) if s == "-Infinity" => return Ok(f64::NEG_INFINITY),
+        JsonValue::Number(n) => n,
+        JsonValue::String(s) if is_json_number(s) => s,
+        v => return Err(invalid_value(format!("expected number, got {}", v.kind()))),
+    };
+    let f: f64 = text
+        .parse()
+        .map_err(|_| invalid_value(format!("invalid number `{}`", text)))?;
+    // 表記が大きすぎて無限大になる値は受け付けない
+    if f.is_infinite() {
+        return Err(invalid_value(format!("number out of range `{}`", text)));
+    }
+    Ok(f)
+}
+
+impl JsonField for f64 {
+    fn to_json_value(&self) -> Result<JsonValue> {
+        Ok(float_to_json(*self, self.to_string()))
+    }
+    fn from_json_value(value: &JsonValue) -> Result<Self> {
+        parse_float(value)
+    }
+    // -0.0 は default 値ではないため、ビットで比較する
+    fn is_json_default(&self) -> bool {
+        self.to_bits() == 0
+    }
+}
+
+impl JsonField for f32 {
+    fn to_json_value(&self) -> Result<JsonValue> {
+        Ok(float_to_json(*self as f64, self.to_string()))
+    }
+    fn from_json_value(value: &JsonValue) -> Result<Self> {
+        let f = parse_float(value)?;
+        if f.is_finite() && f.abs() > f32::MAX as f64 {
+            return Err(invalid_value(format!(
+                "number out of range for float `{}`",
+                f
+            )));
+        }
+        Ok(f as f32)
+    }
+    // -0.0 は default 値ではないため、ビットで比較する
+    fn is_json_default(&self) -> bool {
+        self.to_bits() == 0
+    }
+}
+
+impl JsonField for bool {
+    fn to_json_value(&self) -> Result<JsonValue> {
+        Ok(JsonValue::Bool(*self))
+    }
+    fn from_json_value(value: &JsonValue) -> Result<Self> {
+        match value {
+            JsonValue::Bool(b) => Ok(*b),
+            v => Err(invalid_value(format!("expected bool, got {}", v.kind()))),
+        }
+    }
+    fn is_json_default(&self) -> bool {
+        !*self
+    }
+}
+
+impl JsonField for String {
+    fn to_json_value(&self) -> Result<JsonValue> {
+        Ok(JsonValue::String(self.clone()))
+    }
+    fn from_json_value(value: &JsonValue) -> Result<Self> {
+        match value {
+            JsonValue::String(s) => Ok(s.clone()),
+            v => Err(invalid_value(format!("expected string, got {}", v.kind()))),
+        }
+    }
+    fn is_json_default(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+impl JsonField for Cow<'_, str> {
+    fn to_json_value(&self) -> Result<JsonValue> {
+        Ok(JsonValue::String(self.to_string()))
+    }
+    fn from_json_value(value: &JsonValue) -> Result<Self> {
+        String::from_json_value(value).map(Cow::Owned)
+    }
+    fn is_json_default(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+// bytes は base64 の文字列にします
+impl JsonField for Vec<u8> {
+    fn to_json_value(&self) -> Result<JsonValue> {
+        Ok(JsonValue::String(encode_base64(self)))
+    }
+    fn from_json_value(value: &JsonValue) -> Result<Self> {
+        match value {
+            JsonValue::String(s) => decode_base64(s),
+            v => Err(invalid_value(format!("expected string, got {}", v.kind()))),
+        }
+    }
+    fn is_json_default(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+impl JsonField for Cow<'_, [u8]> {
+    fn to_json_value(&self) -> Result<JsonValue> {
+        Ok(JsonValue::String(encode_base64(self)))
+    }
+    fn from_json_value(value: &JsonValue) -> Result<Self> {
+        Vec::<u8>::from_json_value(value).map(Cow::Owned)
+    }
+    fn is_json_default(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+// enum は値の名前にします。名前のない値（定義されていない値）は数値にします
+// 読み込み時は名前と数値のどちらも受け付けます
+impl<T: VariantEnum> JsonField for T {
+    fn to_json_value(&self) -> Result<JsonValue> {
+        Ok(match self.name() {
+            Some(name) => JsonValue::String(name.to_string()),
+            None => JsonValue::Number(self.into_i32().to_string()),
+        })
+    }
+    fn from_json_value(value: &JsonValue) -> Result<Self> {
+        match value {
+            JsonValue::String(s) => {
+                T::from_name(s).ok_or_else(|| invalid_value(format!("unknown enum value `{}`", s)))
+            }
+            v => parse_integer(v).map(T::from_i32),
+        }
+    }
+    fn is_json_default(&self) -> bool {
+        self.into_i32() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        let input = r#" {"a": [1, -2.5e3, true, null], "b": "x\"\u00e9\ud83d\ude00\n", "c": {}} "#;
+        let v = JsonValue::parse(input).unwrap();
+        assert_eq!(
+            v.get("a"),
+            Some(&JsonValue::Array(vec![
+                JsonValue::Number("1".to_string()),
+                JsonValue::Number("-2.5e3".to_string()),
+                JsonValue::Bool(true),
+                JsonValue::Null,
+            ]))
+        );
+        assert_eq!(v.get("b").and_then(|b| b.as_str()), Some("x\"é😀\n"));
+        assert_eq!(
+            v.to_string(),
+            r#"{"a":[1,-2.5e3,true,null],"b":"x\"é😀\n","c":{}}"#
+        );
+        assert_eq!(JsonValue::parse(&v.to_string()).unwrap(), v);
+
+        for invalid in [
+            "",
+            "{",
+            "[1,]",
+            "01",
+            "1.",
+            "-",
+            "{\"a\" 1}",
+            "\"\\x\"",
+            "\"\\ud83d\"",
+            "nul",
+            "1 2",
+        ] {
+            assert!(
+                matches!(JsonValue::parse(invalid), Err(Error::InvalidJson { .. })),
+                "{}",
+                invalid
+            );
+        }
+        let deep = "[".repeat(MAX_DEPTH + 2);
+        assert!(JsonValue::parse(&deep).is_err());
+    }
+
+    #[test]
+    fn test_base64() {
+        for (raw, encoded) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"\xfb\xff", "+/8="),
+        ] {
+            assert_eq!(encode_base64(raw), encoded);
+            assert_eq!(decode_base64(encoded).unwrap(), raw);
+        }
+        // URL-safe と padding の省略
+        assert_eq!(decode_base64("-_8").unwrap(), b"\xfb\xff");
+        assert!(decode_base64("Zg=x").is_err());
+        assert!(decode_base64("Z").is_err());
+    }
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(
+            i64::MAX.to_json_value().unwrap(),
+            JsonValue::String(i64::MAX.to_string())
+        );
+        assert_eq!(
+            (-7i32).to_json_value().unwrap(),
+            JsonValue::Number("-7".to_string())
+        );
+        assert_eq!(
+            u64::from_json_value(&JsonValue::String("18446744073709551615".to_string())).unwrap(),
+            u64::MAX
+        );
+        assert_eq!(
+            i32::from_json_value(&JsonValue::Number("1e3".to_string())).unwrap(),
+            1000
+        );
+        assert!(u32::from_json_value(&JsonValue::Number("-1".to_string())).is_err());
+        assert!(i32::from_json_value(&JsonValue::Number("1.5".to_string())).is_err());
+        assert!(i32::from_json_value(&JsonValue::String(" 1".to_string())).is_err());
+
+        assert!(0.0f64.is_json_default());
+        assert!(!(-0.0f64).is_json_default());
+        assert!(!(-0.0f32).is_json_default());
+        assert_eq!(
+            f64::NAN.to_json_value().unwrap(),
+            JsonValue::String("NaN".to_string())
+        );
+        assert_eq!(
+            f32::NEG_INFINITY.to_json_value().unwrap(),
+            JsonValue::String("-Infinity".to_string())
+        );
+        assert_eq!(
+            f64::from_json_value(&JsonValue::String("Infinity".to_string())).unwrap(),
+            f64::INFINITY
+        );
+        assert_eq!(
+            f32::from_json_value(&JsonValue::Number("1.5".to_string())).unwrap(),
+            1.5
+        );
+        assert!(f32::from_json_value(&JsonValue::Number("1e300".to_string())).is_err());
+        assert!(f64::from_json_value(&JsonValue::String("inf".to_string())).is_err());
+
+        let e = in_field(
+            "a",
+            in_field("b", u32::from_json_value(&JsonValue::Bool(true))),
+        )
+        .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "invalid json value at `a.b`. expected integer, got bool"
+        );
+    }
+}
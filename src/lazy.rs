@@ -0,0 +1,322 @@
+use std::cell::OnceCell;
+use std::fmt;
+
+use crate::descriptor::MessageDescriptor;
+use crate::encode::{push_bytes_reversed, EncodeOptions};
+use crate::json::JsonValue;
+use crate::text::{TextField, TextWriter};
+use crate::view::HasView;
+use crate::wire::Proto;
+use crate::Result;
+
+// Lazy は embedded message の decode を、値が必要になるまで遅らせるラッパーです
+// parse 時には encode 後のバイト列だけを保持し、最初の get() で decode します
+// 値を変更していない場合、encode 時は保持しているバイト列をそのまま書き出します
+pub struct Lazy<T> {
+    // raw は 値を変更していない場合の encode 後のバイト列です
+    // None の場合、value は必ず設定されています
+    raw: Option<Vec<u8>>,
+    value: OnceCell<T>,
+}
+
+impl<T> Lazy<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            raw: None,
+            value: OnceCell::from(value),
+        }
+    }
+
+    // from_raw は encode 後のバイト列から、decode せずに値を作ります
+    pub fn from_raw(bytes: Vec<u8>) -> Self {
+        Self {
+            raw: Some(bytes),
+            value: OnceCell::new(),
+        }
+    }
+
+    // raw は encode 時にそのまま書き出されるバイト列を返します
+    // 値を変更した場合は None を返します
+    pub fn raw(&self) -> Option<&[u8]> {
+        self.raw.as_deref()
+    }
+
+    // set は値を置き換えます
+    pub fn set(&mut self, value: T) {
+        *self = Self::new(value);
+    }
+}
+
+impl<T: Proto> Lazy<T> {
+    // get は値を返します。最初の呼び出し時に decode します
+    pub fn get(&self) -> Result<&T> {
+        if let Some(v) = self.value.get() {
+            return Ok(v);
+        }
+        let v = T::parse(self.raw.as_deref().unwrap_or_default())?;
+        Ok(self.value.get_or_init(|| v))
+    }
+
+    // get_mut は変更可能な値を返します
+    // 値が変更されたものとして扱い、以降の encode では値から書き出します
+    pub fn get_mut(&mut self) -> Result<&mut T> {
+        self.get()?;
+        self.raw = None;
+        // get で設定済み
+        Ok(self.value.get_mut().unwrap())
+    }
+
+    pub fn into_inner(self) -> Result<T> {
+        match self.value.into_inner() {
+            Some(v) => Ok(v),
+            None => T::parse(self.raw.as_deref().unwrap_or_default()),
+        }
+    }
+
+    // merge_value は decode した値を統合します
+    fn merge_value(&mut self, other: Self) {
+        if let (Ok(v), Ok(other)) = (self.get_mut(), other.into_inner()) {
+            v.merge(other);
+        }
+    }
+}
+
+impl<T> Default for Lazy<T> {
+    // default は空のメッセージのバイト列を保持します
+    fn default() -> Self {
+        Self::from_raw(Vec::new())
+    }
+}
+
+impl<T> From<T> for Lazy<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: Clone> Clone for Lazy<T> {
+    fn clone(&self) -> Self {
+        Self {
+            raw: self.raw.clone(),
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Lazy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lazy")
+            .field("raw", &self.raw)
+            .field("value", &self.value.get())
+            .finish()
+    }
+}
+
+// eq は decode した値を比較します
+// decode できない場合は、保持しているバイト列が一致するかどうかを返します
+impl<T: Proto + PartialEq> PartialEq for Lazy<T> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.get(), other.get()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => self.raw.is_some() && self.raw == other.raw,
+        }
+    }
+}
+
+impl<T: Proto> Proto for Lazy<T> {
+    // parse は bytes を保持するだけで、decode しません
+    // bytes が不正な場合は get でエラーになります
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        Ok(Self::from_raw(bytes.to_vec()))
+    }
+
+    fn encode_reversed(&self, buf: &mut Vec<u8>) -> Result<()> {
+        match (&self.raw, self.value.get()) {
+            (Some(raw), _) => {
+                push_bytes_reversed(buf, raw);
+                Ok(())
+            }
+            (None, Some(v)) => v.encode_reversed(buf),
+            (None, None) => Ok(()),
+        }
+    }
+
+    // options が既定値以外の場合は、保持しているバイト列をそのまま使わずに decode して encode し直します
+    // （読み込んだかどうかで、同じ options の結果が変わらないようにするため）
+    fn encode_reversed_with_options(
+        &self,
+        buf: &mut Vec<u8>,
+        options: &EncodeOptions,
+    ) -> Result<()> {
+        if *options == EncodeOptions::default() {
+            return self.encode_reversed(buf);
+        }
+        self.get()?.encode_reversed_with_options(buf, options)
+    }
+
+    fn encoded_len(&self) -> usize {
+        match (&self.raw, self.value.get()) {
+            (Some(raw), _) => raw.len(),
+            (None, Some(v)) => v.encoded_len(),
+            (None, None) => 0,
+        }
+    }
+
+    fn descriptor() -> Option<MessageDescriptor> {
+        T::descriptor()
+    }
+
+    fn to_json_value(&self) -> Result<JsonValue> {
+        self.get()?.to_json_value()
+    }
+
+    fn from_json_value(value: &JsonValue) -> Result<Self> {
+        T::from_json_value(value).map(Self::new)
+    }
+
+    fn write_text(&self, w: &mut TextWriter) -> Result<()> {
+        self.get()?.write_text(w)
+    }
+
+    fn from_text_fields(fields: &[TextField]) -> Result<Self> {
+        T::from_text_fields(fields).map(Self::new)
+    }
+
+    // merge は encode 後のバイト列を連結して統合します
+    // protobuf ではバイト列の連結は embedded message の統合と同じ意味になるため、decode は不要です
+    // 値を変更している場合は encode してから連結し、encode できない場合は decode して統合します
+    // どちらもできない場合は self を変更しません
+    fn merge(&mut self, other: Self) {
+        let raw = match self.raw.take() {
+            Some(raw) => Ok(raw),
+            None => self.value.get().map_or(Ok(Vec::new()), |v| v.bytes()),
+        };
+        let other_raw = match other.raw {
+            Some(raw) => Ok(raw),
+            None => other.value.get().map_or(Ok(Vec::new()), |v| v.bytes()),
+        };
+        match (raw, other_raw) {
+            (Ok(mut raw), Ok(other_raw)) => {
+                raw.extend(other_raw);
+                *self = Self::from_raw(raw);
+            }
+            (raw, other_raw) => {
+                if let Ok(raw) = raw {
+                    self.raw = Some(raw);
+                }
+                let other = match other_raw {
+                    Ok(raw) => Self::from_raw(raw),
+                    Err(_) => Self {
+                        raw: None,
+                        value: other.value,
+                    },
+                };
+                self.merge_value(other);
+            }
+        }
+    }
+}
+
+// Lazy なフィールドの View は、内部の型の View です
+impl<T: HasView> HasView for Lazy<T> {
+    type View<'a> = T::View<'a>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    // Num は field number 1 の uint64 だけを持つメッセージです
+    #[derive(Debug, Default, Clone, PartialEq)]
+    struct Num(u64);
+
+    impl Proto for Num {
+        fn parse(bytes: &[u8]) -> Result<Self> {
+            match bytes {
+                [] => Ok(Num(0)),
+                [0b00001000, v] if *v < 0x80 => Ok(Num(*v as u64)),
+                _ => Err(Error::UnexpectedFormat),
+            }
+        }
+        fn encode_reversed(&self, buf: &mut Vec<u8>) -> Result<()> {
+            match self.0 {
+                0 => Ok(()),
+                v if v < 0x80 => {
+                    buf.extend([v as u8, 0b00001000]);
+                    Ok(())
+                }
+                _ => Err(Error::UnexpectedFormat),
+            }
+        }
+        fn encoded_len(&self) -> usize {
+            if self.0 == 0 {
+                0
+            } else {
+                2
+            }
+        }
+        fn merge(&mut self, other: Self) {
+            if other.0 != 0 {
+                self.0 = other.0;
+            }
+        }
+    }
+
+    #[test]
+    fn test_lazy() {
+        let mut x = Lazy::<Num>::parse(&[0b00001000, 1]).unwrap();
+        assert_eq!(x.raw(), Some(&[0b00001000, 1][..]));
+        assert_eq!(x.get().unwrap(), &Num(1));
+        // get では変更したものとして扱わない
+        assert_eq!(x.raw(), Some(&[0b00001000, 1][..]));
+        assert_eq!(x.bytes().unwrap(), vec![0b00001000, 1]);
+
+        x.get_mut().unwrap().0 = 2;
+        assert_eq!(x.raw(), None);
+        assert_eq!(x.encoded_len(), 2);
+        assert_eq!(x.bytes().unwrap(), vec![0b00001000, 2]);
+
+        // deterministic の場合は encode し直す
+        let x = Lazy::<Num>::parse(&[0b00001000, 1]).unwrap();
+        let options = EncodeOptions {
+            deterministic: true,
+            ..Default::default()
+        };
+        assert_eq!(x.bytes_with_options(&options).unwrap(), vec![0b00001000, 1]);
+        assert!(Lazy::<Num>::parse(&[0xff])
+            .unwrap()
+            .bytes_with_options(&options)
+            .is_err());
+
+        // 不正なバイト列は get でエラーになる
+        let x = Lazy::<Num>::parse(&[0xff]).unwrap();
+        assert!(x.get().is_err());
+        assert_eq!(x.bytes().unwrap(), vec![0xff]);
+        assert!(x.into_inner().is_err());
+
+        assert_eq!(Lazy::<Num>::default().get().unwrap(), &Num(0));
+        assert_eq!(Lazy::new(Num(3)), Lazy::from_raw(vec![0b00001000, 3]));
+    }
+
+    #[test]
+    fn test_lazy_merge() {
+        // バイト列のまま連結する
+        let mut x = Lazy::<Num>::from_raw(vec![0b00001000, 1]);
+        x.merge(Lazy::from_raw(vec![0b00001000, 2]));
+        assert_eq!(x.raw(), Some(&[0b00001000, 1, 0b00001000, 2][..]));
+
+        // 変更した値は encode してから連結する
+        let mut x = Lazy::new(Num(1));
+        x.merge(Lazy::from_raw(vec![]));
+        assert_eq!(x.raw(), Some(&[0b00001000, 1][..]));
+
+        // encode できない値は decode して統合する
+        let mut x = Lazy::new(Num(0x80));
+        x.merge(Lazy::new(Num(2)));
+        assert_eq!(x.into_inner().unwrap(), Num(2));
+        let mut x = Lazy::new(Num(1));
+        x.merge(Lazy::new(Num(0x80)));
+        assert_eq!(x.into_inner().unwrap(), Num(0x80));
+    }
+}
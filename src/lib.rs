@@ -1,9 +1,22 @@
+pub mod adapter;
 pub mod decode;
+pub mod delimited;
+pub mod descriptor;
+pub mod diagnose;
 pub mod encode;
+pub mod fingerprint;
+pub mod json;
+pub mod lazy;
+pub mod message;
 pub mod parser;
+pub mod query;
+pub mod repeated;
+pub mod schema;
+pub mod text;
+pub mod view;
 pub mod wire;
 mod zigzag;
 
 pub use protowirers_impl::*;
 pub mod error;
-pub use error::{Error, Result};
+pub use error::{DecodeError, Error, Result};
@@ -0,0 +1,379 @@
+use std::io::Cursor;
+
+use crate::decode::decode_wire_binary;
+use crate::encode::{encode_wire_binary_with_options, EncodeOptions};
+use crate::fingerprint::fnv1a_64;
+use crate::wire::{FieldNumber, WireData, WireDataLengthDelimited, WireStruct};
+use crate::{Error, Result};
+
+// WireMessage は スキーマなしで読み書きできる、decode 済みのメッセージです
+// フィールドを入力の順に保持し、embedded message は message_mut などで必要になった時に decode します
+// スキーマを持たないメッセージの一部のフィールドだけを書き換える用途を想定しています
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WireMessage {
+    fields: Vec<(FieldNumber, WireValue)>,
+}
+
+// WireValue は WireMessage のフィールドの値です
+// embedded message として decode した値は Message になります
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireValue {
+    Data(WireData),
+    Message(WireMessage),
+}
+
+impl WireValue {
+    // as_data は decode していない値を返します
+    pub fn as_data(&self) -> Option<&WireData> {
+        match self {
+            WireValue::Data(d) => Some(d),
+            WireValue::Message(_) => None,
+        }
+    }
+
+    pub fn as_message(&self) -> Option<&WireMessage> {
+        match self {
+            WireValue::Data(_) => None,
+            WireValue::Message(m) => Some(m),
+        }
+    }
+
+    // to_wire_data は embedded message を encode し、WireData として返します
+    pub fn to_wire_data(&self) -> Result<WireData> {
+        self.to_wire_data_with_options(&EncodeOptions::default())
+    }
+
+    fn to_wire_data_with_options(&self, options: &EncodeOptions) -> Result<WireData> {
+        match self {
+            WireValue::Data(d) => Ok(d.clone()),
+            WireValue::Message(m) => Ok(WireData::LengthDelimited(WireDataLengthDelimited::new(
+                m.bytes_with_options(options)?,
+            ))),
+        }
+    }
+
+    fn into_wire_data(self) -> Result<WireData> {
+        match self {
+            WireValue::Data(d) => Ok(d),
+            v => v.to_wire_data(),
+        }
+    }
+}
+
+impl From<WireData> for WireValue {
+    fn from(v: WireData) -> Self {
+        WireValue::Data(v)
+    }
+}
+
+impl From<WireMessage> for WireValue {
+    fn from(v: WireMessage) -> Self {
+        WireValue::Message(v)
+    }
+}
+
+impl WireMessage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // parse は bytes をフィールドの並びに decode します
+    // embedded message かどうかは区別できないため、length-delimited の値はそのまま保持します
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut c = Cursor::new(bytes);
+        let fields = decode_wire_binary(&mut c)?
+            .into_iter()
+            .map(|sw| (sw.field_number(), WireValue::Data(sw.into_data())))
+            .collect();
+        Ok(Self { fields })
+    }
+
+    // bytes は すべてのフィールドを並びの順に encode した結果を返します
+    // 値がゼロ（空）のフィールドも decode した時のまま書き出します
+    pub fn bytes(&self) -> Result<Vec<u8>> {
+        self.bytes_with_options(&EncodeOptions::default())
+    }
+
+    // bytes_with_options は options に従って encode した結果を返します
+    // deterministic または FieldOrder::FieldNumber の場合、フィールドを field number の昇順に並べ替えます
+    // （同じ field number の値の順序は保ちます）
+    // drop_defaults の場合、値がゼロ（空）のフィールドを書き出しません（emit_defaults の場合は書き出します）
+    // decode していない length-delimited の値は embedded message かどうか区別できないため、そのまま書き出します
+    pub fn bytes_with_options(&self, options: &EncodeOptions) -> Result<Vec<u8>> {
+        let mut inputs = self
+            .fields
+            .iter()
+            .map(|(n, v)| Ok(WireStruct::new(*n, v.to_wire_data_with_options(options)?)))
+            .collect::<Result<Vec<_>>>()?;
+        if options.sorts_by_field_number() {
+            inputs.sort_by_key(|sw| sw.field_number());
+        }
+        let options = EncodeOptions {
+            emit_defaults: options.emit_defaults || !options.drop_defaults,
+            ..*options
+        };
+        let mut c = Cursor::new(Vec::new());
+        encode_wire_binary_with_options(&mut c, inputs, &options)?;
+        Ok(c.into_inner())
+    }
+
+    // fingerprint は deterministic な encode の結果のハッシュ値（FNV-1a 64-bit）を返します
+    pub fn fingerprint(&self) -> Result<u64> {
+        let bytes = self.bytes_with_options(&EncodeOptions {
+            deterministic: true,
+            ..Default::default()
+        })?;
+        Ok(fnv1a_64(&bytes))
+    }
+
+    // into_wire_structs は embedded message を encode し、WireStruct の並びとして返します
+    pub fn into_wire_structs(self) -> Result<Vec<WireStruct>> {
+        self.fields
+            .into_iter()
+            .map(|(n, v)| Ok(WireStruct::new(n, v.into_wire_data()?)))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    // iter は フィールドを並びの順に返します
+    pub fn iter(&self) -> impl Iterator<Item = (FieldNumber, &WireValue)> {
+        self.fields.iter().map(|(n, v)| (*n, v))
+    }
+
+    pub fn contains(&self, field_number: FieldNumber) -> bool {
+        self.fields.iter().any(|(n, _)| *n == field_number)
+    }
+
+    // get は field number の値を返します
+    // 同じフィールドが複数回現れた場合は、protobuf と同様に最後の値を返します
+    pub fn get(&self, field_number: FieldNumber) -> Option<&WireValue> {
+        self.get_all(field_number).last()
+    }
+
+    // get_all は field number の値を並びの順にすべて返します
+    pub fn get_all(
+        &self,
+        field_number: FieldNumber,
+    ) -> impl DoubleEndedIterator<Item = &WireValue> {
+        self.fields
+            .iter()
+            .filter(move |(n, _)| *n == field_number)
+            .map(|(_, v)| v)
+    }
+
+    // get_mut は field number の最後の値を変更可能な参照で返します
+    pub fn get_mut(&mut self, field_number: FieldNumber) -> Option<&mut WireValue> {
+        self.fields
+            .iter_mut()
+            .rev()
+            .find(|(n, _)| *n == field_number)
+            .map(|(_, v)| v)
+    }
+
+    // set は field number の値を value だけにします
+    // 既に値がある場合は最初の値の位置に置き換え、ない場合は末尾に追加します
+    pub fn set(&mut self, field_number: FieldNumber, value: impl Into<WireValue>) {
+        let value = value.into();
+        match self.fields.iter().position(|(n, _)| *n == field_number) {
+            Some(i) => {
+                self.fields[i].1 = value;
+                // 最初の値以外を取り除く
+                let mut first = true;
+                self.fields
+                    .retain(|(n, _)| *n != field_number || std::mem::take(&mut first));
+            }
+            None => self.fields.push((field_number, value)),
+        }
+    }
+
+    // push は 値を末尾に追加します（repeated fields の要素の追加）
+    pub fn push(&mut self, field_number: FieldNumber, value: impl Into<WireValue>) {
+        self.fields.push((field_number, value.into()));
+    }
+
+    // remove は field number の値をすべて取り除き、取り除いた値を返します
+    pub fn remove(&mut self, field_number: FieldNumber) -> Vec<WireValue> {
+        let (removed, fields) = std::mem::take(&mut self.fields)
+            .into_iter()
+            .partition(|(n, _)| *n == field_number);
+        self.fields = fields;
+        removed.into_iter().map(|(_, v)| v).collect()
+    }
+
+    // message は field number の最後の値を embedded message として decode して返します
+    // 値がない場合は None を返します
+    pub fn message(&self, field_number: FieldNumber) -> Result<Option<WireMessage>> {
+        self.get(field_number).map(to_message).transpose()
+    }
+
+    // message_mut は field number の最後の値を embedded message として decode し、変更可能な参照で返します
+    // decode した結果は保持され、encode 時に再び encode されます
+    // 値がない場合は空のメッセージを末尾に追加します
+    pub fn message_mut(&mut self, field_number: FieldNumber) -> Result<&mut WireMessage> {
+        if !self.contains(field_number) {
+            self.push(field_number, WireMessage::new());
+        }
+        // contains で確認済み
+        let v = self.get_mut(field_number).unwrap();
+        if let WireValue::Data(_) = v {
+            *v = WireValue::Message(to_message(v)?);
+        }
+        match v {
+            WireValue::Message(m) => Ok(m),
+            WireValue::Data(_) => unreachable!(),
+        }
+    }
+}
+
+// to_message は値を embedded message として decode します
+fn to_message(v: &WireValue) -> Result<WireMessage> {
+    match v {
+        WireValue::Message(m) => Ok(m.clone()),
+        WireValue::Data(WireData::LengthDelimited(l)) => WireMessage::parse(&l.value),
+        WireValue::Data(d) => Err(Error::UnexpectedType {
+            want: "LengthDelimited".to_string(),
+            got: format!("{}", d),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wire::{WireDataBit32, WireDataVarint};
+
+    fn varint(v: u128) -> WireData {
+        WireData::Varint(WireDataVarint::new(v))
+    }
+
+    // 1: 150, 2: { 1: 1, 2: "a" }, 3: 1, 3: 2
+    const BYTES: &[u8] = &[
+        0b00001000, 0b10010110, 0b00000001, // 1
+        0b00010010, 0b00000101, 0b00001000, 0b00000001, 0b00010010, 0b00000001, b'a', // 2
+        0b00011000, 0b00000001, // 3
+        0b00011000, 0b00000010, // 3
+    ];
+
+    #[test]
+    fn test_parse_and_get() {
+        let m = WireMessage::parse(BYTES).unwrap();
+        assert_eq!(m.len(), 4);
+        assert_eq!(m.get(1).and_then(WireValue::as_data), Some(&varint(150)));
+        assert_eq!(m.get(3).and_then(WireValue::as_data), Some(&varint(2)));
+        assert_eq!(
+            m.get_all(3)
+                .filter_map(WireValue::as_data)
+                .collect::<Vec<_>>(),
+            vec![&varint(1), &varint(2)]
+        );
+        assert!(m.get(4).is_none());
+        assert_eq!(m.bytes().unwrap(), BYTES);
+
+        let inner = m.message(2).unwrap().unwrap();
+        assert_eq!(inner.get(1).and_then(WireValue::as_data), Some(&varint(1)));
+        assert!(m.message(4).unwrap().is_none());
+        assert!(m.message(1).is_err());
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut m = WireMessage::parse(BYTES).unwrap();
+        // 最初の値の位置に置き換える
+        m.set(3, varint(5));
+        assert_eq!(m.get_all(3).count(), 1);
+        m.push(3, varint(6));
+        m.set(4, WireData::Bit32(WireDataBit32::new([1, 0, 0, 0])));
+        assert_eq!(m.remove(1), vec![WireValue::Data(varint(150))]);
+        assert!(m.remove(1).is_empty());
+
+        let inner = m.message_mut(2).unwrap();
+        inner.set(1, varint(300));
+        inner.remove(2);
+        assert!(m.get(2).unwrap().as_message().is_some());
+
+        m.message_mut(5).unwrap().push(1, varint(1));
+        assert_eq!(
+            m.bytes().unwrap(),
+            vec![
+                0b00010010, 0b00000011, 0b00001000, 0b10101100, 0b00000010, // 2
+                0b00011000, 0b00000101, // 3
+                0b00011000, 0b00000110, // 3
+                0b00100101, 1, 0, 0, 0, // 4
+                0b00101010, 0b00000010, 0b00001000, 0b00000001, // 5
+            ]
+        );
+
+        // embedded message でない値
+        assert!(m.message_mut(3).is_err());
+
+        // deterministic の場合は field number の昇順に並べ替える
+        let mut x = m.clone();
+        x.remove(2);
+        x.push(1, varint(1));
+        let options = EncodeOptions {
+            deterministic: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            x.bytes_with_options(&options).unwrap(),
+            vec![
+                0b00001000, 0b00000001, // 1
+                0b00011000, 0b00000101, // 3
+                0b00011000, 0b00000110, // 3
+                0b00100101, 1, 0, 0, 0, // 4
+                0b00101010, 0b00000010, 0b00001000, 0b00000001, // 5
+            ]
+        );
+        let mut y = WireMessage::new();
+        y.push(1, varint(1));
+        for (n, v) in x.iter().filter(|(n, _)| *n != 1) {
+            y.push(n, v.clone());
+        }
+        assert_ne!(x.bytes().unwrap(), y.bytes().unwrap());
+        assert_eq!(x.fingerprint().unwrap(), y.fingerprint().unwrap());
+
+        let structs = m.into_wire_structs().unwrap();
+        assert_eq!(structs.len(), 5);
+        assert_eq!(structs[0].field_number(), 2);
+    }
+
+    #[test]
+    fn test_round_trip_defaults() {
+        // 1: 0, 2: "", 2: "a", 3: fixed32 0, 4: 0, 4: 1, 5: fixed64 0, 6: { 1: 0 }
+        let bytes: &[u8] = &[
+            0x08, 0x00, // 1
+            0x12, 0x00, // 2
+            0x12, 0x01, b'a', // 2
+            0x1d, 0, 0, 0, 0, // 3
+            0x20, 0x00, // 4
+            0x20, 0x01, // 4
+            0x29, 0, 0, 0, 0, 0, 0, 0, 0, // 5
+            0x32, 0x02, 0x08, 0x00, // 6
+        ];
+        let mut m = WireMessage::parse(bytes).unwrap();
+        assert_eq!(m.bytes().unwrap(), bytes);
+        // 他のフィールドを書き換えても、値がゼロのフィールドは残る
+        m.message_mut(6).unwrap().push(2, varint(0));
+        m.set(7, varint(1));
+        let mut want = bytes[..bytes.len() - 4].to_vec();
+        want.extend_from_slice(&[0x32, 0x04, 0x08, 0x00, 0x10, 0x00, 0x38, 0x01]);
+        assert_eq!(m.bytes().unwrap(), want);
+
+        // drop_defaults を指定した場合のみ省略する
+        let options = EncodeOptions {
+            drop_defaults: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            m.bytes_with_options(&options).unwrap(),
+            vec![0x12, 0x01, b'a', 0x20, 0x01, 0x38, 0x01]
+        );
+    }
+}
@@ -1,6 +1,10 @@
 use crate::{decode::decode_variants_slice, wire::*};
-use crate::{encode::encode_repeat, zigzag};
+use crate::{
+    encode::{push_bytes_reversed, push_variant_reversed, variant_len, EncodeOptions},
+    zigzag,
+};
 use crate::{Error, Result};
+use std::borrow::Cow;
 use std::convert::TryFrom;
 
 pub trait VariantEnum: Sized + From<i32> + Into<i32> + Copy {
@@ -10,6 +14,14 @@ pub trait VariantEnum: Sized + From<i32> + Into<i32> + Copy {
     fn into_i32(self) -> i32 {
         self.into()
     }
+    // name は値の名前を返します。derive した enum は定義された値の名前を返します
+    fn name(self) -> Option<&'static str> {
+        None
+    }
+    // from_name は名前に対応する値を返します
+    fn from_name(_name: &str) -> Option<Self> {
+        None
+    }
 }
 
 pub trait VariantToValue: Sized {
@@ -262,39 +274,110 @@ impl Bit64ToValue for f64 {
 }
 
 pub trait LengthDelimitedToValue: Sized {
-    // TODO ここスライスのほうがいいきはする
-    fn from_length_delimited(input: Vec<u8>, ty: TypeLengthDelimited) -> Result<Self>;
-    fn into_length_delimited(self, ty: TypeLengthDelimited) -> Result<Vec<u8>>;
+    fn from_length_delimited(input: &[u8], ty: TypeLengthDelimited) -> Result<Self>;
+    // to_length_delimited は encode 後の値（長さ部分を除く）を返します
+    // 値を借用して書き込むため、値の複製は発生しません
+    fn to_length_delimited(&self, ty: TypeLengthDelimited) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(self.length_delimited_len(ty));
+        self.write_length_delimited_reversed(ty, &mut buf)?;
+        buf.reverse();
+        Ok(buf)
+    }
+    // length_delimited_len は encode 後の値のバイト数（長さ部分を除く）を返します
+    // encode できない値の場合は 0 を返します（encode 時にエラーとなります）
+    fn length_delimited_len(&self, ty: TypeLengthDelimited) -> usize;
+    // write_length_delimited_reversed は encode 後の値（長さ部分を除く）を buf に逆順で書き込みます
+    // 一時的なバッファを作らないため、入れ子のメッセージでもコピーが発生しません
+    fn write_length_delimited_reversed(
+        &self,
+        ty: TypeLengthDelimited,
+        buf: &mut Vec<u8>,
+    ) -> Result<()>;
+    // write_length_delimited_reversed_with_options は options に従って buf に逆順で書き込みます
+    // デフォルトでは options を使わずに write_length_delimited_reversed で書き込みます
+    fn write_length_delimited_reversed_with_options(
+        &self,
+        ty: TypeLengthDelimited,
+        buf: &mut Vec<u8>,
+        _options: &EncodeOptions,
+    ) -> Result<()> {
+        self.write_length_delimited_reversed(ty, buf)
+    }
+    // merge_length_delimited は 同じフィールドが再度現れた場合に、その値を self に統合します
+    // デフォルトでは後に現れた値で置き換えます
+    fn merge_length_delimited(&mut self, input: &[u8], ty: TypeLengthDelimited) -> Result<()> {
+        *self = Self::from_length_delimited(input, ty)?;
+        Ok(())
+    }
+}
+
+// is_default は 値が default 値（encode 時に書き出されない値）かどうかを返します
+pub fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
 }
 
 impl LengthDelimitedToValue for String {
-    fn from_length_delimited(input: Vec<u8>, ty: TypeLengthDelimited) -> Result<Self> {
+    fn from_length_delimited(input: &[u8], ty: TypeLengthDelimited) -> Result<Self> {
         if !matches!(ty, TypeLengthDelimited::WireString) {
             return Err(Error::UnexpectedType {
                 want: format!("{:?}", TypeLengthDelimited::WireString),
                 got: format!("{:?}", ty),
             });
         }
-        let s = String::from_utf8(input)?;
+        let s = String::from_utf8(input.to_vec())?;
         Ok(s)
     }
 
-    fn into_length_delimited(self, ty: TypeLengthDelimited) -> Result<Vec<u8>> {
+    fn length_delimited_len(&self, _ty: TypeLengthDelimited) -> usize {
+        self.len()
+    }
+
+    fn write_length_delimited_reversed(
+        &self,
+        ty: TypeLengthDelimited,
+        buf: &mut Vec<u8>,
+    ) -> Result<()> {
         if !matches!(ty, TypeLengthDelimited::WireString) {
             return Err(Error::UnexpectedType {
                 want: format!("{:?}", TypeLengthDelimited::WireString),
                 got: format!("{:?}", ty),
             });
         }
-        Ok(self.into())
+        push_bytes_reversed(buf, self.as_bytes());
+        Ok(())
+    }
+}
+
+impl LengthDelimitedToValue for Cow<'_, str> {
+    fn from_length_delimited(input: &[u8], ty: TypeLengthDelimited) -> Result<Self> {
+        String::from_length_delimited(input, ty).map(Cow::Owned)
+    }
+
+    fn length_delimited_len(&self, _ty: TypeLengthDelimited) -> usize {
+        self.len()
+    }
+
+    fn write_length_delimited_reversed(
+        &self,
+        ty: TypeLengthDelimited,
+        buf: &mut Vec<u8>,
+    ) -> Result<()> {
+        if !matches!(ty, TypeLengthDelimited::WireString) {
+            return Err(Error::UnexpectedType {
+                want: format!("{:?}", TypeLengthDelimited::WireString),
+                got: format!("{:?}", ty),
+            });
+        }
+        push_bytes_reversed(buf, self.as_bytes());
+        Ok(())
     }
 }
 
 impl<T: VariantToValue> LengthDelimitedToValue for Vec<T> {
-    fn from_length_delimited(input: Vec<u8>, ty: TypeLengthDelimited) -> Result<Self> {
+    fn from_length_delimited(input: &[u8], ty: TypeLengthDelimited) -> Result<Self> {
         match ty {
             TypeLengthDelimited::PackedRepeatedFields(AllowedPakcedType::Variant(v)) => {
-                let x = decode_variants_slice(&input)?;
+                let x = decode_variants_slice(input)?;
                 let x = x
                     .iter()
                     .try_fold(Vec::with_capacity(x.len()), |mut acc, xx| {
@@ -313,18 +396,27 @@ impl<T: VariantToValue> LengthDelimitedToValue for Vec<T> {
         }
     }
 
-    fn into_length_delimited(self, ty: TypeLengthDelimited) -> Result<Vec<u8>> {
+    fn length_delimited_len(&self, ty: TypeLengthDelimited) -> usize {
+        match ty {
+            TypeLengthDelimited::PackedRepeatedFields(AllowedPakcedType::Variant(tv)) => self
+                .iter()
+                .map(|x| x.to_variant(tv).map(variant_len).unwrap_or_default())
+                .sum(),
+            _ => 0,
+        }
+    }
+
+    fn write_length_delimited_reversed(
+        &self,
+        ty: TypeLengthDelimited,
+        buf: &mut Vec<u8>,
+    ) -> Result<()> {
         match ty {
             TypeLengthDelimited::PackedRepeatedFields(AllowedPakcedType::Variant(tv)) => {
-                let input = self.iter().try_fold(Vec::new(), |mut acc, x| {
-                    x.to_variant(tv).map(|x| {
-                        acc.push(x);
-                        acc
-                    })
-                })?;
-                let mut v = Vec::new();
-                encode_repeat(&mut v, input)?;
-                Ok(v)
+                for x in self.iter().rev() {
+                    push_variant_reversed(buf, x.to_variant(tv)?);
+                }
+                Ok(())
             }
             _ => Err(Error::UnexpectedType {
                 want: "TypeLengthDelimited::PackedRepeatedFields(AllowedPakcedType::Variant())"
@@ -333,12 +425,19 @@ impl<T: VariantToValue> LengthDelimitedToValue for Vec<T> {
             }),
         }
     }
+
+    // repeated は 後に現れた値を末尾に追加します
+    fn merge_length_delimited(&mut self, input: &[u8], ty: TypeLengthDelimited) -> Result<()> {
+        let x = Self::from_length_delimited(input, ty)?;
+        self.extend(x);
+        Ok(())
+    }
 }
 
 impl LengthDelimitedToValue for Vec<u8> {
-    fn from_length_delimited(input: Vec<u8>, ty: TypeLengthDelimited) -> Result<Self> {
+    fn from_length_delimited(input: &[u8], ty: TypeLengthDelimited) -> Result<Self> {
         match ty {
-            TypeLengthDelimited::Bytes => Ok(input),
+            TypeLengthDelimited::Bytes => Ok(input.to_vec()),
             _ => Err(Error::UnexpectedType {
                 want: "TypeLengthDelimited::Bytes".to_string(),
                 got: format!("{:?}", ty),
@@ -346,9 +445,47 @@ impl LengthDelimitedToValue for Vec<u8> {
         }
     }
 
-    fn into_length_delimited(self, ty: TypeLengthDelimited) -> Result<Vec<u8>> {
+    fn length_delimited_len(&self, _ty: TypeLengthDelimited) -> usize {
+        self.len()
+    }
+
+    fn write_length_delimited_reversed(
+        &self,
+        ty: TypeLengthDelimited,
+        buf: &mut Vec<u8>,
+    ) -> Result<()> {
         match ty {
-            TypeLengthDelimited::Bytes => Ok(self),
+            TypeLengthDelimited::Bytes => {
+                push_bytes_reversed(buf, self);
+                Ok(())
+            }
+            _ => Err(Error::UnexpectedType {
+                want: "TypeLengthDelimited::Bytes".to_string(),
+                got: format!("{:?}", ty),
+            }),
+        }
+    }
+}
+
+impl LengthDelimitedToValue for Cow<'_, [u8]> {
+    fn from_length_delimited(input: &[u8], ty: TypeLengthDelimited) -> Result<Self> {
+        Vec::<u8>::from_length_delimited(input, ty).map(Cow::Owned)
+    }
+
+    fn length_delimited_len(&self, _ty: TypeLengthDelimited) -> usize {
+        self.len()
+    }
+
+    fn write_length_delimited_reversed(
+        &self,
+        ty: TypeLengthDelimited,
+        buf: &mut Vec<u8>,
+    ) -> Result<()> {
+        match ty {
+            TypeLengthDelimited::Bytes => {
+                push_bytes_reversed(buf, self);
+                Ok(())
+            }
             _ => Err(Error::UnexpectedType {
                 want: "TypeLengthDelimited::Bytes".to_string(),
                 got: format!("{:?}", ty),
@@ -358,25 +495,59 @@ impl LengthDelimitedToValue for Vec<u8> {
 }
 
 impl<T: Proto> LengthDelimitedToValue for T {
-    fn from_length_delimited(input: Vec<u8>, ty: TypeLengthDelimited) -> Result<Self> {
+    fn from_length_delimited(input: &[u8], ty: TypeLengthDelimited) -> Result<Self> {
         if !matches!(ty, TypeLengthDelimited::EmbeddedMessages) {
             return Err(Error::UnexpectedType {
                 want: format!("{:?}", TypeLengthDelimited::EmbeddedMessages),
                 got: format!("{:?}", ty),
             });
         }
-        let r = T::parse(input.as_slice())?;
+        let r = T::parse(input)?;
         Ok(r)
     }
 
-    fn into_length_delimited(self, ty: TypeLengthDelimited) -> Result<Vec<u8>> {
+    fn length_delimited_len(&self, _ty: TypeLengthDelimited) -> usize {
+        self.encoded_len()
+    }
+
+    fn write_length_delimited_reversed(
+        &self,
+        ty: TypeLengthDelimited,
+        buf: &mut Vec<u8>,
+    ) -> Result<()> {
+        if !matches!(ty, TypeLengthDelimited::EmbeddedMessages) {
+            return Err(Error::UnexpectedType {
+                want: format!("{:?}", TypeLengthDelimited::EmbeddedMessages),
+                got: format!("{:?}", ty),
+            });
+        }
+        self.encode_reversed(buf)
+    }
+
+    fn write_length_delimited_reversed_with_options(
+        &self,
+        ty: TypeLengthDelimited,
+        buf: &mut Vec<u8>,
+        options: &EncodeOptions,
+    ) -> Result<()> {
+        if !matches!(ty, TypeLengthDelimited::EmbeddedMessages) {
+            return Err(Error::UnexpectedType {
+                want: format!("{:?}", TypeLengthDelimited::EmbeddedMessages),
+                got: format!("{:?}", ty),
+            });
+        }
+        self.encode_reversed_with_options(buf, options)
+    }
+
+    // embedded message は 後に現れた値を再帰的に統合します
+    fn merge_length_delimited(&mut self, input: &[u8], ty: TypeLengthDelimited) -> Result<()> {
         if !matches!(ty, TypeLengthDelimited::EmbeddedMessages) {
             return Err(Error::UnexpectedType {
                 want: format!("{:?}", TypeLengthDelimited::EmbeddedMessages),
                 got: format!("{:?}", ty),
             });
         }
-        self.bytes()
+        self.merge_from(input)
     }
 }
 
@@ -450,13 +621,69 @@ impl Bit32ToValue for f32 {
         }
     }
 }
+
+// def は def_type ごとの marker 型です
+// embedded message は Proto で検査するため marker 型はありません
+pub mod def {
+    pub struct Int32;
+    pub struct Int64;
+    pub struct Uint32;
+    pub struct Uint64;
+    pub struct Sint32;
+    pub struct Sint64;
+    pub struct Bool;
+    pub struct Enum;
+    pub struct Fixed64;
+    pub struct Sfixed64;
+    pub struct Double;
+    pub struct String;
+    pub struct Bytes;
+    pub struct Fixed32;
+    pub struct Sfixed32;
+    pub struct Float;
+}
+
+// HasDefType は 型が def_type D のフィールドに使えることを表します
+// #[derive(Proto)] は組み込み以外の型（#[proto(transparent)] な型など）が def_type と一致するかを compile 時に検査します
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be used for def_type `{D}`",
+    label = "defined def_type does not match this Rust type"
+)]
+pub trait HasDefType<D> {}
+
+macro_rules! impl_has_def_type {
+    ($($ty:ty => $($def:ident),+;)*) => {
+        $($(impl HasDefType<def::$def> for $ty {})+)*
+    };
+}
+
+impl_has_def_type! {
+    i32 => Int32, Sint32, Sfixed32;
+    i64 => Int64, Sint64, Sfixed64;
+    u32 => Uint32, Fixed32;
+    u64 => Uint64, Fixed64;
+    bool => Bool;
+    f64 => Double;
+    f32 => Float;
+    String => String;
+    Cow<'_, str> => String;
+    Vec<u8> => Bytes;
+    Cow<'_, [u8]> => Bytes;
+}
+
+impl<T: VariantEnum> HasDefType<def::Enum> for T {}
+
+// assert_def_type は T が def_type D のフィールドに使えない場合に compile エラーにします
+pub fn assert_def_type<T: HasDefType<D> + ?Sized, D>() {}
+
 // Note: Local ParseError removed; unified into crate::Error.
 
 pub trait Parser<Output>: Sized {
     type Type;
     fn parse(&self, ty: Self::Type) -> Result<Output>;
     // TODO rename
-    fn from(input: Output, ty: Self::Type) -> Result<Self>;
+    // from は値を借用して変換するため、値の複製は発生しません
+    fn from(input: &Output, ty: Self::Type) -> Result<Self>;
 }
 
 impl<T: VariantToValue> Parser<T> for WireDataVarint {
@@ -465,7 +692,7 @@ impl<T: VariantToValue> Parser<T> for WireDataVarint {
         T::from_valint(self.value, ty)
     }
 
-    fn from(input: T, ty: Self::Type) -> Result<Self> {
+    fn from(input: &T, ty: Self::Type) -> Result<Self> {
         Ok(Self {
             value: input.to_variant(ty)?,
         })
@@ -478,7 +705,7 @@ impl<T: Bit64ToValue> Parser<T> for WireDataBit64 {
         T::from_bit64(self.value, ty)
     }
 
-    fn from(input: T, ty: Self::Type) -> Result<Self> {
+    fn from(input: &T, ty: Self::Type) -> Result<Self> {
         Ok(Self {
             value: input.to_bit64(ty)?,
         })
@@ -488,13 +715,12 @@ impl<T: Bit64ToValue> Parser<T> for WireDataBit64 {
 impl<T: LengthDelimitedToValue> Parser<T> for WireDataLengthDelimited {
     type Type = TypeLengthDelimited;
     fn parse(&self, ty: Self::Type) -> Result<T> {
-        // TODO remove clone
-        T::from_length_delimited(self.value.clone(), ty)
+        T::from_length_delimited(&self.value, ty)
     }
 
-    fn from(input: T, ty: Self::Type) -> Result<Self> {
+    fn from(input: &T, ty: Self::Type) -> Result<Self> {
         Ok(Self {
-            value: input.into_length_delimited(ty)?,
+            value: input.to_length_delimited(ty)?,
         })
     }
 }
@@ -505,7 +731,7 @@ impl<T: Bit32ToValue> Parser<T> for WireDataBit32 {
         T::from_bit64(self.value, ty)
     }
 
-    fn from(input: T, ty: Self::Type) -> Result<Self> {
+    fn from(input: &T, ty: Self::Type) -> Result<Self> {
         Ok(Self {
             value: input.to_bit64(ty)?,
         })
@@ -687,6 +913,95 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_cow() {
+        assert_eq!(
+            Parser::<Cow<str>>::parse(
+                &WireDataLengthDelimited::new(vec![0x41, 0x41, 0x41]),
+                TypeLengthDelimited::WireString,
+            )
+            .unwrap(),
+            "AAA"
+        );
+        assert!(Parser::<Cow<str>>::parse(
+            &WireDataLengthDelimited::new(vec![0xFF]),
+            TypeLengthDelimited::WireString,
+        )
+        .is_err());
+        assert_eq!(
+            Parser::<Cow<[u8]>>::parse(
+                &WireDataLengthDelimited::new(vec![0xFF, 0x01]),
+                TypeLengthDelimited::Bytes,
+            )
+            .unwrap(),
+            &[0xFF, 0x01][..]
+        );
+        let x: WireDataLengthDelimited =
+            Parser::<Cow<str>>::from(&Cow::Borrowed("AAA"), TypeLengthDelimited::WireString)
+                .unwrap();
+        assert_eq!(x, WireDataLengthDelimited::new(vec![0x41, 0x41, 0x41]));
+    }
+
+    #[test]
+    fn merge_length_delimited() {
+        {
+            let mut x = "abc".to_string();
+            x.merge_length_delimited(&[0x41, 0x41], TypeLengthDelimited::WireString)
+                .unwrap();
+            assert_eq!(x, "AA");
+        }
+        {
+            let mut x: Vec<u32> = vec![1, 2];
+            x.merge_length_delimited(
+                &[0b00000011, 0b11101000, 0b00000111],
+                TypeLengthDelimited::PackedRepeatedFields(AllowedPakcedType::Variant(
+                    TypeVairant::Uint32,
+                )),
+            )
+            .unwrap();
+            assert_eq!(x, vec![1, 2, 3, 1000]);
+        }
+        {
+            let mut x: Vec<u8> = vec![1, 2];
+            x.merge_length_delimited(&[3], TypeLengthDelimited::Bytes)
+                .unwrap();
+            assert_eq!(x, vec![3]);
+        }
+    }
+
+    #[test]
+    fn check_length_delimited_len() {
+        assert_eq!(
+            "abc"
+                .to_string()
+                .length_delimited_len(TypeLengthDelimited::WireString),
+            3
+        );
+        assert_eq!(
+            Cow::Borrowed(&[1_u8, 2][..]).length_delimited_len(TypeLengthDelimited::Bytes),
+            2
+        );
+        // 1000 は 2 バイト
+        let x: Vec<u32> = vec![1, 2, 1000];
+        let ty = TypeLengthDelimited::PackedRepeatedFields(AllowedPakcedType::Variant(
+            TypeVairant::Uint32,
+        ));
+        assert_eq!(x.length_delimited_len(ty), 4);
+        assert_eq!(
+            x.length_delimited_len(ty),
+            x.to_length_delimited(ty).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn check_is_default() {
+        assert!(is_default(&0_u32));
+        assert!(!is_default(&1_i64));
+        assert!(is_default(&String::new()));
+        assert!(!is_default(&vec![0_u8]));
+        assert!(is_default(&0.0_f64));
+    }
+
     #[test]
     fn parse_vec() {
         {
@@ -879,7 +1194,7 @@ mod tests {
             );
         }
         {
-            let x: WireDataBit64 = Parser::<i64>::from(677, TypeBit64::Sfixed64).unwrap();
+            let x: WireDataBit64 = Parser::<i64>::from(&677, TypeBit64::Sfixed64).unwrap();
             assert_eq!(
                 x,
                 WireDataBit64::new([
@@ -887,7 +1202,7 @@ mod tests {
                     0b00000000, 0b00000000,
                 ]),
             );
-            let x: WireDataBit64 = Parser::<i64>::from(-677, TypeBit64::Sfixed64).unwrap();
+            let x: WireDataBit64 = Parser::<i64>::from(&-677, TypeBit64::Sfixed64).unwrap();
             assert_eq!(
                 x,
                 WireDataBit64::new([
@@ -914,7 +1229,7 @@ mod tests {
             );
         }
         {
-            let x: WireDataBit64 = Parser::<u64>::from(443, TypeBit64::Fixed64).unwrap();
+            let x: WireDataBit64 = Parser::<u64>::from(&443, TypeBit64::Fixed64).unwrap();
             assert_eq!(
                 x,
                 WireDataBit64::new([
@@ -939,7 +1254,7 @@ mod tests {
             assert!((r - 1.25f64).abs() < error_margin);
         }
         {
-            let x: WireDataBit64 = Parser::<f64>::from(1.25f64, TypeBit64::Double).unwrap();
+            let x: WireDataBit64 = Parser::<f64>::from(&1.25f64, TypeBit64::Double).unwrap();
             assert_eq!(
                 x,
                 WireDataBit64::new([
@@ -971,12 +1286,12 @@ mod tests {
             );
         }
         {
-            let x: WireDataBit32 = Parser::<i32>::from(1056, TypeBit32::Sfixed32).unwrap();
+            let x: WireDataBit32 = Parser::<i32>::from(&1056, TypeBit32::Sfixed32).unwrap();
             assert_eq!(
                 x,
                 WireDataBit32::new([0b00100000, 0b00000100, 0b00000000, 0b00000000]),
             );
-            let x: WireDataBit32 = Parser::<i32>::from(-1056, TypeBit32::Sfixed32).unwrap();
+            let x: WireDataBit32 = Parser::<i32>::from(&-1056, TypeBit32::Sfixed32).unwrap();
             assert_eq!(
                 x,
                 WireDataBit32::new([0b11100000, 0b11111011, 0b11111111, 0b11111111,]),
@@ -997,7 +1312,7 @@ mod tests {
             );
         }
         {
-            let x: WireDataBit32 = Parser::<u32>::from(43, TypeBit32::Fixed32).unwrap();
+            let x: WireDataBit32 = Parser::<u32>::from(&43, TypeBit32::Fixed32).unwrap();
             assert_eq!(
                 x,
                 WireDataBit32::new([0b00101011, 0b00000000, 0b00000000, 0b00000000]),
@@ -1016,7 +1331,7 @@ mod tests {
             assert!((r - 3.211f32).abs() < error_margin);
         }
         {
-            let x: WireDataBit32 = Parser::<f32>::from(3.211f32, TypeBit32::Float).unwrap();
+            let x: WireDataBit32 = Parser::<f32>::from(&3.211f32, TypeBit32::Float).unwrap();
             assert_eq!(
                 x,
                 WireDataBit32::new([0b00000110, 0b10000001, 0b01001101, 0b01000000,])
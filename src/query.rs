@@ -0,0 +1,396 @@
+use std::fmt::Display;
+use std::io::Cursor;
+
+use crate::decode::{decode_wire_binary, WireReader};
+use crate::message::WireMessage;
+use crate::parser::{Bit32ToValue, Bit64ToValue, VariantToValue};
+use crate::wire::*;
+use crate::{Error, Result};
+
+// query は field number の並び（パス）で、encode 後のバイト列の中の値を取り出します
+// パスは `.` 区切りの field number です。`14.2` は field number 14 の embedded message の、field number 2 の値を表します
+// 各 field number には、同じフィールドが複数回現れた場合の選択を指定できます
+// 指定なしは protobuf と同様に最後の値（embedded message を辿る場合は、すべての値を統合したもの）、`[*]` はすべての値、`[n]` は n 番目（0 始まり）の値です
+pub fn query(bytes: &[u8], path: &str, ty: impl Into<QueryType>) -> Result<Vec<QueryValue>> {
+    let ty = ty.into();
+    query_raw(bytes, path)?
+        .into_iter()
+        .map(|d| QueryValue::from_wire_data(d, ty))
+        .collect()
+}
+
+// query_raw は パスが指す値を、型を解釈せずに返します
+pub fn query_raw(bytes: &[u8], path: &str) -> Result<Vec<WireData>> {
+    let path = FieldPath::parse(path)?;
+    let mut out = Vec::new();
+    select(bytes, &path.segments, &mut out)?;
+    Ok(out)
+}
+
+// select は segments の先頭のフィールドを選択し、残りの segments を embedded message として再帰的に解釈します
+fn select(bytes: &[u8], segments: &[Segment], out: &mut Vec<WireData>) -> Result<()> {
+    let (segment, rest) = match segments.split_first() {
+        Some(x) => x,
+        None => return Ok(()),
+    };
+    let mut c = Cursor::new(bytes);
+    let mut values: Vec<WireData> = decode_wire_binary(&mut c)?
+        .into_iter()
+        .filter(|sw| sw.field_number() == segment.field_number)
+        .map(WireStruct::into_data)
+        .collect();
+    let selected: Vec<WireData> = match segment.selector {
+        // embedded message を辿る場合は、protobuf と同様に複数回現れた値を統合する
+        Selector::Last if !rest.is_empty() && !values.is_empty() => {
+            let mut merged = Vec::new();
+            for v in values {
+                merged.extend(expect_message(v)?);
+            }
+            vec![WireData::LengthDelimited(WireDataLengthDelimited::new(
+                merged,
+            ))]
+        }
+        Selector::Last => values.pop().into_iter().collect(),
+        Selector::All => values,
+        Selector::Index(n) => values.into_iter().nth(n).into_iter().collect(),
+    };
+    if rest.is_empty() {
+        out.extend(selected);
+        return Ok(());
+    }
+    for v in selected {
+        select(&expect_message(v)?, rest, out)?;
+    }
+    Ok(())
+}
+
+// expect_message は embedded message として辿る値のバイト列を返します
+fn expect_message(v: WireData) -> Result<Vec<u8>> {
+    match v {
+        WireData::LengthDelimited(l) => Ok(l.value),
+        v => Err(Error::UnexpectedType {
+            want: "LengthDelimited".to_string(),
+            got: format!("{}", v),
+        }),
+    }
+}
+
+// FieldPath は query のパスです
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPath {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    field_number: FieldNumber,
+    selector: Selector,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Selector {
+    Last,
+    All,
+    Index(usize),
+}
+
+impl FieldPath {
+    pub fn parse(path: &str) -> Result<Self> {
+        let invalid = |reason: &'static str| Error::InvalidPath {
+            path: path.to_string(),
+            reason,
+        };
+        if path.is_empty() {
+            return Err(invalid("path is empty"));
+        }
+        let segments = path
+            .split('.')
+            .map(|s| {
+                let (number, selector) = match s.split_once('[') {
+                    Some((number, rest)) => {
+                        let selector = rest
+                            .strip_suffix(']')
+                            .ok_or_else(|| invalid("selector must end with `]`"))?;
+                        let selector = match selector {
+                            "*" => Selector::All,
+                            n => Selector::Index(
+                                n.parse()
+                                    .map_err(|_| invalid("selector must be `*` or an index"))?,
+                            ),
+                        };
+                        (number, selector)
+                    }
+                    None => (s, Selector::Last),
+                };
+                let field_number: FieldNumber = number
+                    .parse()
+                    .map_err(|_| invalid("field number must be an integer"))?;
+                if field_number == 0 {
+                    return Err(invalid("field number must be greater than 0"));
+                }
+                Ok(Segment {
+                    field_number,
+                    selector,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { segments })
+    }
+}
+
+// QueryType は query の結果の型です
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryType {
+    Variant(TypeVairant),
+    Bit64(TypeBit64),
+    LengthDelimited(TypeLengthDelimited),
+    Bit32(TypeBit32),
+}
+
+impl From<TypeVairant> for QueryType {
+    fn from(v: TypeVairant) -> Self {
+        QueryType::Variant(v)
+    }
+}
+
+impl From<TypeBit64> for QueryType {
+    fn from(v: TypeBit64) -> Self {
+        QueryType::Bit64(v)
+    }
+}
+
+impl From<TypeLengthDelimited> for QueryType {
+    fn from(v: TypeLengthDelimited) -> Self {
+        QueryType::LengthDelimited(v)
+    }
+}
+
+impl From<TypeBit32> for QueryType {
+    fn from(v: TypeBit32) -> Self {
+        QueryType::Bit32(v)
+    }
+}
+
+// QueryValue は QueryType に従って解釈した値です
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryValue {
+    Int32(i32),
+    Int64(i64),
+    Uint32(u32),
+    Uint64(u64),
+    Bool(bool),
+    Enum(i32),
+    Double(f64),
+    Float(f32),
+    String(String),
+    Bytes(Vec<u8>),
+    Message(WireMessage),
+    Packed(Vec<QueryValue>),
+}
+
+impl QueryValue {
+    // from_wire_data は ty に従って値を解釈します
+    // ty の wire type が値と一致しない場合はエラーになります
+    pub fn from_wire_data(data: WireData, ty: QueryType) -> Result<Self> {
+        match (data, ty) {
+            (WireData::Varint(v), QueryType::Variant(ty)) => Self::from_variant(v.value, ty),
+            (WireData::Bit64(v), QueryType::Bit64(ty)) => Self::from_bit64(v.value, ty),
+            (WireData::Bit32(v), QueryType::Bit32(ty)) => Self::from_bit32(v.value, ty),
+            (WireData::LengthDelimited(v), QueryType::LengthDelimited(ty)) => match ty {
+                TypeLengthDelimited::WireString => {
+                    Ok(QueryValue::String(String::from_utf8(v.value)?))
+                }
+                TypeLengthDelimited::Bytes => Ok(QueryValue::Bytes(v.value)),
+                TypeLengthDelimited::EmbeddedMessages => {
+                    Ok(QueryValue::Message(WireMessage::parse(&v.value)?))
+                }
+                TypeLengthDelimited::PackedRepeatedFields(ty) => Self::from_packed(&v.value, ty),
+            },
+            (data, ty) => Err(Error::UnexpectedType {
+                want: format!("{:?}", ty),
+                got: format!("{}", data),
+            }),
+        }
+    }
+
+    fn from_variant(v: u128, ty: TypeVairant) -> Result<Self> {
+        Ok(match ty {
+            TypeVairant::Int32 | TypeVairant::Sint32 => QueryValue::Int32(i32::from_valint(v, ty)?),
+            TypeVairant::Int64 | TypeVairant::Sint64 => QueryValue::Int64(i64::from_valint(v, ty)?),
+            TypeVairant::Uint32 => QueryValue::Uint32(u32::from_valint(v, ty)?),
+            TypeVairant::Uint64 => QueryValue::Uint64(u64::from_valint(v, ty)?),
+            TypeVairant::Bool => QueryValue::Bool(bool::from_valint(v, ty)?),
+            // enum は int32 と同じ形式で encode されます
+            TypeVairant::Enum => QueryValue::Enum(i32::from_valint(v, TypeVairant::Int32)?),
+        })
+    }
+
+    fn from_bit64(v: [u8; 8], ty: TypeBit64) -> Result<Self> {
+        Ok(match ty {
+            TypeBit64::Fixed64 => QueryValue::Uint64(u64::from_bit64(v, ty)?),
+            TypeBit64::Sfixed64 => QueryValue::Int64(i64::from_bit64(v, ty)?),
+            TypeBit64::Double => QueryValue::Double(f64::from_bit64(v, ty)?),
+        })
+    }
+
+    fn from_bit32(v: [u8; 4], ty: TypeBit32) -> Result<Self> {
+        Ok(match ty {
+            TypeBit32::Fixed32 => QueryValue::Uint32(u32::from_bit64(v, ty)?),
+            TypeBit32::Sfixed32 => QueryValue::Int32(i32::from_bit64(v, ty)?),
+            TypeBit32::Float => QueryValue::Float(f32::from_bit64(v, ty)?),
+        })
+    }
+
+    fn from_packed(bytes: &[u8], ty: AllowedPakcedType) -> Result<Self> {
+        let mut r = WireReader::new(bytes);
+        let mut values = Vec::new();
+        while !r.is_empty() {
+            values.push(match ty {
+                AllowedPakcedType::Variant(ty) => Self::from_variant(r.read_varint()?, ty)?,
+                AllowedPakcedType::Bit64(ty) => Self::from_bit64(r.read_bit64()?, ty)?,
+                AllowedPakcedType::Bit32(ty) => Self::from_bit32(r.read_bit32()?, ty)?,
+            });
+        }
+        Ok(QueryValue::Packed(values))
+    }
+}
+
+impl Display for QueryValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryValue::Int32(v) | QueryValue::Enum(v) => write!(f, "{}", v),
+            QueryValue::Int64(v) => write!(f, "{}", v),
+            QueryValue::Uint32(v) => write!(f, "{}", v),
+            QueryValue::Uint64(v) => write!(f, "{}", v),
+            QueryValue::Bool(v) => write!(f, "{}", v),
+            QueryValue::Double(v) => write!(f, "{}", v),
+            QueryValue::Float(v) => write!(f, "{}", v),
+            QueryValue::String(v) => write!(f, "{:?}", v),
+            QueryValue::Bytes(v) => write!(f, "{:?}", v),
+            QueryValue::Message(v) => write!(f, "{:?}", v),
+            QueryValue::Packed(v) => {
+                write!(f, "[")?;
+                for (i, x) in v.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", x)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1: 150, 14: { 1: "a", 2: -1 }, 14: { 1: "b", 2: 1 }, 3: packed [1, 2]
+    const BYTES: &[u8] = &[
+        0b00001000, 0b10010110, 0b00000001, // 1
+        0b01110010, 0b00000101, 0b00001010, 0b00000001, b'a', 0b00010000, 0b00000001, // 14
+        0b01110010, 0b00000101, 0b00001010, 0b00000001, b'b', 0b00010000, 0b00000010, // 14
+        0b00011010, 0b00000010, 0b00000001, 0b00000010, // 3
+    ];
+
+    #[test]
+    fn test_field_path() {
+        assert_eq!(
+            FieldPath::parse("14[*].2[1].3").unwrap().segments,
+            vec![
+                Segment {
+                    field_number: 14,
+                    selector: Selector::All
+                },
+                Segment {
+                    field_number: 2,
+                    selector: Selector::Index(1)
+                },
+                Segment {
+                    field_number: 3,
+                    selector: Selector::Last
+                },
+            ]
+        );
+        for path in ["", "1.", "a", "0", "1[", "1[x]", "1[*", "1.-2"] {
+            assert!(
+                matches!(FieldPath::parse(path), Err(Error::InvalidPath { .. })),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_query() {
+        assert_eq!(
+            query(BYTES, "1", TypeVairant::Uint32).unwrap(),
+            vec![QueryValue::Uint32(150)]
+        );
+        assert_eq!(
+            query(BYTES, "14.1", TypeLengthDelimited::WireString).unwrap(),
+            vec![QueryValue::String("b".to_string())]
+        );
+        assert_eq!(
+            query(BYTES, "14[*].2", TypeVairant::Sint32).unwrap(),
+            vec![QueryValue::Int32(-1), QueryValue::Int32(1)]
+        );
+        assert_eq!(
+            query(BYTES, "14[0].1", TypeLengthDelimited::Bytes).unwrap(),
+            vec![QueryValue::Bytes(b"a".to_vec())]
+        );
+        assert!(query(BYTES, "14[2].1", TypeLengthDelimited::Bytes)
+            .unwrap()
+            .is_empty());
+        assert!(query(BYTES, "4", TypeVairant::Uint32).unwrap().is_empty());
+        assert_eq!(
+            query(
+                BYTES,
+                "3",
+                TypeLengthDelimited::PackedRepeatedFields(AllowedPakcedType::Variant(
+                    TypeVairant::Uint32
+                ))
+            )
+            .unwrap(),
+            vec![QueryValue::Packed(vec![
+                QueryValue::Uint32(1),
+                QueryValue::Uint32(2)
+            ])]
+        );
+        let got = query(BYTES, "14", TypeLengthDelimited::EmbeddedMessages).unwrap();
+        assert!(matches!(&got[..], [QueryValue::Message(m)] if m.len() == 2));
+
+        // 指定なしで辿る embedded message は、すべての値を統合する
+        // 14: { 1: "a" }, 14: { 2: 1 }
+        let bytes: &[u8] = &[
+            0b01110010, 0b00000011, 0b00001010, 0b00000001, b'a', // 14
+            0b01110010, 0b00000010, 0b00010000, 0b00000001, // 14
+        ];
+        assert_eq!(
+            query(bytes, "14.1", TypeLengthDelimited::WireString).unwrap(),
+            vec![QueryValue::String("a".to_string())]
+        );
+        assert_eq!(
+            query(bytes, "14.2", TypeVairant::Uint32).unwrap(),
+            vec![QueryValue::Uint32(1)]
+        );
+        assert!(query(bytes, "14[1].1", TypeLengthDelimited::WireString)
+            .unwrap()
+            .is_empty());
+
+        // wire type が一致しない
+        assert!(query(BYTES, "1", TypeBit64::Fixed64).is_err());
+        // embedded message でない値の中は参照できない
+        assert!(query_raw(BYTES, "1.1").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let v = QueryValue::Packed(vec![
+            QueryValue::Int32(-1),
+            QueryValue::String("a".to_string()),
+        ]);
+        assert_eq!(v.to_string(), r#"[-1, "a"]"#);
+    }
+}
@@ -0,0 +1,295 @@
+use crate::decode::WireReader;
+use crate::encode::{
+    length_delimited_field_len, push_bytes_reversed, push_variant_reversed, variant_len,
+    EncodeOptions,
+};
+use crate::parser::{Bit32ToValue, Bit64ToValue, LengthDelimitedToValue, VariantToValue};
+use crate::wire::{TypeBit32, TypeBit64, TypeLengthDelimited, TypeVairant};
+use crate::Result;
+
+// 以下は derive で生成されるコードから使われる、repeated フィールドを要素ごとに encode/decode する処理です
+// scalar の repeated フィールドは、packed（1 つの length-delimited）と unpacked（要素ごとに tag を付ける）の
+// どちらでも読み込めます。書き込み時は packed で指定します
+
+// read_packed_varint は packed な varint の値をすべて decode し、out の末尾に追加します
+pub fn read_packed_varint<T: VariantToValue>(
+    input: &[u8],
+    ty: TypeVairant,
+    out: &mut Vec<T>,
+) -> Result<()> {
+    let mut r = WireReader::new(input);
+    while !r.is_empty() {
+        out.push(T::from_valint(r.read_varint()?, ty)?);
+    }
+    Ok(())
+}
+
+// read_packed_bit64 は packed な 64-bit の値をすべて decode し、out の末尾に追加します
+pub fn read_packed_bit64<T: Bit64ToValue>(
+    input: &[u8],
+    ty: TypeBit64,
+    out: &mut Vec<T>,
+) -> Result<()> {
+    let mut r = WireReader::new(input);
+    while !r.is_empty() {
+        out.push(T::from_bit64(r.read_bit64()?, ty)?);
+    }
+    Ok(())
+}
+
+// read_packed_bit32 は packed な 32-bit の値をすべて decode し、out の末尾に追加します
+pub fn read_packed_bit32<T: Bit32ToValue>(
+    input: &[u8],
+    ty: TypeBit32,
+    out: &mut Vec<T>,
+) -> Result<()> {
+    let mut r = WireReader::new(input);
+    while !r.is_empty() {
+        out.push(T::from_bit64(r.read_bit32()?, ty)?);
+    }
+    Ok(())
+}
+
+// write_packed_reversed は push で要素を書き込んだ後、長さと tag を書き込みます
+// 要素がない場合は何も書き込みません
+fn write_packed_reversed<F>(buf: &mut Vec<u8>, field_number: u128, push: F) -> Result<()>
+where
+    F: FnOnce(&mut Vec<u8>) -> Result<()>,
+{
+    let start = buf.len();
+    push(buf)?;
+    let len = buf.len() - start;
+    if len == 0 {
+        return Ok(());
+    }
+    push_variant_reversed(buf, len as u128);
+    push_variant_reversed(buf, (field_number << 3) + 2);
+    Ok(())
+}
+
+// write_varint_elements_reversed は values を逆順で書き込みます
+// 値がゼロの要素も省略せずに書き込みます
+pub fn write_varint_elements_reversed<T: VariantToValue>(
+    buf: &mut Vec<u8>,
+    field_number: u128,
+    values: &[T],
+    ty: TypeVairant,
+    packed: bool,
+) -> Result<()> {
+    if packed {
+        return write_packed_reversed(buf, field_number, |buf| {
+            for v in values.iter().rev() {
+                push_variant_reversed(buf, v.to_variant(ty)?);
+            }
+            Ok(())
+        });
+    }
+    for v in values.iter().rev() {
+        push_variant_reversed(buf, v.to_variant(ty)?);
+        push_variant_reversed(buf, field_number << 3);
+    }
+    Ok(())
+}
+
+// write_bit64_elements_reversed は values を逆順で書き込みます
+pub fn write_bit64_elements_reversed<T: Bit64ToValue>(
+    buf: &mut Vec<u8>,
+    field_number: u128,
+    values: &[T],
+    ty: TypeBit64,
+    packed: bool,
+) -> Result<()> {
+    if packed {
+        return write_packed_reversed(buf, field_number, |buf| {
+            for v in values.iter().rev() {
+                push_bytes_reversed(buf, &v.to_bit64(ty)?);
+            }
+            Ok(())
+        });
+    }
+    for v in values.iter().rev() {
+        push_bytes_reversed(buf, &v.to_bit64(ty)?);
+        push_variant_reversed(buf, (field_number << 3) + 1);
+    }
+    Ok(())
+}
+
+// write_bit32_elements_reversed は values を逆順で書き込みます
+pub fn write_bit32_elements_reversed<T: Bit32ToValue>(
+    buf: &mut Vec<u8>,
+    field_number: u128,
+    values: &[T],
+    ty: TypeBit32,
+    packed: bool,
+) -> Result<()> {
+    if packed {
+        return write_packed_reversed(buf, field_number, |buf| {
+            for v in values.iter().rev() {
+                push_bytes_reversed(buf, &v.to_bit64(ty)?);
+            }
+            Ok(())
+        });
+    }
+    for v in values.iter().rev() {
+        push_bytes_reversed(buf, &v.to_bit64(ty)?);
+        push_variant_reversed(buf, (field_number << 3) + 5);
+    }
+    Ok(())
+}
+
+// write_length_delimited_elements_reversed は values を要素ごとに tag を付けて逆順で書き込みます
+// 空の要素も省略せずに書き込みます
+pub fn write_length_delimited_elements_reversed<T: LengthDelimitedToValue>(
+    buf: &mut Vec<u8>,
+    field_number: u128,
+    values: &[T],
+    ty: TypeLengthDelimited,
+    options: &EncodeOptions,
+) -> Result<()> {
+    for v in values.iter().rev() {
+        let start = buf.len();
+        v.write_length_delimited_reversed_with_options(ty, buf, options)?;
+        let len = buf.len() - start;
+        push_variant_reversed(buf, len as u128);
+        push_variant_reversed(buf, (field_number << 3) + 2);
+    }
+    Ok(())
+}
+
+// 以下の *_elements_len は write_*_elements_reversed で書き込んだ場合のバイト数を返します
+// 値の変換に失敗する場合は 0 とします（bytes ではエラーになります）
+
+fn elements_len(field_number: u128, len: usize, count: usize, packed: bool) -> usize {
+    if packed {
+        return length_delimited_field_len(field_number, len);
+    }
+    variant_len(field_number << 3) * count + len
+}
+
+pub fn varint_elements_len<T: VariantToValue>(
+    field_number: u128,
+    values: &[T],
+    ty: TypeVairant,
+    packed: bool,
+) -> usize {
+    let len = values
+        .iter()
+        .map(|v| v.to_variant(ty).map(variant_len).unwrap_or_default())
+        .sum();
+    elements_len(field_number, len, values.len(), packed)
+}
+
+pub fn bit64_elements_len<T>(field_number: u128, values: &[T], packed: bool) -> usize {
+    elements_len(field_number, 8 * values.len(), values.len(), packed)
+}
+
+pub fn bit32_elements_len<T>(field_number: u128, values: &[T], packed: bool) -> usize {
+    elements_len(field_number, 4 * values.len(), values.len(), packed)
+}
+
+pub fn length_delimited_elements_len<T: LengthDelimitedToValue>(
+    field_number: u128,
+    values: &[T],
+    ty: TypeLengthDelimited,
+) -> usize {
+    values
+        .iter()
+        .map(|v| {
+            let len = v.length_delimited_len(ty);
+            variant_len(field_number << 3) + variant_len(len as u128) + len
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode<F: FnOnce(&mut Vec<u8>) -> Result<()>>(write: F) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write(&mut buf).unwrap();
+        buf.reverse();
+        buf
+    }
+
+    #[test]
+    fn test_varint_elements() {
+        let values: Vec<u32> = vec![1, 0, 300];
+        let packed =
+            encode(|b| write_varint_elements_reversed(b, 4, &values, TypeVairant::Uint32, true));
+        assert_eq!(
+            packed,
+            vec![0b00100010, 4, 0b00000001, 0, 0b10101100, 0b00000010]
+        );
+        assert_eq!(
+            varint_elements_len(4, &values, TypeVairant::Uint32, true),
+            packed.len()
+        );
+        let unpacked =
+            encode(|b| write_varint_elements_reversed(b, 4, &values, TypeVairant::Uint32, false));
+        assert_eq!(
+            unpacked,
+            vec![0b00100000, 1, 0b00100000, 0, 0b00100000, 0b10101100, 0b00000010]
+        );
+        assert_eq!(
+            varint_elements_len(4, &values, TypeVairant::Uint32, false),
+            unpacked.len()
+        );
+
+        let mut got: Vec<u32> = Vec::new();
+        read_packed_varint(&packed[2..], TypeVairant::Uint32, &mut got).unwrap();
+        assert_eq!(got, values);
+
+        // 要素がない場合、packed でも何も書き込まない
+        let empty: Vec<u32> = Vec::new();
+        assert!(encode(|b| {
+            write_varint_elements_reversed(b, 4, &empty, TypeVairant::Uint32, true)
+        })
+        .is_empty());
+        assert_eq!(varint_elements_len(4, &empty, TypeVairant::Uint32, true), 0);
+    }
+
+    #[test]
+    fn test_fixed_elements() {
+        let values: Vec<f64> = vec![1.5, 0.0];
+        let packed =
+            encode(|b| write_bit64_elements_reversed(b, 1, &values, TypeBit64::Double, true));
+        assert_eq!(packed.len(), 2 + 16);
+        assert_eq!(bit64_elements_len(1, &values, true), packed.len());
+        let mut got: Vec<f64> = Vec::new();
+        read_packed_bit64(&packed[2..], TypeBit64::Double, &mut got).unwrap();
+        assert_eq!(got, values);
+
+        let values: Vec<u32> = vec![7, 0, 9];
+        let unpacked =
+            encode(|b| write_bit32_elements_reversed(b, 2, &values, TypeBit32::Fixed32, false));
+        assert_eq!(unpacked.len(), 3 * 5);
+        assert_eq!(bit32_elements_len(2, &values, false), unpacked.len());
+        let mut got: Vec<u32> = Vec::new();
+        read_packed_bit32(&[7, 0, 0, 0, 9, 0, 0, 0], TypeBit32::Fixed32, &mut got).unwrap();
+        assert_eq!(got, vec![7, 9]);
+
+        // 4 バイトに満たない
+        assert!(read_packed_bit32(&[7, 0, 0], TypeBit32::Fixed32, &mut got).is_err());
+    }
+
+    #[test]
+    fn test_length_delimited_elements() {
+        let values = vec!["ab".to_string(), String::new()];
+        let got = encode(|b| {
+            write_length_delimited_elements_reversed(
+                b,
+                3,
+                &values,
+                TypeLengthDelimited::WireString,
+                &EncodeOptions::default(),
+            )
+        });
+        // 空の要素も書き込む
+        assert_eq!(got, vec![0b00011010, 2, b'a', b'b', 0b00011010, 0]);
+        assert_eq!(
+            length_delimited_elements_len(3, &values, TypeLengthDelimited::WireString),
+            got.len()
+        );
+    }
+}
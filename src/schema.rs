@@ -0,0 +1,1657 @@
+use crate::text::Position;
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+// 以下は .proto ファイルを protoc なしで読み込み、AST にする処理です
+// proto2 と proto3 の構文（message, 入れ子の型, enum, oneof, map, option, import, package, reserved）に対応します
+// 型名の解決は行わず、記述されたままの名前（FieldType::Named）を保持します
+
+// MAX_FIELD_NUMBER は field number の最大値です
+pub const MAX_FIELD_NUMBER: u32 = (1 << 29) - 1;
+
+// RESERVED_FIELD_NUMBERS は protobuf の実装が予約している field number の範囲です
+const RESERVED_FIELD_NUMBERS: std::ops::RangeInclusive<u32> = 19000..=19999;
+
+// ProtoFile は 1 つの .proto ファイルの内容です
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtoFile {
+    // name はエラーメッセージで使うファイル名です
+    pub name: String,
+    pub syntax: Syntax,
+    pub package: Option<String>,
+    pub imports: Vec<Import>,
+    pub options: Vec<OptionDef>,
+    pub messages: Vec<Message>,
+    pub enums: Vec<EnumDef>,
+    pub services: Vec<Service>,
+    pub extends: Vec<Extend>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Proto2,
+    Proto3,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    pub path: String,
+    pub kind: ImportKind,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportKind {
+    Default,
+    Public,
+    Weak,
+}
+
+// OptionDef は `option name = value;` やフィールドの `[name = value]` です
+// name は記述されたままの名前（`java_package`, `(my.option).field` など）です
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionDef {
+    pub name: String,
+    pub value: Constant,
+}
+
+// Constant は option の値です
+// Aggregate は `{ ... }` で記述された値で、text format の文字列のまま保持します
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    Identifier(String),
+    Integer(i128),
+    Float(f64),
+    String(Vec<u8>),
+    Bool(bool),
+    Aggregate(String),
+}
+
+impl Constant {
+    // as_str は UTF-8 の文字列の値を返します
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Constant::String(s) => std::str::from_utf8(s).ok(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub name: String,
+    pub position: Position,
+    // fields は oneof 以外のフィールドです
+    pub fields: Vec<Field>,
+    pub oneofs: Vec<Oneof>,
+    pub messages: Vec<Message>,
+    pub enums: Vec<EnumDef>,
+    pub extends: Vec<Extend>,
+    pub reserved: Vec<Reserved>,
+    pub extensions: Vec<FieldRange>,
+    pub options: Vec<OptionDef>,
+}
+
+impl Message {
+    // all_fields は oneof のフィールドを含むすべてのフィールドを返します
+    pub fn all_fields(&self) -> impl Iterator<Item = &Field> {
+        self.fields
+            .iter()
+            .chain(self.oneofs.iter().flat_map(|o| o.fields.iter()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub number: u32,
+    pub label: Label,
+    pub ty: FieldType,
+    pub options: Vec<OptionDef>,
+    pub position: Position,
+}
+
+impl Field {
+    // option は name の option の値を返します
+    pub fn option(&self, name: &str) -> Option<&Constant> {
+        self.options
+            .iter()
+            .rev()
+            .find(|o| o.name == name)
+            .map(|o| &o.value)
+    }
+
+    // json_name は proto3 JSON でのフィールド名を返します
+    // json_name option がない場合は protoc と同じく、`_` を取り除いてその次の文字を大文字にした名前です
+    pub fn json_name(&self) -> String {
+        if let Some(name) = self.option("json_name").and_then(|c| c.as_str()) {
+            return name.to_string();
+        }
+        let mut out = String::with_capacity(self.name.len());
+        let mut upper = false;
+        for c in self.name.chars() {
+            if c == '_' {
+                upper = true;
+            } else if upper {
+                out.extend(c.to_uppercase());
+                upper = false;
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+}
+
+// Label はフィールドの label です
+// None は label を省略した proto3 のフィールド、oneof と map のフィールドです
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Label {
+    None,
+    Optional,
+    Required,
+    Repeated,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldType {
+    Scalar(ScalarType),
+    // Named は message または enum の名前です（先頭の `.` を含め、記述されたまま）
+    Named(String),
+    Map(ScalarType, Box<FieldType>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarType {
+    Double,
+    Float,
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+    Sint32,
+    Sint64,
+    Fixed32,
+    Fixed64,
+    Sfixed32,
+    Sfixed64,
+    Bool,
+    String,
+    Bytes,
+}
+
+impl ScalarType {
+    fn from_name(name: &str) -> Option<Self> {
+        let ty = match name {
+            "double" => ScalarType::Double,
+            "float" => ScalarType::Float,
+            "int32" => ScalarType::Int32,
+            "int64" => ScalarType::Int64,
+            "uint32" => ScalarType::Uint32,
+            "uint64" => ScalarType::Uint64,
+            "sint32" => ScalarType::Sint32,
+            "sint64" => ScalarType::Sint64,
+            "fixed32" => ScalarType::Fixed32,
+            "fixed64" => ScalarType::Fixed64,
+            "sfixed32" => ScalarType::Sfixed32,
+            "sfixed64" => ScalarType::Sfixed64,
+            "bool" => ScalarType::Bool,
+            "string" => ScalarType::String,
+            "bytes" => ScalarType::Bytes,
+            _ => return None,
+        };
+        Some(ty)
+    }
+
+    // name は .proto での型名を返します（#[def(def_type = "...")] の値と同じです）
+    pub fn name(self) -> &'static str {
+        match self {
+            ScalarType::Double => "double",
+            ScalarType::Float => "float",
+            ScalarType::Int32 => "int32",
+            ScalarType::Int64 => "int64",
+            ScalarType::Uint32 => "uint32",
+            ScalarType::Uint64 => "uint64",
+            ScalarType::Sint32 => "sint32",
+            ScalarType::Sint64 => "sint64",
+            ScalarType::Fixed32 => "fixed32",
+            ScalarType::Fixed64 => "fixed64",
+            ScalarType::Sfixed32 => "sfixed32",
+            ScalarType::Sfixed64 => "sfixed64",
+            ScalarType::Bool => "bool",
+            ScalarType::String => "string",
+            ScalarType::Bytes => "bytes",
+        }
+    }
+
+    // is_map_key は map の key に使える型かどうかを返します（浮動小数点数と bytes 以外）
+    pub fn is_map_key(self) -> bool {
+        !matches!(
+            self,
+            ScalarType::Double | ScalarType::Float | ScalarType::Bytes
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Oneof {
+    pub name: String,
+    pub fields: Vec<Field>,
+    pub options: Vec<OptionDef>,
+}
+
+// FieldRange は reserved や extensions の field number の範囲です（end を含みます）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldRange {
+    pub start: i64,
+    pub end: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reserved {
+    Ranges(Vec<FieldRange>),
+    Names(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumDef {
+    pub name: String,
+    pub position: Position,
+    pub values: Vec<EnumValue>,
+    pub reserved: Vec<Reserved>,
+    pub options: Vec<OptionDef>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumValue {
+    pub name: String,
+    pub number: i32,
+    pub options: Vec<OptionDef>,
+    pub position: Position,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Service {
+    pub name: String,
+    pub methods: Vec<Method>,
+    pub options: Vec<OptionDef>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Method {
+    pub name: String,
+    pub input: String,
+    pub output: String,
+    pub client_streaming: bool,
+    pub server_streaming: bool,
+    pub options: Vec<OptionDef>,
+}
+
+// Extend は proto2 の `extend Name { ... }` です
+#[derive(Debug, Clone, PartialEq)]
+pub struct Extend {
+    pub extendee: String,
+    pub fields: Vec<Field>,
+}
+
+// parse は source を .proto ファイルとして読み込みます
+// name はエラーメッセージで使うファイル名です
+pub fn parse(name: &str, source: &str) -> Result<ProtoFile> {
+    let tokens = tokenize(name, source)?;
+    let mut p = SchemaParser {
+        file: name,
+        tokens,
+        pos: 0,
+        syntax: Syntax::Proto2,
+    };
+    p.file()
+}
+
+// parse_file は path のファイルを .proto ファイルとして読み込みます
+pub fn parse_file(path: impl AsRef<Path>) -> Result<ProtoFile> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path)?;
+    parse(&path.display().to_string(), &source)
+}
+
+fn error_at(file: &str, position: Position, reason: impl Into<String>) -> Error {
+    Error::InvalidSchema {
+        file: file.to_string(),
+        line: position.line,
+        column: position.column,
+        reason: reason.into(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Int(u64),
+    Float(f64),
+    Str(Vec<u8>),
+    Symbol(char),
+    Eof,
+}
+
+impl Tok {
+    fn describe(&self) -> String {
+        match self {
+            Tok::Ident(i) => format!("`{}`", i),
+            Tok::Int(i) => format!("`{}`", i),
+            Tok::Float(f) => format!("`{}`", f),
+            Tok::Str(_) => "string".to_string(),
+            Tok::Symbol(c) => format!("`{}`", c),
+            Tok::Eof => "end of file".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    tok: Tok,
+    position: Position,
+}
+
+// tokenize は source をトークンに分割します。コメントと空白は読み飛ばします
+fn tokenize(file: &str, source: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut position = Position { line: 1, column: 1 };
+    // advance は n 文字読み進め、行と列を更新します
+    let advance = |i: &mut usize, position: &mut Position, n: usize| {
+        for _ in 0..n {
+            if chars.get(*i) == Some(&'\n') {
+                position.line += 1;
+                position.column = 1;
+            } else {
+                position.column += 1;
+            }
+            *i += 1;
+        }
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        let start = position;
+        if c.is_whitespace() {
+            advance(&mut i, &mut position, 1);
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                advance(&mut i, &mut position, 1);
+            }
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'*') {
+            advance(&mut i, &mut position, 2);
+            loop {
+                if i >= chars.len() {
+                    return Err(error_at(file, start, "unterminated comment"));
+                }
+                if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                    advance(&mut i, &mut position, 2);
+                    break;
+                }
+                advance(&mut i, &mut position, 1);
+            }
+            continue;
+        }
+        if c.is_ascii_alphabetic() || c == '_' {
+            let n = chars[i..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                .count();
+            let ident: String = chars[i..i + n].iter().collect();
+            advance(&mut i, &mut position, n);
+            tokens.push(Token {
+                tok: Tok::Ident(ident),
+                position: start,
+            });
+            continue;
+        }
+        if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()))
+        {
+            let mut n = 0;
+            while let Some(&d) = chars.get(i + n) {
+                let exponent_sign = (d == '+' || d == '-')
+                    && n > 0
+                    && matches!(chars[i + n - 1], 'e' | 'E')
+                    && !matches!(chars.get(i + 1), Some('x' | 'X'));
+                if !(d.is_ascii_alphanumeric() || d == '.' || exponent_sign) {
+                    break;
+                }
+                n += 1;
+            }
+            let text: String = chars[i..i + n].iter().collect();
+            let tok = parse_number(&text)
+                .ok_or_else(|| error_at(file, start, format!("invalid number `{}`", text)))?;
+            advance(&mut i, &mut position, n);
+            tokens.push(Token {
+                tok,
+                position: start,
+            });
+            continue;
+        }
+        if c == '"' || c == '\'' {
+            advance(&mut i, &mut position, 1);
+            let mut out = Vec::new();
+            loop {
+                let Some(&s) = chars.get(i).filter(|s| **s != '\n') else {
+                    return Err(error_at(file, start, "unterminated string"));
+                };
+                if s == c {
+                    advance(&mut i, &mut position, 1);
+                    break;
+                }
+                if s != '\\' {
+                    let mut buf = [0; 4];
+                    out.extend_from_slice(s.encode_utf8(&mut buf).as_bytes());
+                    advance(&mut i, &mut position, 1);
+                    continue;
+                }
+                let escape = position;
+                let (bytes, n) = unescape(&chars[i + 1..])
+                    .ok_or_else(|| error_at(file, escape, "invalid escape"))?;
+                out.extend_from_slice(&bytes);
+                advance(&mut i, &mut position, 1 + n);
+            }
+            tokens.push(Token {
+                tok: Tok::Str(out),
+                position: start,
+            });
+            continue;
+        }
+        if "{}[]()<>;,=.:-+".contains(c) {
+            advance(&mut i, &mut position, 1);
+            tokens.push(Token {
+                tok: Tok::Symbol(c),
+                position: start,
+            });
+            continue;
+        }
+        return Err(error_at(file, start, format!("unexpected `{}`", c)));
+    }
+    tokens.push(Token {
+        tok: Tok::Eof,
+        position,
+    });
+    Ok(tokens)
+}
+
+// parse_number は 10 進数・16 進数（0x）・8 進数（0 始まり）の整数と浮動小数点数を読み込みます
+fn parse_number(text: &str) -> Option<Tok> {
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        return u64::from_str_radix(hex, 16).ok().map(Tok::Int);
+    }
+    if text.contains(['.', 'e', 'E']) {
+        return text.parse::<f64>().ok().map(Tok::Float);
+    }
+    if text.len() > 1 && text.starts_with('0') {
+        return u64::from_str_radix(&text[1..], 8).ok().map(Tok::Int);
+    }
+    text.parse::<u64>().ok().map(Tok::Int)
+}
+
+// unescape は `\` に続くエスケープを解除し、値と読み込んだ文字数を返します
+fn unescape(chars: &[char]) -> Option<(Vec<u8>, usize)> {
+    let digits = |radix: u32, max: usize| -> (u32, usize) {
+        chars[1..]
+            .iter()
+            .take(max)
+            .map_while(|c| c.to_digit(radix))
+            .fold((0, 0), |(v, n), d| (v * radix + d, n + 1))
+    };
+    let byte = |b: u8| Some((vec![b], 1));
+    match chars.first()? {
+        'a' => byte(0x07),
+        'b' => byte(0x08),
+        'f' => byte(0x0c),
+        'n' => byte(b'\n'),
+        'r' => byte(b'\r'),
+        't' => byte(b'\t'),
+        'v' => byte(0x0b),
+        c @ ('\\' | '\'' | '"' | '?') => byte(*c as u8),
+        '0'..='7' => {
+            let (v, n) = chars
+                .iter()
+                .take(3)
+                .map_while(|c| c.to_digit(8))
+                .fold((0, 0), |(v, n), d| (v * 8 + d, n + 1));
+            Some((vec![u8::try_from(v).ok()?], n))
+        }
+        'x' | 'X' => match digits(16, 2) {
+            (_, 0) => None,
+            (v, n) => Some((vec![v as u8], 1 + n)),
+        },
+        u @ ('u' | 'U') => {
+            let len = if *u == 'u' { 4 } else { 8 };
+            let (v, n) = digits(16, len);
+            if n != len {
+                return None;
+            }
+            let mut buf = [0; 4];
+            let s = char::from_u32(v)?.encode_utf8(&mut buf);
+            Some((s.as_bytes().to_vec(), 1 + n))
+        }
+        _ => None,
+    }
+}
+
+struct SchemaParser<'a> {
+    file: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    syntax: Syntax,
+}
+
+impl SchemaParser<'_> {
+    fn peek(&self) -> &Tok {
+        &self.tokens[self.pos].tok
+    }
+
+    fn peek_at(&self, n: usize) -> &Tok {
+        let i = (self.pos + n).min(self.tokens.len() - 1);
+        &self.tokens[i].tok
+    }
+
+    fn position(&self) -> Position {
+        self.tokens[self.pos].position
+    }
+
+    fn next(&mut self) -> Tok {
+        let tok = self.tokens[self.pos].tok.clone();
+        if self.pos < self.tokens.len() - 1 {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn error_at(&self, position: Position, reason: impl Into<String>) -> Error {
+        error_at(self.file, position, reason)
+    }
+
+    fn unexpected(&self, want: &str) -> Error {
+        self.error_at(
+            self.position(),
+            format!("expected {}, got {}", want, self.peek().describe()),
+        )
+    }
+
+    fn is_symbol(&self, c: char) -> bool {
+        *self.peek() == Tok::Symbol(c)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Tok::Ident(i) if i == keyword)
+    }
+
+    fn eat_symbol(&mut self, c: char) -> bool {
+        if self.is_symbol(c) {
+            self.next();
+            return true;
+        }
+        false
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.next();
+            return true;
+        }
+        false
+    }
+
+    fn expect_symbol(&mut self, c: char) -> Result<()> {
+        if self.eat_symbol(c) {
+            return Ok(());
+        }
+        Err(self.unexpected(&format!("`{}`", c)))
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.peek() {
+            Tok::Ident(_) => match self.next() {
+                Tok::Ident(i) => Ok(i),
+                _ => unreachable!(),
+            },
+            _ => Err(self.unexpected("identifier")),
+        }
+    }
+
+    // full_ident は `.` 区切りの名前（型名の場合は先頭の `.` を含む）を読み込みます
+    fn full_ident(&mut self, leading_dot: bool) -> Result<String> {
+        let mut name = String::new();
+        if leading_dot && self.eat_symbol('.') {
+            name.push('.');
+        }
+        name.push_str(&self.ident()?);
+        while self.eat_symbol('.') {
+            name.push('.');
+            name.push_str(&self.ident()?);
+        }
+        Ok(name)
+    }
+
+    fn string(&mut self) -> Result<Vec<u8>> {
+        let mut out = match self.next() {
+            Tok::Str(s) => s,
+            _ => {
+                self.pos -= 1;
+                return Err(self.unexpected("string"));
+            }
+        };
+        // 連続する文字列は連結する
+        while let Tok::Str(s) = self.peek() {
+            out.extend_from_slice(s);
+            self.next();
+        }
+        Ok(out)
+    }
+
+    fn utf8_string(&mut self) -> Result<String> {
+        let position = self.position();
+        String::from_utf8(self.string()?)
+            .map_err(|_| self.error_at(position, "string is not valid UTF-8"))
+    }
+
+    // integer は符号付きの整数を読み込みます
+    fn integer(&mut self) -> Result<i128> {
+        let negative = self.eat_symbol('-');
+        if !negative {
+            self.eat_symbol('+');
+        }
+        match self.peek() {
+            Tok::Int(i) => {
+                let v = *i as i128;
+                self.next();
+                Ok(if negative { -v } else { v })
+            }
+            _ => Err(self.unexpected("integer")),
+        }
+    }
+
+    fn file(&mut self) -> Result<ProtoFile> {
+        let mut file = ProtoFile {
+            name: self.file.to_string(),
+            syntax: Syntax::Proto2,
+            package: None,
+            imports: Vec::new(),
+            options: Vec::new(),
+            messages: Vec::new(),
+            enums: Vec::new(),
+            services: Vec::new(),
+            extends: Vec::new(),
+        };
+        // syntax は最初の文でのみ指定できる。省略した場合は proto2
+        if self.eat_keyword("syntax") {
+            self.expect_symbol('=')?;
+            let position = self.position();
+            file.syntax = match self.utf8_string()?.as_str() {
+                "proto2" => Syntax::Proto2,
+                "proto3" => Syntax::Proto3,
+                s => return Err(self.error_at(position, format!("unsupported syntax `{}`", s))),
+            };
+            self.expect_symbol(';')?;
+            self.syntax = file.syntax;
+        } else if self.is_keyword("edition") {
+            return Err(self.error_at(self.position(), "editions are not supported"));
+        }
+        loop {
+            let position = self.position();
+            match self.peek() {
+                Tok::Eof => break,
+                Tok::Symbol(';') => {
+                    self.next();
+                }
+                Tok::Ident(i) => match i.as_str() {
+                    "package" => {
+                        self.next();
+                        if file.package.is_some() {
+                            return Err(self.error_at(position, "multiple package definitions"));
+                        }
+                        file.package = Some(self.full_ident(false)?);
+                        self.expect_symbol(';')?;
+                    }
+                    "import" => {
+                        self.next();
+                        let kind = if self.eat_keyword("public") {
+                            ImportKind::Public
+                        } else if self.eat_keyword("weak") {
+                            ImportKind::Weak
+                        } else {
+                            ImportKind::Default
+                        };
+                        let path = self.utf8_string()?;
+                        self.expect_symbol(';')?;
+                        file.imports.push(Import {
+                            path,
+                            kind,
+                            position,
+                        });
+                    }
+                    "option" => file.options.push(self.option_statement()?),
+                    "message" => file.messages.push(self.message(0)?),
+                    "enum" => file.enums.push(self.enum_def()?),
+                    "service" => file.services.push(self.service()?),
+                    "extend" => file.extends.push(self.extend(0)?),
+                    "syntax" => {
+                        return Err(self.error_at(position, "syntax must be the first statement"))
+                    }
+                    _ => return Err(self.unexpected("top-level definition")),
+                },
+                _ => return Err(self.unexpected("top-level definition")),
+            }
+        }
+        let scope = file.package.as_deref().unwrap_or(self.file);
+        self.check_type_names(scope, &file.messages, &file.enums)?;
+        Ok(file)
+    }
+
+    // check_type_names は 同じスコープで message と enum の名前が重複していないかを検査します
+    fn check_type_names(&self, scope: &str, messages: &[Message], enums: &[EnumDef]) -> Result<()> {
+        let mut names: HashMap<&str, ()> = HashMap::new();
+        let types = messages
+            .iter()
+            .map(|m| (m.name.as_str(), m.position))
+            .chain(enums.iter().map(|e| (e.name.as_str(), e.position)));
+        for (name, position) in types {
+            if names.insert(name, ()).is_some() {
+                return Err(self.error_at(
+                    position,
+                    format!("`{}` is already defined in `{}`", name, scope),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // option_name は option の名前（`a.b`, `(a.b).c` など）を読み込みます
+    fn option_name(&mut self) -> Result<String> {
+        let mut name = String::new();
+        loop {
+            if self.eat_symbol('(') {
+                name.push('(');
+                name.push_str(&self.full_ident(true)?);
+                self.expect_symbol(')')?;
+                name.push(')');
+            } else {
+                name.push_str(&self.ident()?);
+            }
+            if !self.eat_symbol('.') {
+                return Ok(name);
+            }
+            name.push('.');
+        }
+    }
+
+    // constant は option の値を読み込みます
+    fn constant(&mut self) -> Result<Constant> {
+        match self.peek().clone() {
+            Tok::Str(_) => self.string().map(Constant::String),
+            Tok::Symbol('{') => self.aggregate().map(Constant::Aggregate),
+            Tok::Ident(i) => {
+                self.next();
+                Ok(match i.as_str() {
+                    "true" => Constant::Bool(true),
+                    "false" => Constant::Bool(false),
+                    "inf" => Constant::Float(f64::INFINITY),
+                    "nan" => Constant::Float(f64::NAN),
+                    _ => {
+                        let mut name = i;
+                        while self.eat_symbol('.') {
+                            name.push('.');
+                            name.push_str(&self.ident()?);
+                        }
+                        Constant::Identifier(name)
+                    }
+                })
+            }
+            Tok::Symbol('-' | '+') | Tok::Int(_) | Tok::Float(_) => {
+                let negative = self.eat_symbol('-');
+                if !negative {
+                    self.eat_symbol('+');
+                }
+                let sign = if negative { -1.0 } else { 1.0 };
+                match self.next() {
+                    Tok::Int(i) if negative => Ok(Constant::Integer(-(i as i128))),
+                    Tok::Int(i) => Ok(Constant::Integer(i as i128)),
+                    Tok::Float(f) => Ok(Constant::Float(sign * f)),
+                    Tok::Ident(i) if i == "inf" => Ok(Constant::Float(sign * f64::INFINITY)),
+                    Tok::Ident(i) if i == "nan" => Ok(Constant::Float(f64::NAN)),
+                    _ => {
+                        self.pos -= 1;
+                        Err(self.unexpected("number"))
+                    }
+                }
+            }
+            _ => Err(self.unexpected("constant")),
+        }
+    }
+
+    // aggregate は `{ ... }` の値を、トークンを空白で区切った text format の文字列として読み込みます
+    fn aggregate(&mut self) -> Result<String> {
+        let start = self.position();
+        self.expect_symbol('{')?;
+        let mut depth = 1;
+        let mut parts = Vec::new();
+        loop {
+            let tok = self.next();
+            match &tok {
+                Tok::Eof => return Err(self.error_at(start, "unterminated aggregate value")),
+                Tok::Symbol('{') => depth += 1,
+                Tok::Symbol('}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(parts.join(" "));
+                    }
+                }
+                _ => {}
+            }
+            parts.push(match tok {
+                Tok::Ident(i) => i,
+                Tok::Int(i) => i.to_string(),
+                Tok::Float(f) => format!("{:?}", f),
+                Tok::Str(s) => {
+                    let mut w = crate::text::TextWriter::compact();
+                    w.string(&s, true);
+                    w.finish()
+                }
+                Tok::Symbol(c) => c.to_string(),
+                Tok::Eof => unreachable!(),
+            });
+        }
+    }
+
+    // option_statement は `option name = value;` を読み込みます
+    fn option_statement(&mut self) -> Result<OptionDef> {
+        self.next();
+        let name = self.option_name()?;
+        self.expect_symbol('=')?;
+        let value = self.constant()?;
+        self.expect_symbol(';')?;
+        Ok(OptionDef { name, value })
+    }
+
+    // field_options は `[name = value, ...]` を読み込みます（ない場合は空）
+    fn field_options(&mut self) -> Result<Vec<OptionDef>> {
+        let mut options = Vec::new();
+        if !self.eat_symbol('[') {
+            return Ok(options);
+        }
+        loop {
+            let position = self.position();
+            let name = self.option_name()?;
+            if name == "default" && self.syntax == Syntax::Proto3 {
+                return Err(self.error_at(position, "default values are not allowed in proto3"));
+            }
+            self.expect_symbol('=')?;
+            options.push(OptionDef {
+                name,
+                value: self.constant()?,
+            });
+            if self.eat_symbol(']') {
+                return Ok(options);
+            }
+            self.expect_symbol(',')?;
+        }
+    }
+
+    fn check_depth(&self, depth: usize) -> Result<()> {
+        if depth > 100 {
+            return Err(self.error_at(self.position(), "nested too deeply"));
+        }
+        Ok(())
+    }
+
+    fn message(&mut self, depth: usize) -> Result<Message> {
+        self.check_depth(depth)?;
+        self.next();
+        let position = self.position();
+        let name = self.ident()?;
+        let mut m = Message {
+            name,
+            position,
+            fields: Vec::new(),
+            oneofs: Vec::new(),
+            messages: Vec::new(),
+            enums: Vec::new(),
+            extends: Vec::new(),
+            reserved: Vec::new(),
+            extensions: Vec::new(),
+            options: Vec::new(),
+        };
+        self.expect_symbol('{')?;
+        while !self.eat_symbol('}') {
+            match self.peek() {
+                Tok::Symbol(';') => {
+                    self.next();
+                }
+                Tok::Eof => return Err(self.unexpected("`}`")),
+                Tok::Ident(i) => match i.as_str() {
+                    "message" if matches!(self.peek_at(1), Tok::Ident(_)) => {
+                        m.messages.push(self.message(depth + 1)?)
+                    }
+                    "enum" if matches!(self.peek_at(1), Tok::Ident(_)) => {
+                        m.enums.push(self.enum_def()?)
+                    }
+                    "extend" if matches!(self.peek_at(1), Tok::Ident(_) | Tok::Symbol('.')) => {
+                        m.extends.push(self.extend(depth + 1)?)
+                    }
+                    "option" if matches!(self.peek_at(1), Tok::Ident(_) | Tok::Symbol('(')) => {
+                        m.options.push(self.option_statement()?)
+                    }
+                    "oneof" if matches!(self.peek_at(2), Tok::Symbol('{')) => {
+                        m.oneofs.push(self.oneof()?)
+                    }
+                    "reserved" if !matches!(self.peek_at(1), Tok::Ident(_)) => {
+                        m.reserved.push(self.reserved(false)?)
+                    }
+                    "extensions" if matches!(self.peek_at(1), Tok::Int(_)) => {
+                        self.next();
+                        m.extensions.extend(self.ranges()?);
+                        self.field_options()?;
+                        self.expect_symbol(';')?;
+                    }
+                    _ => m.fields.push(self.field(false)?),
+                },
+                Tok::Symbol('.') => m.fields.push(self.field(false)?),
+                _ => return Err(self.unexpected("field")),
+            }
+        }
+        self.check_message(&m)?;
+        Ok(m)
+    }
+
+    // check_message は field number と名前の重複、reserved との衝突、入れ子の型の名前の重複を検査します
+    fn check_message(&self, m: &Message) -> Result<()> {
+        self.check_type_names(&m.name, &m.messages, &m.enums)?;
+        let mut numbers: HashMap<u32, &str> = HashMap::new();
+        let mut names: HashMap<&str, ()> = HashMap::new();
+        for f in m.all_fields() {
+            if let Some(other) = numbers.insert(f.number, &f.name) {
+                return Err(self.error_at(
+                    f.position,
+                    format!(
+                        "field number {} of `{}` is already used by `{}` in `{}`",
+                        f.number, f.name, other, m.name
+                    ),
+                ));
+            }
+            if names.insert(&f.name, ()).is_some() {
+                return Err(self.error_at(
+                    f.position,
+                    format!("field `{}` is already defined in `{}`", f.name, m.name),
+                ));
+            }
+            for r in &m.reserved {
+                let reserved = match r {
+                    Reserved::Ranges(ranges) => ranges
+                        .iter()
+                        .any(|r| (r.start..=r.end).contains(&(f.number as i64))),
+                    Reserved::Names(names) => names.contains(&f.name),
+                };
+                if reserved {
+                    return Err(self.error_at(
+                        f.position,
+                        format!("field `{}` uses a reserved name or number", f.name),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // field は フィールド（map を含む）を読み込みます
+    // in_oneof の場合、label は指定できません
+    fn field(&mut self, in_oneof: bool) -> Result<Field> {
+        let position = self.position();
+        let label = match self.peek() {
+            Tok::Ident(i) if i == "optional" => Label::Optional,
+            Tok::Ident(i) if i == "required" => Label::Required,
+            Tok::Ident(i) if i == "repeated" => Label::Repeated,
+            _ => Label::None,
+        };
+        if label != Label::None {
+            if in_oneof {
+                return Err(self.error_at(position, "fields in oneof must not have labels"));
+            }
+            self.next();
+        }
+        if label == Label::Required && self.syntax == Syntax::Proto3 {
+            return Err(self.error_at(position, "required fields are not allowed in proto3"));
+        }
+        let ty_position = self.position();
+        let ty = if self.is_keyword("map") && *self.peek_at(1) == Tok::Symbol('<') {
+            self.next();
+            self.next();
+            let key_position = self.position();
+            let key = match self.field_type()? {
+                FieldType::Scalar(s) if s.is_map_key() => s,
+                _ => return Err(self.error_at(key_position, "invalid map key type")),
+            };
+            self.expect_symbol(',')?;
+            let value_position = self.position();
+            let value = self.field_type()?;
+            if matches!(value, FieldType::Map(..)) {
+                return Err(self.error_at(value_position, "map value must not be a map"));
+            }
+            self.expect_symbol('>')?;
+            if label != Label::None || in_oneof {
+                return Err(self.error_at(position, "map fields must not have labels"));
+            }
+            FieldType::Map(key, Box::new(value))
+        } else {
+            if self.is_keyword("group") {
+                return Err(self.error_at(ty_position, "groups are not supported"));
+            }
+            self.field_type()?
+        };
+        let is_map = matches!(ty, FieldType::Map(..));
+        if label == Label::None && !in_oneof && !is_map && self.syntax == Syntax::Proto2 {
+            return Err(self.error_at(position, "fields in proto2 must have a label"));
+        }
+        let name_position = self.position();
+        let name = self.ident()?;
+        self.expect_symbol('=')?;
+        let number_position = self.position();
+        let number = self.integer()?;
+        let number = match u32::try_from(number) {
+            Ok(n)
+                if (1..=MAX_FIELD_NUMBER).contains(&n) && !RESERVED_FIELD_NUMBERS.contains(&n) =>
+            {
+                n
+            }
+            _ => {
+                return Err(self.error_at(
+                    number_position,
+                    format!("invalid field number {} for `{}`", number, name),
+                ))
+            }
+        };
+        let options = self.field_options()?;
+        self.expect_symbol(';')?;
+        Ok(Field {
+            name,
+            number,
+            label,
+            ty,
+            options,
+            position: name_position,
+        })
+    }
+
+    fn field_type(&mut self) -> Result<FieldType> {
+        let name = self.full_ident(true)?;
+        Ok(match ScalarType::from_name(&name) {
+            Some(s) => FieldType::Scalar(s),
+            None => FieldType::Named(name),
+        })
+    }
+
+    fn oneof(&mut self) -> Result<Oneof> {
+        self.next();
+        let name = self.ident()?;
+        let mut oneof = Oneof {
+            name,
+            fields: Vec::new(),
+            options: Vec::new(),
+        };
+        self.expect_symbol('{')?;
+        while !self.eat_symbol('}') {
+            match self.peek() {
+                Tok::Symbol(';') => {
+                    self.next();
+                }
+                Tok::Ident(i) if i == "option" => oneof.options.push(self.option_statement()?),
+                _ => oneof.fields.push(self.field(true)?),
+            }
+        }
+        Ok(oneof)
+    }
+
+    // ranges は `1, 5 to 10, 20 to max` を読み込みます
+    fn ranges(&mut self) -> Result<Vec<FieldRange>> {
+        let mut ranges = Vec::new();
+        loop {
+            let position = self.position();
+            let start = self.integer()?;
+            let end = if self.eat_keyword("to") {
+                if self.eat_keyword("max") {
+                    MAX_FIELD_NUMBER as i128
+                } else {
+                    self.integer()?
+                }
+            } else {
+                start
+            };
+            let (start, end) = match (i64::try_from(start), i64::try_from(end)) {
+                (Ok(s), Ok(e)) if s <= e => (s, e),
+                _ => return Err(self.error_at(position, "invalid range")),
+            };
+            ranges.push(FieldRange { start, end });
+            if !self.eat_symbol(',') {
+                return Ok(ranges);
+            }
+        }
+    }
+
+    // reserved は `reserved 1, 2 to 5;` または `reserved "a", "b";` を読み込みます
+    // enum の場合は max が i32 の最大値になります
+    fn reserved(&mut self, in_enum: bool) -> Result<Reserved> {
+        self.next();
+        let reserved = if matches!(self.peek(), Tok::Str(_)) {
+            let mut names = vec![self.utf8_string()?];
+            while self.eat_symbol(',') {
+                names.push(self.utf8_string()?);
+            }
+            Reserved::Names(names)
+        } else {
+            let mut ranges = self.ranges()?;
+            if in_enum {
+                for r in ranges
+                    .iter_mut()
+                    .filter(|r| r.end == MAX_FIELD_NUMBER as i64)
+                {
+                    r.end = i32::MAX as i64;
+                }
+            }
+            Reserved::Ranges(ranges)
+        };
+        self.expect_symbol(';')?;
+        Ok(reserved)
+    }
+
+    fn enum_def(&mut self) -> Result<EnumDef> {
+        self.next();
+        let position = self.position();
+        let name = self.ident()?;
+        let mut e = EnumDef {
+            name,
+            position,
+            values: Vec::new(),
+            reserved: Vec::new(),
+            options: Vec::new(),
+        };
+        self.expect_symbol('{')?;
+        while !self.eat_symbol('}') {
+            match self.peek() {
+                Tok::Symbol(';') => {
+                    self.next();
+                }
+                Tok::Ident(i) if i == "option" && *self.peek_at(1) != Tok::Symbol('=') => {
+                    e.options.push(self.option_statement()?)
+                }
+                Tok::Ident(i) if i == "reserved" && *self.peek_at(1) != Tok::Symbol('=') => {
+                    e.reserved.push(self.reserved(true)?)
+                }
+                Tok::Ident(_) => {
+                    let position = self.position();
+                    let name = self.ident()?;
+                    self.expect_symbol('=')?;
+                    let number_position = self.position();
+                    let number = i32::try_from(self.integer()?).map_err(|_| {
+                        self.error_at(
+                            number_position,
+                            format!("invalid enum value for `{}`", name),
+                        )
+                    })?;
+                    let options = self.field_options()?;
+                    self.expect_symbol(';')?;
+                    e.values.push(EnumValue {
+                        name,
+                        number,
+                        options,
+                        position,
+                    });
+                }
+                _ => return Err(self.unexpected("enum value")),
+            }
+        }
+        match e.values.first() {
+            None => {
+                return Err(self.error_at(e.position, format!("enum `{}` has no values", e.name)))
+            }
+            Some(v) if self.syntax == Syntax::Proto3 && v.number != 0 => {
+                return Err(self.error_at(
+                    v.position,
+                    format!(
+                        "the first value of enum `{}` must be zero in proto3",
+                        e.name
+                    ),
+                ))
+            }
+            _ => {}
+        }
+        // 同じ値は allow_alias = true の場合のみ使える
+        let allow_alias = e
+            .options
+            .iter()
+            .rev()
+            .find(|o| o.name == "allow_alias")
+            .is_some_and(|o| o.value == Constant::Bool(true));
+        if !allow_alias {
+            let mut numbers: HashMap<i32, &str> = HashMap::new();
+            for v in &e.values {
+                if let Some(other) = numbers.insert(v.number, &v.name) {
+                    return Err(self.error_at(
+                        v.position,
+                        format!(
+                            "value {} of `{}` is already used by `{}` in `{}`; set allow_alias = true to allow aliases",
+                            v.number, v.name, other, e.name
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(e)
+    }
+
+    fn service(&mut self) -> Result<Service> {
+        self.next();
+        let name = self.ident()?;
+        let mut s = Service {
+            name,
+            methods: Vec::new(),
+            options: Vec::new(),
+        };
+        self.expect_symbol('{')?;
+        while !self.eat_symbol('}') {
+            match self.peek() {
+                Tok::Symbol(';') => {
+                    self.next();
+                }
+                Tok::Ident(i) if i == "option" => s.options.push(self.option_statement()?),
+                Tok::Ident(i) if i == "rpc" => {
+                    self.next();
+                    let name = self.ident()?;
+                    self.expect_symbol('(')?;
+                    let client_streaming = self.is_keyword("stream")
+                        && !matches!(self.peek_at(1), Tok::Symbol(')' | '.'));
+                    if client_streaming {
+                        self.next();
+                    }
+                    let input = self.full_ident(true)?;
+                    self.expect_symbol(')')?;
+                    if !self.eat_keyword("returns") {
+                        return Err(self.unexpected("`returns`"));
+                    }
+                    self.expect_symbol('(')?;
+                    let server_streaming = self.is_keyword("stream")
+                        && !matches!(self.peek_at(1), Tok::Symbol(')' | '.'));
+                    if server_streaming {
+                        self.next();
+                    }
+                    let output = self.full_ident(true)?;
+                    self.expect_symbol(')')?;
+                    let mut options = Vec::new();
+                    if self.eat_symbol('{') {
+                        while !self.eat_symbol('}') {
+                            match self.peek() {
+                                Tok::Symbol(';') => {
+                                    self.next();
+                                }
+                                Tok::Ident(i) if i == "option" => {
+                                    options.push(self.option_statement()?)
+                                }
+                                _ => return Err(self.unexpected("option")),
+                            }
+                        }
+                    } else {
+                        self.expect_symbol(';')?;
+                    }
+                    s.methods.push(Method {
+                        name,
+                        input,
+                        output,
+                        client_streaming,
+                        server_streaming,
+                        options,
+                    });
+                }
+                _ => return Err(self.unexpected("rpc")),
+            }
+        }
+        Ok(s)
+    }
+
+    fn extend(&mut self, depth: usize) -> Result<Extend> {
+        self.check_depth(depth)?;
+        self.next();
+        let extendee = self.full_ident(true)?;
+        let mut fields = Vec::new();
+        self.expect_symbol('{')?;
+        while !self.eat_symbol('}') {
+            if self.eat_symbol(';') {
+                continue;
+            }
+            fields.push(self.field(false)?);
+        }
+        Ok(Extend { extendee, fields })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proto3() {
+        let source = r#"
+// user service
+syntax = "proto3";
+
+package example.v1;
+
+import "google/protobuf/timestamp.proto";
+import public "other.proto";
+
+option java_package = "com.example.v1";
+option (my.file_opt).level = -3;
+
+/* a user */
+message User {
+  uint64 id = 1;
+  string name = 2 [json_name = "displayName", deprecated = true];
+  repeated string tags = 3;
+  optional int32 age = 4;
+  map<string, Address> addresses = 5;
+  Status status = 6;
+  .google.protobuf.Timestamp created_at = 7;
+  oneof contact {
+    string email = 8;
+    string phone = 9;
+  }
+  reserved 10, 12 to 15, 100 to max;
+  reserved "old";
+
+  message Address {
+    string city = 1;
+  }
+  enum Status {
+    option allow_alias = true;
+    STATUS_UNSPECIFIED = 0;
+    ACTIVE = 1;
+    ENABLED = 1 [(my.value_opt) = { a: 1 b: "x" }];
+    reserved 5 to max;
+  }
+}
+
+service Users {
+  rpc Get(GetRequest) returns (User);
+  rpc Watch(stream GetRequest) returns (stream User) {
+    option deprecated = true;
+  }
+}
+"#;
+        let f = parse("user.proto", source).unwrap();
+        assert_eq!(f.syntax, Syntax::Proto3);
+        assert_eq!(f.package.as_deref(), Some("example.v1"));
+        assert_eq!(
+            f.imports,
+            vec![
+                Import {
+                    path: "google/protobuf/timestamp.proto".to_string(),
+                    kind: ImportKind::Default,
+                    position: Position { line: 7, column: 1 },
+                },
+                Import {
+                    path: "other.proto".to_string(),
+                    kind: ImportKind::Public,
+                    position: Position { line: 8, column: 1 },
+                },
+            ]
+        );
+        assert_eq!(f.options[0].value.as_str(), Some("com.example.v1"));
+        assert_eq!(f.options[1].name, "(my.file_opt).level");
+        assert_eq!(f.options[1].value, Constant::Integer(-3));
+
+        let user = &f.messages[0];
+        assert_eq!(user.name, "User");
+        assert_eq!(
+            user.position,
+            Position {
+                line: 14,
+                column: 9
+            }
+        );
+        let fields: Vec<(&str, u32, Label)> = user
+            .all_fields()
+            .map(|f| (f.name.as_str(), f.number, f.label))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("id", 1, Label::None),
+                ("name", 2, Label::None),
+                ("tags", 3, Label::Repeated),
+                ("age", 4, Label::Optional),
+                ("addresses", 5, Label::None),
+                ("status", 6, Label::None),
+                ("created_at", 7, Label::None),
+                ("email", 8, Label::None),
+                ("phone", 9, Label::None),
+            ]
+        );
+        assert_eq!(
+            user.fields[4].ty,
+            FieldType::Map(
+                ScalarType::String,
+                Box::new(FieldType::Named("Address".to_string()))
+            )
+        );
+        assert_eq!(
+            user.fields[6].ty,
+            FieldType::Named(".google.protobuf.Timestamp".to_string())
+        );
+        assert_eq!(
+            user.fields[1].option("json_name").and_then(|c| c.as_str()),
+            Some("displayName")
+        );
+        assert_eq!(user.fields[1].json_name(), "displayName");
+        assert_eq!(user.fields[6].json_name(), "createdAt");
+        assert_eq!(user.oneofs[0].name, "contact");
+        assert_eq!(
+            user.reserved[0],
+            Reserved::Ranges(vec![
+                FieldRange { start: 10, end: 10 },
+                FieldRange { start: 12, end: 15 },
+                FieldRange {
+                    start: 100,
+                    end: MAX_FIELD_NUMBER as i64
+                },
+            ])
+        );
+        assert_eq!(user.reserved[1], Reserved::Names(vec!["old".to_string()]));
+        assert_eq!(user.messages[0].name, "Address");
+
+        let status = &user.enums[0];
+        assert_eq!(status.values.len(), 3);
+        assert_eq!(
+            status.values[2].options[0].value,
+            Constant::Aggregate("a : 1 b : \"x\"".to_string())
+        );
+        assert_eq!(
+            status.reserved[0],
+            Reserved::Ranges(vec![FieldRange {
+                start: 5,
+                end: i32::MAX as i64
+            }])
+        );
+
+        let methods = &f.services[0].methods;
+        assert_eq!(methods[0].input, "GetRequest");
+        assert!(!methods[0].client_streaming);
+        assert!(methods[1].client_streaming && methods[1].server_streaming);
+    }
+
+    #[test]
+    fn test_parse_proto2() {
+        let source = r#"
+package legacy;
+message Item {
+  required string name = 1;
+  optional sint64 delta = 2 [default = -5];
+  repeated fixed32 ids = 3 [packed = true];
+  optional bytes raw = 4 [default = "\001\x02é"];
+  extensions 100 to 199;
+}
+extend Item {
+  optional int32 extra = 100;
+}
+"#;
+        let f = parse("legacy.proto", source).unwrap();
+        assert_eq!(f.syntax, Syntax::Proto2);
+        let item = &f.messages[0];
+        assert_eq!(item.fields[0].label, Label::Required);
+        assert_eq!(
+            item.fields[1].option("default"),
+            Some(&Constant::Integer(-5))
+        );
+        assert_eq!(item.fields[2].option("packed"), Some(&Constant::Bool(true)));
+        assert_eq!(
+            item.fields[3].option("default"),
+            Some(&Constant::String(vec![1, 2, 0xc3, 0xa9]))
+        );
+        assert_eq!(
+            item.extensions,
+            vec![FieldRange {
+                start: 100,
+                end: 199
+            }]
+        );
+        assert_eq!(f.extends[0].extendee, "Item");
+        assert_eq!(f.extends[0].fields[0].name, "extra");
+    }
+
+    #[test]
+    fn test_parse_error() {
+        for (source, line, column, reason) in [
+            (
+                "syntax = \"proto3\";\nmessage A {\n  required int32 a = 1;\n}",
+                3,
+                3,
+                "required fields are not allowed in proto3",
+            ),
+            (
+                "message A {\n  int32 a = 1;\n}",
+                2,
+                3,
+                "fields in proto2 must have a label",
+            ),
+            (
+                "syntax = \"proto3\";\nmessage A {\n  int32 a = 1;\n  int32 b = 1;\n}",
+                4,
+                9,
+                "field number 1 of `b` is already used by `a` in `A`",
+            ),
+            (
+                "syntax = \"proto3\";\nmessage A {\n  int32 a = 0;\n}",
+                3,
+                13,
+                "invalid field number 0 for `a`",
+            ),
+            (
+                "syntax = \"proto3\";\nmessage A {\n  int32 a = 19000;\n}",
+                3,
+                13,
+                "invalid field number 19000 for `a`",
+            ),
+            (
+                "syntax = \"proto3\";\nmessage A {\n  reserved 2;\n  int32 a = 2;\n}",
+                4,
+                9,
+                "field `a` uses a reserved name or number",
+            ),
+            (
+                "syntax = \"proto3\";\nmessage A {\n  map<double, int32> m = 1;\n}",
+                3,
+                7,
+                "invalid map key type",
+            ),
+            (
+                "syntax = \"proto3\";\nenum E {\n  A = 1;\n}",
+                3,
+                3,
+                "the first value of enum `E` must be zero in proto3",
+            ),
+            (
+                "syntax = \"proto3\";\nmessage A {\n  int32 a = 1\n}",
+                4,
+                1,
+                "expected `;`, got `}`",
+            ),
+            (
+                "syntax = \"proto3\";\nmessage A {\n  string s = 1 [default = \"x];\n}",
+                3,
+                27,
+                "unterminated string",
+            ),
+            (
+                "syntax = \"proto3\";\nmessage A {\n  string s = 1 [default = \"x\"];\n}",
+                3,
+                17,
+                "default values are not allowed in proto3",
+            ),
+            (
+                "syntax = \"proto3\";\npackage p;\nmessage A {}\nenum A {\n  X = 0;\n}",
+                4,
+                6,
+                "`A` is already defined in `p`",
+            ),
+            (
+                "syntax = \"proto3\";\nmessage A {\n  message B {}\n  message B {}\n}",
+                4,
+                11,
+                "`B` is already defined in `A`",
+            ),
+            (
+                "syntax = \"proto3\";\nenum E {\n  A = 0;\n  B = 0;\n}",
+                4,
+                3,
+                "value 0 of `B` is already used by `A` in `E`; set allow_alias = true to allow aliases",
+            ),
+            ("syntax = \"proto4\";", 1, 10, "unsupported syntax `proto4`"),
+            (
+                "message A {\n  optional group G = 1 {}\n}",
+                2,
+                12,
+                "groups are not supported",
+            ),
+            ("/* open", 1, 1, "unterminated comment"),
+        ] {
+            match parse("a.proto", source) {
+                Err(Error::InvalidSchema {
+                    file,
+                    line: l,
+                    column: c,
+                    reason: r,
+                }) => {
+                    assert_eq!(file, "a.proto");
+                    assert_eq!((l, c, r.as_str()), (line, column, reason), "{}", source);
+                }
+                got => panic!("{}: unexpected result {:?}", source, got),
+            }
+        }
+        // allow_alias = true の場合は同じ値を使える
+        let f = parse(
+            "a.proto",
+            "syntax = \"proto3\";\nenum E {\n  option allow_alias = true;\n  A = 0;\n  B = 0;\n}",
+        )
+        .unwrap();
+        assert_eq!(f.enums[0].values.len(), 2);
+        // proto2 では default を指定できる
+        assert!(parse(
+            "a.proto",
+            "message A {\n  optional int32 a = 1 [default = 5];\n}"
+        )
+        .is_ok());
+
+        let e = parse("dir/a.proto", "message {").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "dir/a.proto:1:9: expected identifier, got `{`"
+        );
+    }
+}
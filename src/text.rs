@@ -0,0 +1,893 @@
+use crate::parser::VariantEnum;
+use crate::wire::Proto;
+use crate::{Error, Result};
+use std::borrow::Cow;
+use std::fmt;
+
+// 以下は protobuf の text format を扱う処理です
+// derive で生成される write_text/from_text_fields から使われます
+
+// MAX_DEPTH は text format を読み込む際に許可する message/list の入れ子の深さです
+const MAX_DEPTH: usize = 100;
+
+// Position は入力中の位置（1 始まりの行と列）です
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+// TextField は text format の 1 つのフィールド（`name: value` または `name { ... }`）です
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextField {
+    pub name: String,
+    // position はフィールド名の位置、value_position は値の位置です
+    pub position: Position,
+    pub value_position: Position,
+    pub value: TextValue,
+}
+
+// TextValue は text format の値です
+// Identifier は enum の名前や true/false/inf/nan、Number は符号を含む数値の表記のままの値です
+// String は連続する文字列を連結し、エスケープを解除したバイト列です
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextValue {
+    Identifier(String),
+    Number(String),
+    String(Vec<u8>),
+    Message(Vec<TextField>),
+    List(Vec<(Position, TextValue)>),
+}
+
+impl TextValue {
+    // kind はエラーメッセージで使う値の種類を返します
+    fn kind(&self) -> &'static str {
+        match self {
+            TextValue::Identifier(_) => "identifier",
+            TextValue::Number(_) => "number",
+            TextValue::String(_) => "string",
+            TextValue::Message(_) => "message",
+            TextValue::List(_) => "list",
+        }
+    }
+}
+
+// parse は input を text format の message として読み込みます
+pub fn parse(input: &str) -> Result<Vec<TextField>> {
+    let mut p = TextParser {
+        chars: input.chars().collect(),
+        pos: 0,
+        line: 1,
+        column: 1,
+    };
+    let fields = p.message(None, 0)?;
+    Ok(fields)
+}
+
+struct TextParser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl TextParser {
+    fn position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn error_at(position: Position, reason: impl Into<String>) -> Error {
+        Error::InvalidText {
+            line: position.line,
+            column: position.column,
+            reason: reason.into(),
+        }
+    }
+
+    fn error(&self, reason: impl Into<String>) -> Error {
+        Self::error_at(self.position(), reason)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    // skip は空白と `#` から行末までのコメントを読み飛ばします
+    fn skip(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while !matches!(self.peek(), None | Some('\n')) {
+                    self.bump();
+                }
+            } else if c.is_whitespace() {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    // eat は空白の後に c が続く場合に読み進めます
+    fn eat(&mut self, c: char) -> bool {
+        self.skip();
+        if self.peek() == Some(c) {
+            self.bump();
+            return true;
+        }
+        false
+    }
+
+    fn identifier(&mut self) -> String {
+        let mut s = String::new();
+        while let Some(c) = self
+            .peek()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+        {
+            s.push(c);
+            self.bump();
+        }
+        s
+    }
+
+    // message は close（トップレベルの場合は入力の終わり）までのフィールドを読み込みます
+    fn message(&mut self, close: Option<char>, depth: usize) -> Result<Vec<TextField>> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        let mut fields = Vec::new();
+        loop {
+            self.skip();
+            let position = self.position();
+            match (self.peek(), close) {
+                (None, None) => return Ok(fields),
+                (None, Some(c)) => return Err(self.error(format!("expected `{}`", c))),
+                (Some(c), Some(close)) if c == close => {
+                    self.bump();
+                    return Ok(fields);
+                }
+                (Some(c), _) if c.is_ascii_alphabetic() || c == '_' => {}
+                (Some('['), _) => {
+                    return Err(self.error("extension and Any field names are not supported"))
+                }
+                (Some(c), _) => return Err(self.error(format!("unexpected `{}`", c))),
+            }
+            let name = self.identifier();
+            let colon = self.eat(':');
+            self.skip();
+            let value_position = self.position();
+            let value = match self.peek() {
+                Some('{' | '<' | '[') => self.value(depth + 1)?,
+                _ if colon => self.value(depth + 1)?,
+                _ => return Err(self.error(format!("expected `:` after `{}`", name))),
+            };
+            fields.push(TextField {
+                name,
+                position,
+                value_position,
+                value,
+            });
+            // フィールドの区切りは省略できる
+            if !self.eat(',') {
+                self.eat(';');
+            }
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<TextValue> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.skip();
+        match self.peek() {
+            None => Err(self.error("unexpected end of input")),
+            Some('{') => {
+                self.bump();
+                self.message(Some('}'), depth).map(TextValue::Message)
+            }
+            Some('<') => {
+                self.bump();
+                self.message(Some('>'), depth).map(TextValue::Message)
+            }
+            Some('[') => {
+                self.bump();
+                let mut items = Vec::new();
+                if self.eat(']') {
+                    return Ok(TextValue::List(items));
+                }
+                loop {
+                    self.skip();
+                    let position = self.position();
+                    items.push((position, self.value(depth + 1)?));
+                    if self.eat(']') {
+                        return Ok(TextValue::List(items));
+                    }
+                    if !self.eat(',') {
+                        return Err(self.error("expected `,` or `]`"));
+                    }
+                }
+            }
+            Some('"' | '\'') => {
+                let mut bytes = Vec::new();
+                // 連続する文字列は連結する
+                while let Some(quote @ ('"' | '\'')) = self.peek() {
+                    self.string(quote, &mut bytes)?;
+                    self.skip();
+                }
+                Ok(TextValue::String(bytes))
+            }
+            // `-` の後は数値か識別子を 1 つだけ読み込む
+            Some('-') => {
+                self.bump();
+                self.skip();
+                match self.peek() {
+                    Some(c) if c.is_ascii_digit() || c == '.' => {
+                        Ok(TextValue::Number(format!("-{}", self.number())))
+                    }
+                    Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                        Ok(TextValue::Identifier(format!("-{}", self.identifier())))
+                    }
+                    Some(c) => Err(self.error(format!("unexpected `{}` after `-`", c))),
+                    None => Err(self.error("unexpected end of input after `-`")),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => Ok(TextValue::Number(self.number())),
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                Ok(TextValue::Identifier(self.identifier()))
+            }
+            Some(c) => Err(self.error(format!("unexpected `{}`", c))),
+        }
+    }
+
+    // number は数値の表記を読み込みます
+    // 値としての検査は、フィールドの型に変換する際に行います
+    fn number(&mut self) -> String {
+        let mut s = String::new();
+        let hex = self.chars[self.pos..].starts_with(&['0', 'x'])
+            || self.chars[self.pos..].starts_with(&['0', 'X']);
+        while let Some(c) = self.peek() {
+            let exponent_sign = !hex && (c == '+' || c == '-') && s.ends_with(['e', 'E']);
+            if !(c.is_ascii_alphanumeric() || c == '.' || exponent_sign) {
+                break;
+            }
+            s.push(c);
+            self.bump();
+        }
+        s
+    }
+
+    // string は quote で囲まれた文字列を読み込み、エスケープを解除して out に追加します
+    fn string(&mut self, quote: char, out: &mut Vec<u8>) -> Result<()> {
+        self.bump();
+        loop {
+            let c = match self.bump() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some(c) => c,
+            };
+            if c == quote {
+                return Ok(());
+            }
+            if c != '\\' {
+                let mut buf = [0; 4];
+                out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                continue;
+            }
+            let position = self.position();
+            let invalid = || Self::error_at(position, "invalid escape");
+            match self.bump().ok_or_else(invalid)? {
+                'n' => out.push(b'\n'),
+                'r' => out.push(b'\r'),
+                't' => out.push(b'\t'),
+                'a' => out.push(0x07),
+                'b' => out.push(0x08),
+                'f' => out.push(0x0c),
+                'v' => out.push(0x0b),
+                c @ ('\\' | '\'' | '"' | '?') => out.push(c as u8),
+                c @ '0'..='7' => {
+                    let mut v = c.to_digit(8).unwrap_or_default();
+                    for _ in 0..2 {
+                        match self.peek().and_then(|c| c.to_digit(8)) {
+                            Some(d) => {
+                                v = v * 8 + d;
+                                self.bump();
+                            }
+                            None => break,
+                        }
+                    }
+                    out.push(u8::try_from(v).map_err(|_| invalid())?);
+                }
+                'x' => {
+                    let v = self.hex_digits(2).ok_or_else(invalid)?;
+                    out.push(v as u8);
+                }
+                u @ ('u' | 'U') => {
+                    let v = self
+                        .hex_digits(if u == 'u' { 4 } else { 8 })
+                        .ok_or_else(invalid)?;
+                    let c = char::from_u32(v).ok_or_else(invalid)?;
+                    let mut buf = [0; 4];
+                    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                _ => return Err(invalid()),
+            }
+        }
+    }
+
+    // hex_digits は最大 max 桁（`\x` 以外は max 桁ちょうど）の 16 進数を読み込みます
+    fn hex_digits(&mut self, max: usize) -> Option<u32> {
+        let mut v = 0;
+        let mut n = 0;
+        while n < max {
+            match self.peek().and_then(|c| c.to_digit(16)) {
+                Some(d) => {
+                    v = v * 16 + d;
+                    self.bump();
+                    n += 1;
+                }
+                None => break,
+            }
+        }
+        match n {
+            0 => None,
+            n if max > 2 && n < max => None,
+            _ => Some(v),
+        }
+    }
+}
+
+// TextMark は begin_message を呼び出した時点の TextWriter の状態です
+#[derive(Debug, Clone, Copy)]
+pub struct TextMark {
+    len: usize,
+    empty: bool,
+}
+
+// TextWriter は text format を書き込みます
+// compact は 1 行で、pretty はフィールドごとに改行してインデントを付けて書き込みます
+#[derive(Debug, Default)]
+pub struct TextWriter {
+    out: String,
+    pretty: bool,
+    depth: usize,
+    // empty は現在の message にまだフィールドを書き込んでいないかどうかです
+    empty: bool,
+}
+
+impl TextWriter {
+    pub fn compact() -> Self {
+        Self {
+            empty: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn pretty() -> Self {
+        Self {
+            pretty: true,
+            empty: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+
+    // separate はフィールドの前の区切り（compact は空白、pretty は改行とインデント）を書き込みます
+    fn separate(&mut self) {
+        if self.pretty {
+            if !(self.empty && self.depth == 0) {
+                self.out.push('\n');
+            }
+            for _ in 0..self.depth {
+                self.out.push_str("  ");
+            }
+        } else if !self.empty || self.depth > 0 {
+            self.out.push(' ');
+        }
+        self.empty = false;
+    }
+
+    // field は scalar のフィールド名を書き込みます。続けて値を書き込みます
+    pub fn field(&mut self, name: &str) {
+        self.separate();
+        self.out.push_str(name);
+        self.out.push_str(": ");
+    }
+
+    // begin_message は embedded message のフィールド名と `{` を書き込みます
+    pub fn begin_message(&mut self, name: &str) -> TextMark {
+        let mark = TextMark {
+            len: self.out.len(),
+            empty: self.empty,
+        };
+        self.separate();
+        self.out.push_str(name);
+        self.out.push_str(" {");
+        self.depth += 1;
+        self.empty = true;
+        mark
+    }
+
+    // end_message は `}` を書き込みます
+    // message にフィールドがなく keep_empty でない場合は、begin_message から書き込んだ内容を取り消します
+    pub fn end_message(&mut self, mark: TextMark, keep_empty: bool) {
+        self.depth -= 1;
+        if self.empty && !keep_empty {
+            self.out.truncate(mark.len);
+            self.empty = mark.empty;
+            return;
+        }
+        if self.empty {
+            self.out.push('}');
+        } else if self.pretty {
+            self.out.push('\n');
+            for _ in 0..self.depth {
+                self.out.push_str("  ");
+            }
+            self.out.push('}');
+        } else {
+            self.out.push_str(" }");
+        }
+        self.empty = false;
+    }
+
+    // list は values を `name: [v1, v2]` の形式で書き込みます
+    pub fn list<'a, T: TextScalar + 'a, I: IntoIterator<Item = &'a T>>(
+        &mut self,
+        name: &str,
+        values: I,
+    ) {
+        self.field(name);
+        self.out.push('[');
+        for (i, v) in values.into_iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            v.write_text(self);
+        }
+        self.out.push(']');
+    }
+
+    // raw は値をそのまま書き込みます
+    pub fn raw(&mut self, value: &str) {
+        self.out.push_str(value);
+    }
+
+    // string は value を `"` で囲み、必要な文字をエスケープして書き込みます
+    // utf8 の場合は ASCII 以外の文字をそのまま書き込み、それ以外は 8 進数でエスケープします
+    pub fn string(&mut self, value: &[u8], utf8: bool) {
+        self.out.push('"');
+        match std::str::from_utf8(value) {
+            Ok(s) if utf8 => {
+                for c in s.chars() {
+                    match c {
+                        c if c.is_ascii() => self.ascii(c as u8),
+                        c => self.out.push(c),
+                    }
+                }
+            }
+            _ => value.iter().for_each(|b| self.ascii(*b)),
+        }
+        self.out.push('"');
+    }
+
+    fn ascii(&mut self, b: u8) {
+        match b {
+            b'"' => self.out.push_str("\\\""),
+            b'\'' => self.out.push_str("\\'"),
+            b'\\' => self.out.push_str("\\\\"),
+            b'\n' => self.out.push_str("\\n"),
+            b'\r' => self.out.push_str("\\r"),
+            b'\t' => self.out.push_str("\\t"),
+            0x20..=0x7e => self.out.push(b as char),
+            b => self.out.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+}
+
+// TextDisplay は message を pretty な text format で表示します
+// encode できない値を含む場合は fmt::Error を返します
+pub struct TextDisplay<'a, T: ?Sized>(pub &'a T);
+
+impl<T: Proto + ?Sized> fmt::Display for TextDisplay<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut w = TextWriter::pretty();
+        self.0.write_text(&mut w).map_err(|_| fmt::Error)?;
+        f.write_str(&w.finish())
+    }
+}
+
+// at は フィールドの値の変換で発生したエラーに、値の位置を付けます
+// 既に位置を持つエラー（embedded message の中のエラー）はそのまま返します
+pub fn at<T>(position: Position, result: Result<T>) -> Result<T> {
+    result.map_err(|e| match e {
+        Error::InvalidText {
+            line: 0, reason, ..
+        } => TextParser::error_at(position, reason),
+        e @ Error::InvalidText { .. } => e,
+        e => TextParser::error_at(position, e.to_string()),
+    })
+}
+
+// unknown_field は message に定義されていないフィールドのエラーを返します
+pub fn unknown_field(field: &TextField) -> Error {
+    TextParser::error_at(field.position, format!("unknown field `{}`", field.name))
+}
+
+// message は embedded message の値を f で変換します
+pub fn message<T, F>(value: &TextValue, f: F) -> Result<T>
+where
+    F: Fn(&[TextField]) -> Result<T>,
+{
+    match value {
+        TextValue::Message(fields) => f(fields),
+        v => Err(unexpected("message", v)),
+    }
+}
+
+// repeated は repeated フィールドの値を f で変換します
+// list（`[v1, v2]`）の場合は各要素を、それ以外は 1 つの要素として変換します
+pub fn repeated<T, F>(value: &TextValue, f: F) -> Result<Vec<T>>
+where
+    F: Fn(&TextValue) -> Result<T>,
+{
+    match value {
+        TextValue::List(items) => items.iter().map(|(p, v)| at(*p, f(v))).collect(),
+        v => Ok(vec![f(v)?]),
+    }
+}
+
+fn unexpected(want: &str, got: &TextValue) -> Error {
+    Error::UnexpectedType {
+        want: want.to_string(),
+        got: got.kind().to_string(),
+    }
+}
+
+// invalid_value は値が不正な場合のエラーを返します
+// 位置（行と列）は 0 とし、at で値の位置に置き換えます
+fn invalid_value(reason: String) -> Error {
+    Error::InvalidText {
+        line: 0,
+        column: 0,
+        reason,
+    }
+}
+
+// TextScalar は embedded message 以外のフィールドの値と text format の値を相互に変換します
+pub trait TextScalar: Sized {
+    fn write_text(&self, w: &mut TextWriter);
+    fn from_text_value(value: &TextValue) -> Result<Self>;
+    // is_text_default は text format に書き込む際に省略する default 値かどうかを返します
+    fn is_text_default(&self) -> bool;
+}
+
+// parse_integer は 10 進数・16 進数（0x）・8 進数（0 始まり）の整数を読み込みます
+fn parse_integer<T: TryFrom<i128>>(value: &TextValue) -> Result<T> {
+    let text = match value {
+        TextValue::Number(n) => n,
+        v => return Err(unexpected("integer", v)),
+    };
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(d) => (true, d),
+        None => (false, text.as_str()),
+    };
+    let parsed = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        i128::from_str_radix(hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i128::from_str_radix(&digits[1..], 8)
+    } else {
+        digits.parse::<i128>()
+    };
+    let n = parsed.map_err(|_| invalid_value(format!("invalid integer `{}`", text)))?;
+    let n = if negative { -n } else { n };
+    T::try_from(n).map_err(|_| invalid_value(format!("integer out of range `{}`", text)))
+}
+
+macro_rules! text_integer {
+    ($($t:ty),*) => {$(
+        impl TextScalar for $t {
+            fn write_text(&self, w: &mut TextWriter) {
+                w.raw(&self.to_string());
+            }
+            fn from_text_value(value: &TextValue) -> Result<Self> {
+                parse_integer(value)
+            }
+            fn is_text_default(&self) -> bool {
+                *self == 0
+            }
+        }
+    )*};
+}
+
+text_integer!(i32, i64, u32, u64);
+
+// parse_float は 数値（末尾の f を含む）と inf, infinity, nan（大文字小文字は区別しない）を読み込みます
+fn parse_float(value: &TextValue) -> Result<f64> {
+    match value {
+        TextValue::Identifier(i) => {
+            let (negative, name) = match i.strip_prefix('-') {
+                Some(n) => (true, n),
+                None => (false, i.as_str()),
+            };
+            let v = match name.to_ascii_lowercase().as_str() {
+                "inf" | "infinity" => f64::INFINITY,
+                "nan" => f64::NAN,
+                _ => return Err(invalid_value(format!("invalid number `{}`", i))),
+            };
+            Ok(if negative { -v } else { v })
+        }
+        TextValue::Number(n) => {
+            let digits = n.trim_end_matches(['f', 'F']);
+            if digits.contains(['x', 'X']) {
+                return parse_integer::<i128>(value).map(|v| v as f64);
+            }
+            digits
+                .parse::<f64>()
+                .map_err(|_| invalid_value(format!("invalid number `{}`", n)))
+        }
+        v => Err(unexpected("number", v)),
+    }
+}
+
+impl TextScalar for f64 {
+    fn write_text(&self, w: &mut TextWriter) {
+        write_float(w, *self, format!("{:?}", self));
+    }
+    fn from_text_value(value: &TextValue) -> Result<Self> {
+        parse_float(value)
+    }
+    // -0.0 は default 値ではないため、ビットで比較する
+    fn is_text_default(&self) -> bool {
+        self.to_bits() == 0
+    }
+}
+
+impl TextScalar for f32 {
+    fn write_text(&self, w: &mut TextWriter) {
+        write_float(w, *self as f64, format!("{:?}", self));
+    }
+    fn from_text_value(value: &TextValue) -> Result<Self> {
+        parse_float(value).map(|v| v as f32)
+    }
+    // -0.0 は default 値ではないため、ビットで比較する
+    fn is_text_default(&self) -> bool {
+        self.to_bits() == 0
+    }
+}
+
+fn write_float(w: &mut TextWriter, v: f64, text: String) {
+    if v.is_nan() {
+        w.raw("nan");
+    } else if v.is_infinite() && v > 0.0 {
+        w.raw("inf");
+    } else if v.is_infinite() {
+        w.raw("-inf");
+    } else {
+        w.raw(&text);
+    }
+}
+
+impl TextScalar for bool {
+    fn write_text(&self, w: &mut TextWriter) {
+        w.raw(if *self { "true" } else { "false" });
+    }
+    fn from_text_value(value: &TextValue) -> Result<Self> {
+        match value {
+            TextValue::Identifier(i) if matches!(i.as_str(), "true" | "True" | "t") => Ok(true),
+            TextValue::Identifier(i) if matches!(i.as_str(), "false" | "False" | "f") => Ok(false),
+            TextValue::Number(n) if n == "1" => Ok(true),
+            TextValue::Number(n) if n == "0" => Ok(false),
+            TextValue::Identifier(i) | TextValue::Number(i) => {
+                Err(invalid_value(format!("invalid bool `{}`", i)))
+            }
+            v => Err(unexpected("bool", v)),
+        }
+    }
+    fn is_text_default(&self) -> bool {
+        !*self
+    }
+}
+
+fn text_bytes(value: &TextValue) -> Result<&[u8]> {
+    match value {
+        TextValue::String(s) => Ok(s),
+        v => Err(unexpected("string", v)),
+    }
+}
+
+impl TextScalar for String {
+    fn write_text(&self, w: &mut TextWriter) {
+        w.string(self.as_bytes(), true);
+    }
+    fn from_text_value(value: &TextValue) -> Result<Self> {
+        Ok(String::from_utf8(text_bytes(value)?.to_vec())?)
+    }
+    fn is_text_default(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+impl TextScalar for Cow<'_, str> {
+    fn write_text(&self, w: &mut TextWriter) {
+        w.string(self.as_bytes(), true);
+    }
+    fn from_text_value(value: &TextValue) -> Result<Self> {
+        String::from_text_value(value).map(Cow::Owned)
+    }
+    fn is_text_default(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+impl TextScalar for Vec<u8> {
+    fn write_text(&self, w: &mut TextWriter) {
+        w.string(self, false);
+    }
+    fn from_text_value(value: &TextValue) -> Result<Self> {
+        text_bytes(value).map(|b| b.to_vec())
+    }
+    fn is_text_default(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+impl TextScalar for Cow<'_, [u8]> {
+    fn write_text(&self, w: &mut TextWriter) {
+        w.string(self, false);
+    }
+    fn from_text_value(value: &TextValue) -> Result<Self> {
+        Vec::<u8>::from_text_value(value).map(Cow::Owned)
+    }
+    fn is_text_default(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+// enum は値の名前にします。名前のない値（定義されていない値）は数値にします
+impl<T: VariantEnum> TextScalar for T {
+    fn write_text(&self, w: &mut TextWriter) {
+        match self.name() {
+            Some(name) => w.raw(name),
+            None => w.raw(&self.into_i32().to_string()),
+        }
+    }
+    fn from_text_value(value: &TextValue) -> Result<Self> {
+        match value {
+            TextValue::Identifier(i) => {
+                T::from_name(i).ok_or_else(|| invalid_value(format!("unknown enum value `{}`", i)))
+            }
+            v => parse_integer(v).map(T::from_i32),
+        }
+    }
+    fn is_text_default(&self) -> bool {
+        self.into_i32() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let input = "a: 1 # comment\nb { c: \"x\" 'y' }, d: [1, -0x10]; e < f: -inf >\ng: \"\\101\\x42\\n\"";
+        let fields = parse(input).unwrap();
+        let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b", "d", "e", "g"]);
+        assert_eq!(fields[0].value, TextValue::Number("1".to_string()));
+        assert_eq!(fields[1].position, Position { line: 2, column: 1 });
+        let TextValue::Message(b) = &fields[1].value else {
+            panic!("unexpected value: {:?}", fields[1].value);
+        };
+        assert_eq!(b[0].value, TextValue::String(b"xy".to_vec()));
+        assert_eq!(b[0].value_position, Position { line: 2, column: 8 });
+        let TextValue::List(d) = &fields[2].value else {
+            panic!("unexpected value: {:?}", fields[2].value);
+        };
+        assert_eq!(d[1].1, TextValue::Number("-0x10".to_string()));
+        assert_eq!(parse_integer::<i32>(&d[1].1).unwrap(), -16);
+        assert_eq!(fields[4].value, TextValue::String(b"AB\n".to_vec()));
+
+        for (invalid, line, column) in [
+            ("a 1", 1, 3),
+            ("a: {", 1, 5),
+            ("a: \"x", 1, 6),
+            ("\n  a: [1 2]", 2, 9),
+            ("a: }", 1, 4),
+            ("[ext]: 1", 1, 1),
+            // `-` の後は数値か識別子を 1 つだけ受け付ける
+            ("a: - -5", 1, 6),
+            ("a: -{}", 1, 5),
+        ] {
+            match parse(invalid) {
+                Err(Error::InvalidText {
+                    line: l, column: c, ..
+                }) => assert_eq!((l, c), (line, column), "{}", invalid),
+                got => panic!("{}: unexpected result {:?}", invalid, got),
+            }
+        }
+        // 連続する `-` で再帰しない
+        let input = format!("a: {}1", "-".repeat(1_000_000));
+        assert!(matches!(
+            parse(&input),
+            Err(Error::InvalidText {
+                line: 1,
+                column: 5,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_scalars() {
+        let number = |s: &str| TextValue::Number(s.to_string());
+        let ident = |s: &str| TextValue::Identifier(s.to_string());
+        assert_eq!(parse_integer::<u32>(&number("017")).unwrap(), 15);
+        assert!(parse_integer::<u32>(&number("-1")).is_err());
+        assert!(parse_integer::<i32>(&number("1.5")).is_err());
+        assert_eq!(f32::from_text_value(&number("1.5f")).unwrap(), 1.5);
+        assert_eq!(f64::from_text_value(&number("1e3")).unwrap(), 1000.0);
+        assert!(f64::from_text_value(&ident("-Infinity"))
+            .unwrap()
+            .is_infinite());
+        assert!(f64::from_text_value(&ident("nan")).unwrap().is_nan());
+        assert!(0.0f64.is_text_default());
+        assert!(!(-0.0f64).is_text_default());
+        assert!(!(-0.0f32).is_text_default());
+        assert!(bool::from_text_value(&ident("True")).unwrap());
+        assert!(bool::from_text_value(&number("2")).is_err());
+
+        let mut w = TextWriter::compact();
+        w.field("s");
+        w.string(&[b'a', b'"', 0xe3, 0x81, 0x82, 0], true);
+        w.field("b");
+        w.string(&[b'a', 0xff], false);
+        w.field("f");
+        f64::NEG_INFINITY.write_text(&mut w);
+        assert_eq!(w.finish(), "s: \"a\\\"あ\\000\" b: \"a\\377\" f: -inf");
+    }
+
+    #[test]
+    fn test_writer() {
+        for (mut w, want) in [
+            (TextWriter::compact(), "a: 1 b { c: 2 } d {} e: [1, 2]"),
+            (
+                TextWriter::pretty(),
+                "a: 1\nb {\n  c: 2\n}\nd {}\ne: [1, 2]",
+            ),
+        ] {
+            w.field("a");
+            w.raw("1");
+            let m = w.begin_message("b");
+            w.field("c");
+            w.raw("2");
+            w.end_message(m, false);
+            // 空の message は keep_empty の場合のみ書き込む
+            let m = w.begin_message("x");
+            w.end_message(m, false);
+            let m = w.begin_message("d");
+            w.end_message(m, true);
+            w.list("e", &[1u32, 2]);
+            assert_eq!(w.finish(), want);
+        }
+    }
+}
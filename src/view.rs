@@ -0,0 +1,454 @@
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+use crate::decode::WireReader;
+use crate::parser::{Bit32ToValue, Bit64ToValue, LengthDelimitedToValue, VariantToValue};
+use crate::wire::{TypeBit32, TypeBit64, TypeLengthDelimited};
+use crate::wire::{TypeVairant, WireDataRef};
+use crate::Result;
+
+// MessageView は #[proto(view)] で生成される FooView<'a> が実装するトレイトです
+// View は元のバイト列を借用し、アクセサが呼び出された時にそのフィールドだけを decode します
+pub trait MessageView<'a>: Sized {
+    // new は bytes の wire format を検査し、View を返します
+    // 値の decode はアクセサが呼び出されるまで行いません
+    fn new(bytes: &'a [u8]) -> Result<Self> {
+        Self::from_segments(Segments::new(bytes))
+    }
+    // from_segments は 各バイト列の wire format を検査し、それらを連結したメッセージの View を返します
+    fn from_segments(segments: Segments<'a>) -> Result<Self>;
+    fn segments(&self) -> &Segments<'a>;
+}
+
+// Segments は View が参照するメッセージのバイト列です
+// embedded message が複数回現れた場合、parse はそれらを統合するため、View もすべての出現を順に保持します
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Segments<'a> {
+    first: &'a [u8],
+    rest: Vec<&'a [u8]>,
+}
+
+impl<'a> Segments<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            first: bytes,
+            rest: Vec::new(),
+        }
+    }
+
+    // push は末尾にバイト列を追加します
+    pub fn push(&mut self, bytes: &'a [u8]) {
+        self.rest.push(bytes);
+    }
+
+    // iter は バイト列を現れた順に返します
+    pub fn iter(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+        std::iter::once(self.first).chain(self.rest.iter().copied())
+    }
+
+    // to_bytes は すべてのバイト列を連結して返します
+    // バイト列が 1 つだけの場合は借用したまま返します
+    pub fn to_bytes(&self) -> Cow<'a, [u8]> {
+        if self.rest.is_empty() {
+            Cow::Borrowed(self.first)
+        } else {
+            Cow::Owned(self.iter().flatten().copied().collect())
+        }
+    }
+}
+
+impl<'a> From<&'a [u8]> for Segments<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        Self::new(bytes)
+    }
+}
+
+// HasView は `#[proto(view)]` を指定した型と、その View を対応付けます
+// embedded message のアクセサは、フィールドの型の View を返します
+pub trait HasView {
+    type View<'a>: MessageView<'a>;
+}
+
+// validate は bytes を走査し、wire format として正しいか検査します
+pub fn validate(bytes: &[u8]) -> Result<()> {
+    let mut r = WireReader::new(bytes);
+    while let Some((_, wire_type)) = r.read_tag()? {
+        r.skip(wire_type)?;
+    }
+    Ok(())
+}
+
+// find_last は field number と wire type が一致する最後の値を返します
+// protobuf と同様に、同じフィールドが複数回現れた場合は最後の値を使います
+pub fn find_last<'a>(
+    segments: &Segments<'a>,
+    field_number: u128,
+    wire_type: u128,
+) -> Result<Option<WireDataRef<'a>>> {
+    let mut found = None;
+    for bytes in segments.iter() {
+        let mut r = WireReader::new(bytes);
+        while let Some((n, wt)) = r.read_tag()? {
+            let value = r.read_value(wt)?;
+            if n == field_number && wt == wire_type {
+                found = Some(value);
+            }
+        }
+    }
+    Ok(found)
+}
+
+// find_bytes は field number に対応する length-delimited の最後の値を返します
+// 値がない場合は空のバイト列を返します
+pub fn find_bytes<'a>(segments: &Segments<'a>, field_number: u128) -> Result<&'a [u8]> {
+    match find_last(segments, field_number, 2)? {
+        Some(WireDataRef::LengthDelimited(v)) => Ok(v),
+        _ => Ok(&[]),
+    }
+}
+
+// find_str は field number に対応する string の最後の値を返します
+// 値がない場合は空文字列を返します
+pub fn find_str<'a>(segments: &Segments<'a>, field_number: u128) -> Result<&'a str> {
+    Ok(std::str::from_utf8(find_bytes(segments, field_number)?)?)
+}
+
+// varint は field number に対応する varint の最後の値を decode します
+// 値がない場合は default 値を返します
+pub fn varint<T: VariantToValue + Default>(
+    segments: &Segments<'_>,
+    field_number: u128,
+    ty: TypeVairant,
+) -> Result<T> {
+    match find_last(segments, field_number, 0)? {
+        Some(WireDataRef::Varint(v)) => T::from_valint(v, ty),
+        _ => Ok(T::default()),
+    }
+}
+
+// bit64 は field number に対応する 64-bit の最後の値を decode します
+pub fn bit64<T: Bit64ToValue + Default>(
+    segments: &Segments<'_>,
+    field_number: u128,
+    ty: TypeBit64,
+) -> Result<T> {
+    match find_last(segments, field_number, 1)? {
+        Some(WireDataRef::Bit64(v)) => T::from_bit64(v, ty),
+        _ => Ok(T::default()),
+    }
+}
+
+// bit32 は field number に対応する 32-bit の最後の値を decode します
+pub fn bit32<T: Bit32ToValue + Default>(
+    segments: &Segments<'_>,
+    field_number: u128,
+    ty: TypeBit32,
+) -> Result<T> {
+    match find_last(segments, field_number, 5)? {
+        Some(WireDataRef::Bit32(v)) => T::from_bit64(v, ty),
+        _ => Ok(T::default()),
+    }
+}
+
+// length_delimited は field number に対応する length-delimited の値を decode します
+// parse と同様に、同じフィールドが複数回現れた場合は統合します
+pub fn length_delimited<T: LengthDelimitedToValue + Default>(
+    segments: &Segments<'_>,
+    field_number: u128,
+    ty: TypeLengthDelimited,
+) -> Result<T> {
+    let mut found: Option<T> = None;
+    for v in LengthDelimitedIter::new(segments, field_number) {
+        let v = v?;
+        match found.as_mut() {
+            Some(x) => x.merge_length_delimited(v, ty)?,
+            None => found = Some(T::from_length_delimited(v, ty)?),
+        }
+    }
+    Ok(found.unwrap_or_default())
+}
+
+// message は field number に対応する embedded message の View を返します
+// 同じフィールドが複数回現れた場合は parse と同様に統合するため、すべての出現を保持した View を返します
+// 値がない場合は空のメッセージの View を返します
+pub fn message<'a, V: MessageView<'a>>(segments: &Segments<'a>, field_number: u128) -> Result<V> {
+    let mut found: Option<Segments<'a>> = None;
+    for v in LengthDelimitedIter::new(segments, field_number) {
+        let v = v?;
+        match found.as_mut() {
+            Some(s) => s.push(v),
+            None => found = Some(Segments::new(v)),
+        }
+    }
+    V::from_segments(found.unwrap_or_default())
+}
+
+// ScalarType は repeated な scalar フィールドの要素の型です
+// PackedIter は ScalarType に従って要素を decode します
+pub trait ScalarType<T>: Copy {
+    // WIRE_TYPE は unpacked の場合の要素の wire type です
+    const WIRE_TYPE: u128;
+    fn read(self, r: &mut WireReader<'_>) -> Result<T>;
+}
+
+impl<T: VariantToValue> ScalarType<T> for TypeVairant {
+    const WIRE_TYPE: u128 = 0;
+    fn read(self, r: &mut WireReader<'_>) -> Result<T> {
+        T::from_valint(r.read_varint()?, self)
+    }
+}
+
+impl<T: Bit64ToValue> ScalarType<T> for TypeBit64 {
+    const WIRE_TYPE: u128 = 1;
+    fn read(self, r: &mut WireReader<'_>) -> Result<T> {
+        T::from_bit64(r.read_bit64()?, self)
+    }
+}
+
+impl<T: Bit32ToValue> ScalarType<T> for TypeBit32 {
+    const WIRE_TYPE: u128 = 5;
+    fn read(self, r: &mut WireReader<'_>) -> Result<T> {
+        T::from_bit64(r.read_bit32()?, self)
+    }
+}
+
+// PackedIter は repeated な scalar フィールドの値を先頭から順に decode するイテレータです
+// packed と unpacked のどちらの形式も読み込み、同じフィールドが複数回現れた場合は、それらを連結した値を返します
+pub struct PackedIter<'a, T, K = TypeVairant> {
+    rest: std::vec::IntoIter<&'a [u8]>,
+    message: WireReader<'a>,
+    current: WireReader<'a>,
+    field_number: u128,
+    ty: K,
+    done: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T, K: ScalarType<T>> PackedIter<'a, T, K> {
+    pub fn new(segments: &Segments<'a>, field_number: u128, ty: K) -> Self {
+        Self {
+            rest: segments.rest.clone().into_iter(),
+            message: WireReader::new(segments.first),
+            current: WireReader::new(&[]),
+            field_number,
+            ty,
+            done: false,
+            _marker: PhantomData,
+        }
+    }
+
+    // read_next は 次の要素を decode します
+    // packed の値は current に設定し、unpacked の値はそのまま decode します
+    fn read_next(&mut self) -> Result<Option<T>> {
+        loop {
+            if !self.current.is_empty() {
+                return self.ty.read(&mut self.current).map(Some);
+            }
+            let (n, wt) = match self.message.read_tag()? {
+                Some(tag) => tag,
+                None => match self.rest.next() {
+                    Some(bytes) => {
+                        self.message = WireReader::new(bytes);
+                        continue;
+                    }
+                    None => return Ok(None),
+                },
+            };
+            if n != self.field_number {
+                self.message.skip(wt)?;
+            } else if wt == 2 {
+                self.current = WireReader::new(self.message.read_length_delimited()?);
+            } else if wt == K::WIRE_TYPE {
+                return self.ty.read(&mut self.message).map(Some);
+            } else {
+                self.message.skip(wt)?;
+            }
+        }
+    }
+}
+
+impl<T, K: ScalarType<T>> Iterator for PackedIter<'_, T, K> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_next() {
+            Ok(Some(v)) => Some(Ok(v)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            // エラーの後は None を返し続ける
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+// LengthDelimitedIter は field number に対応する length-delimited の値を、現れた順に返すイテレータです
+// repeated な string, bytes, embedded message の要素を借用して返します
+pub struct LengthDelimitedIter<'a> {
+    rest: std::vec::IntoIter<&'a [u8]>,
+    message: WireReader<'a>,
+    field_number: u128,
+    done: bool,
+}
+
+impl<'a> LengthDelimitedIter<'a> {
+    pub fn new(segments: &Segments<'a>, field_number: u128) -> Self {
+        Self {
+            rest: segments.rest.clone().into_iter(),
+            message: WireReader::new(segments.first),
+            field_number,
+            done: false,
+        }
+    }
+
+    fn read_next(&mut self) -> Result<Option<&'a [u8]>> {
+        loop {
+            while let Some((n, wt)) = self.message.read_tag()? {
+                if let (true, WireDataRef::LengthDelimited(v)) =
+                    (n == self.field_number, self.message.read_value(wt)?)
+                {
+                    return Ok(Some(v));
+                }
+            }
+            match self.rest.next() {
+                Some(bytes) => self.message = WireReader::new(bytes),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for LengthDelimitedIter<'a> {
+    type Item = Result<&'a [u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_next() {
+            Ok(Some(v)) => Some(Ok(v)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    // 1: varint 300, 2: "abc", 3: packed [1, 2], 2: "d", 3: packed [300]
+    const BYTES: &[u8] = &[
+        0b00001000, 0b10101100, 0b00000010, // 1
+        0b00010010, 0b00000011, 0b01100001, 0b01100010, 0b01100011, // 2
+        0b00011010, 0b00000010, 0b00000001, 0b00000010, // 3
+        0b00010010, 0b00000001, 0b01100100, // 2
+        0b00011010, 0b00000010, 0b10101100, 0b00000010, // 3
+    ];
+
+    #[test]
+    fn test_find() {
+        validate(BYTES).unwrap();
+        assert!(validate(&BYTES[..4]).is_err());
+
+        assert_eq!(
+            find_last(&BYTES.into(), 1, 0).unwrap(),
+            Some(WireDataRef::Varint(300))
+        );
+        // wire type が異なる
+        assert_eq!(find_last(&BYTES.into(), 1, 2).unwrap(), None);
+        assert_eq!(find_str(&BYTES.into(), 2).unwrap(), "d");
+        assert_eq!(find_str(&BYTES.into(), 4).unwrap(), "");
+        assert_eq!(find_bytes(&BYTES.into(), 4).unwrap(), &[] as &[u8]);
+        assert!(matches!(
+            find_str(&[0b00010010, 0b00000001, 0xff].as_slice().into(), 2),
+            Err(Error::Utf8Str(_))
+        ));
+    }
+
+    #[test]
+    fn test_packed_iter() {
+        let got: Vec<u32> = PackedIter::new(&BYTES.into(), 3, TypeVairant::Uint32)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(got, vec![1, 2, 300]);
+        assert_eq!(
+            PackedIter::<u32>::new(&BYTES.into(), 4, TypeVairant::Uint32).count(),
+            0
+        );
+
+        // u32 に収まらない値はエラーになり、その後は None を返す
+        let bytes: &[u8] = &[0b00011010, 0b00000110, 1, 0xff, 0xff, 0xff, 0xff, 0x7f];
+        let mut iter = PackedIter::<u32>::new(&bytes.into(), 3, TypeVairant::Uint32);
+        assert_eq!(iter.next().unwrap().unwrap(), 1);
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+
+        // unpacked と packed が混在していても、現れた順に返す
+        // 3: 5 (unpacked), 3: packed [0, 7], 3: 1.0 (fixed64, wire type が異なるため読み飛ばす)
+        let bytes: &[u8] = &[
+            0b00011000, 5, 0b00011010, 2, 0, 7, 0b00011001, 0, 0, 0, 0, 0, 0, 0xf0, 0x3f,
+        ];
+        let got: Vec<u32> = PackedIter::new(&bytes.into(), 3, TypeVairant::Uint32)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(got, vec![5, 0, 7]);
+
+        // fixed32 の packed と unpacked
+        let bytes: &[u8] = &[0b00010101, 1, 0, 0, 0, 0b00010010, 4, 2, 0, 0, 0];
+        let got: Vec<u32> = PackedIter::new(&bytes.into(), 2, TypeBit32::Fixed32)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(got, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_length_delimited_iter() {
+        let got: Vec<&[u8]> = LengthDelimitedIter::new(&BYTES.into(), 2)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(got, vec![b"abc".as_slice(), b"d".as_slice()]);
+        assert_eq!(LengthDelimitedIter::new(&BYTES.into(), 1).count(), 0);
+
+        let mut iter = LengthDelimitedIter::new(&BYTES[..6].into(), 2);
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_segments() {
+        // BYTES を 2 つに分けても、連結した場合と同じ値を返す
+        let mut segments = Segments::new(&BYTES[..12]);
+        segments.push(&BYTES[12..]);
+        assert_eq!(segments.to_bytes(), BYTES);
+        assert_eq!(
+            find_last(&segments, 1, 0).unwrap(),
+            Some(WireDataRef::Varint(300))
+        );
+        assert_eq!(find_str(&segments, 2).unwrap(), "d");
+        let got: Vec<u32> = PackedIter::new(&segments, 3, TypeVairant::Uint32)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(got, vec![1, 2, 300]);
+        let got: Vec<&[u8]> = LengthDelimitedIter::new(&segments, 2)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(got, vec![b"abc".as_slice(), b"d".as_slice()]);
+        let got: String = length_delimited(&segments, 2, TypeLengthDelimited::WireString).unwrap();
+        assert_eq!(got, "d");
+    }
+}
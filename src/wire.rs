@@ -1,3 +1,7 @@
+use crate::descriptor::MessageDescriptor;
+use crate::encode::EncodeOptions;
+use crate::json::JsonValue;
+use crate::text::{TextDisplay, TextField, TextWriter};
 use crate::zigzag::ZigZag;
 use crate::Result;
 use std::fmt::Display;
@@ -6,7 +10,139 @@ pub trait Proto {
     fn parse(bytes: &[u8]) -> Result<Self>
     where
         Self: Sized;
-    fn bytes(&self) -> Result<Vec<u8>>;
+    fn bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.encode_to(&mut buf)?;
+        Ok(buf)
+    }
+    // encode_to は buf の末尾に encode した結果を書き込みます
+    fn encode_to(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let start = buf.len();
+        self.encode_reversed(buf)?;
+        buf[start..].reverse();
+        Ok(())
+    }
+    // bytes_with_options は options に従って encode した結果を返します
+    fn bytes_with_options(&self, options: &EncodeOptions) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.encode_reversed_with_options(&mut buf, options)?;
+        buf.reverse();
+        Ok(buf)
+    }
+    // fingerprint は deterministic な encode の結果のハッシュ値（FNV-1a 64-bit）を返します
+    // 同じ値からは、実行環境やバージョンによらず同じ値が返ります
+    fn fingerprint(&self) -> Result<u64> {
+        let bytes = self.bytes_with_options(&EncodeOptions {
+            deterministic: true,
+            ..Default::default()
+        })?;
+        Ok(crate::fingerprint::fnv1a_64(&bytes))
+    }
+    // encode_reversed は buf の末尾に encode した結果を逆順で書き込みます
+    // 後ろから書き込むため、embedded message も一時的なバッファや長さの事前計算なしに書き込めます
+    fn encode_reversed(&self, buf: &mut Vec<u8>) -> Result<()>;
+    // encode_reversed_with_options は options に従って buf の末尾に逆順で書き込みます
+    // デフォルトでは options を使わずに encode_reversed で書き込みます
+    fn encode_reversed_with_options(
+        &self,
+        buf: &mut Vec<u8>,
+        _options: &EncodeOptions,
+    ) -> Result<()> {
+        self.encode_reversed(buf)
+    }
+    // encoded_len は bytes の結果のバイト数を、encode せずに返します
+    fn encoded_len(&self) -> usize;
+    // merge は other を self に統合します
+    // scalar は other の値が default でなければ置き換え、repeated は末尾に追加し、
+    // embedded message は再帰的に統合します
+    fn merge(&mut self, other: Self)
+    where
+        Self: Sized;
+    // merge_from は bytes を decode し、self に統合します
+    fn merge_from(&mut self, bytes: &[u8]) -> Result<()>
+    where
+        Self: Sized,
+    {
+        let other = Self::parse(bytes)?;
+        self.merge(other);
+        Ok(())
+    }
+    // descriptor は型の定義（型名とフィールド）を返します
+    // derive した型は定義を返し、それ以外はデフォルトで None を返します
+    fn descriptor() -> Option<MessageDescriptor>
+    where
+        Self: Sized,
+    {
+        None
+    }
+    // to_json_value は proto3 の JSON mapping に従い、JSON の値（object）に変換します
+    // derive した型は変換し、それ以外はデフォルトでエラーを返します
+    fn to_json_value(&self) -> Result<JsonValue> {
+        Err(crate::Error::JsonUnsupported(std::any::type_name::<Self>()))
+    }
+    // from_json_value は proto3 の JSON mapping に従い、JSON の値（object）から変換します
+    fn from_json_value(_value: &JsonValue) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Err(crate::Error::JsonUnsupported(std::any::type_name::<Self>()))
+    }
+    // to_json は JSON の文字列に変換します
+    fn to_json(&self) -> Result<String> {
+        Ok(self.to_json_value()?.to_string())
+    }
+    // from_json は JSON の文字列から変換します
+    fn from_json(input: &str) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::from_json_value(&JsonValue::parse(input)?)
+    }
+    // write_text は text format で w に書き込みます
+    // derive した型は書き込み、それ以外はデフォルトでエラーを返します
+    fn write_text(&self, _w: &mut TextWriter) -> Result<()> {
+        Err(crate::Error::TextUnsupported(std::any::type_name::<Self>()))
+    }
+    // from_text_fields は text format のフィールドから変換します
+    fn from_text_fields(_fields: &[TextField]) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Err(crate::Error::TextUnsupported(std::any::type_name::<Self>()))
+    }
+    // to_text は 1 行の text format の文字列に変換します
+    fn to_text(&self) -> Result<String> {
+        let mut w = TextWriter::compact();
+        self.write_text(&mut w)?;
+        Ok(w.finish())
+    }
+    // from_text は text format の文字列から変換します
+    // エラーは入力中の行と列を持ちます
+    fn from_text(input: &str) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::from_text_fields(&crate::text::parse(input)?)
+    }
+    // display_text は フィールドごとに改行した text format で表示する Display を返します
+    fn display_text(&self) -> TextDisplay<'_, Self> {
+        TextDisplay(self)
+    }
+    // write_delimited は 長さ（varint）を先頭に付けて w に書き込みます
+    fn write_delimited<W: std::io::Write>(&self, w: &mut W) -> Result<()>
+    where
+        Self: Sized,
+    {
+        crate::delimited::write_delimited(self, w)
+    }
+    // read_delimited は r から長さ付きのメッセージを 1 つ読み込みます
+    // レコードの先頭で入力が終わっている場合は None を返します
+    fn read_delimited<R: std::io::Read>(r: &mut R) -> Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        crate::delimited::read_delimited(r)
+    }
 }
 
 // alias　ではなく、タプル構造体にしたほうがよさそう
@@ -26,6 +162,16 @@ impl WireStruct {
     pub fn wire_type(&self) -> WireData {
         self.wire_type.clone()
     }
+    // data は値を複製せずに返します
+    pub fn data(&self) -> &WireData {
+        &self.wire_type
+    }
+    pub fn data_mut(&mut self) -> &mut WireData {
+        &mut self.wire_type
+    }
+    pub fn into_data(self) -> WireData {
+        self.wire_type
+    }
     // is_empty は WireStruct の 値がゼロかどうか確認します。
     // ゼロの場合、encode時に書き出されません
     pub fn is_empty(&self) -> bool {
@@ -105,6 +251,39 @@ impl WireData {
     }
 }
 
+// WireDataRef は入力を借用した WireData です
+// length-delimited の値は入力をコピーせずに参照します
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WireDataRef<'a> {
+    Varint(u128),
+    Bit64([u8; 8]),
+    LengthDelimited(&'a [u8]),
+    Bit32([u8; 4]),
+}
+
+impl WireDataRef<'_> {
+    pub fn type_number(&self) -> u128 {
+        match &self {
+            WireDataRef::Varint(_) => 0,
+            WireDataRef::Bit64(_) => 1,
+            WireDataRef::LengthDelimited(_) => 2,
+            WireDataRef::Bit32(_) => 5,
+        }
+    }
+
+    // to_wire_data は値をコピーして WireData を返します
+    pub fn to_wire_data(&self) -> WireData {
+        match *self {
+            WireDataRef::Varint(v) => WireData::Varint(WireDataVarint::new(v)),
+            WireDataRef::Bit64(v) => WireData::Bit64(WireDataBit64::new(v)),
+            WireDataRef::LengthDelimited(v) => {
+                WireData::LengthDelimited(WireDataLengthDelimited::new(v.to_vec()))
+            }
+            WireDataRef::Bit32(v) => WireData::Bit32(WireDataBit32::new(v)),
+        }
+    }
+}
+
 impl Display for WireData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
@@ -0,0 +1,9 @@
+use protowirers::*;
+
+#[derive(Proto)]
+struct Sample {
+    #[def(field_num = 1, def_type = "uint64", with = "not a path")]
+    s: u64,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use protowirers::*;
+
+#[derive(Proto)]
+struct Sample {
+    #[def(field_num = 1, def_type = "string", repeated, packed)]
+    s: Vec<String>,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use protowirers::*;
+
+#[derive(Proto, Default, PartialEq)]
+#[proto(transparent)]
+struct UserId(#[def(def_type = "uint64")] u64);
+
+#[derive(Proto)]
+struct Sample {
+    #[def(field_num = 1, def_type = "uint32")]
+    id: UserId,
+}
+
+fn main() {}
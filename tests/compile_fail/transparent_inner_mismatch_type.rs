@@ -0,0 +1,11 @@
+use protowirers::*;
+
+#[derive(Proto)]
+#[proto(transparent)]
+struct UserId(#[def(def_type = "uint64")] u64);
+
+#[derive(Proto)]
+#[proto(transparent)]
+struct OwnerId(#[def(def_type = "uint32")] UserId);
+
+fn main() {}
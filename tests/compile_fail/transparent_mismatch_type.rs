@@ -0,0 +1,7 @@
+use protowirers::*;
+
+#[derive(Proto)]
+#[proto(transparent)]
+struct UserId(#[def(def_type = "uint64")] u32);
+
+fn main() {}
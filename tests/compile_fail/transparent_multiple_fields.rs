@@ -0,0 +1,7 @@
+use protowirers::*;
+
+#[derive(Proto)]
+#[proto(transparent)]
+struct UserId(#[def(def_type = "uint64")] u64, #[def(def_type = "uint64")] u64);
+
+fn main() {}
@@ -0,0 +1,6 @@
+use protowirers::*;
+
+#[derive(Proto)]
+struct Point(#[def(field_num = 1, def_type = "sint32")] i32, i32);
+
+fn main() {}
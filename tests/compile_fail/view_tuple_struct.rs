@@ -0,0 +1,7 @@
+use protowirers::*;
+
+#[derive(Proto)]
+#[proto(view)]
+struct Sample(#[def(field_num = 1, def_type = "uint32")] u32);
+
+fn main() {}
@@ -0,0 +1,10 @@
+use protowirers::*;
+
+#[derive(Proto)]
+#[proto(transparent, view)]
+struct UserId {
+    #[def(def_type = "uint64")]
+    value: u64,
+}
+
+fn main() {}
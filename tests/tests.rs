@@ -272,3 +272,1594 @@ fn test_repeated_field() {
     assert_eq!(x.str_field, "abc");
     assert_eq!(x.vec_field, vec![100000001, 2, 3,]);
 }
+
+#[test]
+fn test_with_module() {
+    use protowirers::adapter::ProtoAdapter;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    mod ip_addr {
+        use std::net::{IpAddr, Ipv4Addr};
+        pub fn to_wire(v: &IpAddr) -> protowirers::Result<Vec<u8>> {
+            Ok(match v {
+                IpAddr::V4(v) => v.octets().to_vec(),
+                IpAddr::V6(v) => v.octets().to_vec(),
+            })
+        }
+        pub fn from_wire(v: Vec<u8>) -> protowirers::Result<IpAddr> {
+            match v.len() {
+                0 => Ok(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+                4 => Ok(IpAddr::V4(<[u8; 4]>::try_from(v).unwrap().into())),
+                16 => Ok(IpAddr::V6(<[u8; 16]>::try_from(v).unwrap().into())),
+                n => Err(protowirers::Error::UnexpectedType {
+                    want: "4 or 16 bytes".to_string(),
+                    got: format!("{} bytes", n),
+                }),
+            }
+        }
+    }
+    mod unix_nanos {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+        pub fn to_wire(v: &SystemTime) -> protowirers::Result<i64> {
+            let d = v.duration_since(UNIX_EPOCH).unwrap_or_default();
+            Ok(i64::try_from(d.as_nanos())?)
+        }
+        pub fn from_wire(v: i64) -> protowirers::Result<SystemTime> {
+            Ok(UNIX_EPOCH + Duration::from_nanos(u64::try_from(v)?))
+        }
+    }
+
+    #[derive(Debug, PartialEq, Default)]
+    struct UserId(u64);
+    impl ProtoAdapter for UserId {
+        type Wire = u64;
+        fn to_wire(&self) -> protowirers::Result<u64> {
+            Ok(self.0)
+        }
+        fn from_wire(wire: u64) -> protowirers::Result<Self> {
+            Ok(UserId(wire))
+        }
+    }
+
+    #[derive(Proto)]
+    struct Sample {
+        #[def(field_num = 1, def_type = "bytes", with = "ip_addr")]
+        addr: IpAddr,
+        #[def(field_num = 2, def_type = "int64", with = "unix_nanos")]
+        created_at: SystemTime,
+        #[def(field_num = 3, def_type = "uint64", with = "protowirers::adapter")]
+        user_id: UserId,
+    }
+    let s = Sample {
+        addr: IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)),
+        created_at: UNIX_EPOCH + Duration::from_nanos(300),
+        user_id: UserId(150),
+    };
+    let bytes = s.bytes().unwrap();
+    assert_eq!(
+        bytes,
+        vec![
+            0b00001010, 0b00000100, 192, 168, 0, 1, // field_num = 1
+            0b00010000, 0b10101100, 0b00000010, // field_num = 2
+            0b00011000, 0b10010110, 0b00000001, // field_num = 3
+        ]
+    );
+    let x = Sample::parse(&bytes).unwrap();
+    assert_eq!(x.addr, IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)));
+    assert_eq!(x.created_at, UNIX_EPOCH + Duration::from_nanos(300));
+    assert_eq!(x.user_id, UserId(150));
+
+    // 値がない場合は wire の default 値から変換される
+    let x = Sample::parse(&[]).unwrap();
+    assert_eq!(x.addr, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    assert_eq!(x.created_at, UNIX_EPOCH);
+    assert_eq!(x.user_id, UserId(0));
+
+    let bytes: &[u8] = &[
+        0b00001010, 0b00010000, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
+    ];
+    let x = Sample::parse(bytes).unwrap();
+    assert_eq!(x.addr, IpAddr::V6(Ipv6Addr::LOCALHOST));
+
+    let bytes: &[u8] = &[0b00001010, 0b00000010, 1, 2];
+    assert!(Sample::parse(bytes).is_err());
+}
+
+#[test]
+fn test_adapter() {
+    use protowirers::adapter::ProtoAdapter;
+
+    #[derive(Debug, PartialEq)]
+    struct Uuid([u8; 16]);
+    impl ProtoAdapter for Uuid {
+        type Wire = Vec<u8>;
+        fn to_wire(&self) -> protowirers::Result<Vec<u8>> {
+            Ok(self.0.to_vec())
+        }
+        fn from_wire(wire: Vec<u8>) -> protowirers::Result<Self> {
+            let mut v = [0; 16];
+            if !wire.is_empty() {
+                v = wire
+                    .try_into()
+                    .map_err(|w: Vec<u8>| protowirers::Error::UnexpectedType {
+                        want: "16 bytes".to_string(),
+                        got: format!("{} bytes", w.len()),
+                    })?;
+            }
+            Ok(Uuid(v))
+        }
+    }
+
+    #[derive(Proto)]
+    struct Sample {
+        #[def(field_num = 1, def_type = "bytes", with = "protowirers::adapter")]
+        id: Uuid,
+    }
+    let s = Sample { id: Uuid([7; 16]) };
+    let bytes = s.bytes().unwrap();
+    assert_eq!(bytes[..2], [0b00001010, 16]);
+    assert_eq!(Sample::parse(&bytes).unwrap().id, Uuid([7; 16]));
+    // 値がない場合は空の Vec<u8> から変換される
+    assert_eq!(Sample::parse(&[]).unwrap().id, Uuid([0; 16]));
+    assert!(Sample::parse(&[0b00001010, 1, 7]).is_err());
+}
+
+#[test]
+fn test_transparent() {
+    #[derive(Proto, Debug, Default, PartialEq, Clone, Copy)]
+    #[proto(transparent)]
+    struct UserId(#[def(def_type = "uint64")] u64);
+
+    #[derive(Proto, Debug, Default, PartialEq, Clone)]
+    #[proto(transparent)]
+    struct Name {
+        #[def(def_type = "string")]
+        value: String,
+    }
+
+    #[derive(Proto, Debug, Default, PartialEq, Clone, Copy)]
+    #[proto(transparent)]
+    struct Score(#[def(def_type = "double")] f64);
+
+    #[derive(Proto, Debug, Default, PartialEq, Clone, Copy)]
+    #[proto(transparent)]
+    struct Delta(#[def(def_type = "sint32")] i32);
+
+    #[derive(Proto)]
+    struct Sample {
+        #[def(field_num = 1, def_type = "uint64")]
+        id: UserId,
+        #[def(field_num = 2, def_type = "string")]
+        name: Name,
+        #[def(field_num = 3, def_type = "double")]
+        score: Score,
+        #[def(field_num = 4, def_type = "sint32", repeated, packed)]
+        deltas: Vec<Delta>,
+    }
+    let s = Sample {
+        id: UserId(150),
+        name: Name {
+            value: "abc".to_string(),
+        },
+        score: Score(1.25),
+        deltas: vec![Delta(-1), Delta(1)],
+    };
+    let bytes = s.bytes().unwrap();
+    assert_eq!(
+        bytes,
+        vec![
+            0b00001000, 0b10010110, 0b00000001, // field_num = 1
+            0b00010010, 0b00000011, 0b01100001, 0b01100010, 0b01100011, // field_num = 2
+            0b00011001, 0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00000000,
+            0b11110100, 0b00111111, // field_num = 3
+            0b00100010, 0b00000010, 0b00000001, 0b00000010, // field_num = 4
+        ]
+    );
+    let x = Sample::parse(&bytes).unwrap();
+    assert_eq!(x.id, UserId(150));
+    assert_eq!(x.name.value, "abc");
+    assert_eq!(x.score, Score(1.25));
+    assert_eq!(x.deltas, vec![Delta(-1), Delta(1)]);
+}
+
+#[test]
+fn test_generics() {
+    use std::borrow::Cow;
+
+    #[derive(Proto, Default, Clone, Debug, PartialEq)]
+    struct Payload {
+        #[def(field_num = 1, def_type = "string")]
+        s_string: String,
+    }
+    #[derive(Proto, Default, Clone, Copy, Debug, PartialEq)]
+    #[proto(transparent)]
+    struct Wrapped<N>(#[def(def_type = "uint64")] N);
+
+    #[derive(Proto)]
+    struct Envelope<'a, T, N: Copy>
+    where
+        T: std::fmt::Debug,
+    {
+        #[def(field_num = 1, def_type = "embedded")]
+        body: T,
+        #[def(field_num = 2, def_type = "string")]
+        name: Cow<'a, str>,
+        #[def(field_num = 3, def_type = "uint64")]
+        count: N,
+        #[def(field_num = 4, def_type = "uint64", repeated, packed)]
+        ids: Vec<N>,
+    }
+
+    let e: Envelope<Payload, Wrapped<u64>> = Envelope {
+        body: Payload {
+            s_string: "hi".to_string(),
+        },
+        name: Cow::Borrowed("env"),
+        count: Wrapped(3),
+        ids: vec![Wrapped(1), Wrapped(2)],
+    };
+    let bytes = e.bytes().unwrap();
+    assert_eq!(
+        bytes,
+        vec![
+            0b00001010, 0b00000100, 0b00001010, 0b00000010, 0b01101000,
+            0b01101001, // field_num = 1
+            0b00010010, 0b00000011, 0b01100101, 0b01101110, 0b01110110, // field_num = 2
+            0b00011000, 0b00000011, // field_num = 3
+            0b00100010, 0b00000010, 0b00000001, 0b00000010, // field_num = 4
+        ]
+    );
+    let x = Envelope::<Payload, u64>::parse(&bytes).unwrap();
+    assert_eq!(x.body.s_string, "hi");
+    assert_eq!(x.name, "env");
+    assert_eq!(x.count, 3);
+    assert_eq!(x.ids, vec![1, 2]);
+}
+
+#[test]
+fn test_tuple_struct() {
+    #[derive(Proto, Debug, Default, Clone, PartialEq)]
+    struct Point(
+        #[def(field_num = 1, def_type = "sint32")] i32,
+        #[def(field_num = 2, def_type = "sint32")] i32,
+    );
+    #[derive(Proto, Debug, Default, Clone, PartialEq)]
+    struct Marker;
+    #[derive(Proto, Debug, PartialEq)]
+    struct Line(
+        #[def(field_num = 1, def_type = "embedded")] Point,
+        #[def(field_num = 2, def_type = "embedded")] Point,
+        #[def(field_num = 3, def_type = "embedded")] Marker,
+    );
+
+    let p = Point(-1, 2);
+    let bytes = p.bytes().unwrap();
+    assert_eq!(bytes, vec![0b00001000, 0b00000001, 0b00010000, 0b00000100]);
+    assert_eq!(Point::parse(&bytes).unwrap(), Point(-1, 2));
+
+    let bytes = Marker.bytes().unwrap();
+    assert_eq!(bytes, vec![]);
+    assert_eq!(Marker::parse(&[0b00001000, 0b00000001]).unwrap(), Marker);
+
+    let l = Line(Point(0, 1), Point(3, 0), Marker);
+    let bytes = l.bytes().unwrap();
+    assert_eq!(
+        bytes,
+        vec![
+            0b00001010, 0b00000010, 0b00010000, 0b00000010, // field_num = 1
+            0b00010010, 0b00000010, 0b00001000, 0b00000110, // field_num = 2
+        ]
+    );
+    assert_eq!(
+        Line::parse(&bytes).unwrap(),
+        Line(Point(0, 1), Point(3, 0), Marker)
+    );
+}
+
+#[test]
+fn test_merge() {
+    #[derive(Proto, Debug, Default, Clone, PartialEq)]
+    struct Inner {
+        #[def(field_num = 1, def_type = "int32")]
+        i_int32: i32,
+        #[def(field_num = 2, def_type = "string")]
+        s_string: String,
+    }
+    #[derive(Proto, Debug, Default, Clone, PartialEq)]
+    struct Config {
+        #[def(field_num = 1, def_type = "uint32")]
+        u_uint32: u32,
+        #[def(field_num = 2, def_type = "string")]
+        s_string: String,
+        #[def(field_num = 3, def_type = "embedded")]
+        i_inner: Inner,
+        #[def(field_num = 4, def_type = "uint32", repeated, packed)]
+        r_u_int32: Vec<u32>,
+    }
+    let base = Config {
+        u_uint32: 1,
+        s_string: "base".to_string(),
+        i_inner: Inner {
+            i_int32: 10,
+            s_string: "inner".to_string(),
+        },
+        r_u_int32: vec![1, 2],
+    };
+    let overrides = Config {
+        u_uint32: 0,
+        s_string: "override".to_string(),
+        i_inner: Inner {
+            i_int32: 20,
+            s_string: String::new(),
+        },
+        r_u_int32: vec![3],
+    };
+    let expected = Config {
+        u_uint32: 1,
+        s_string: "override".to_string(),
+        i_inner: Inner {
+            i_int32: 20,
+            s_string: "inner".to_string(),
+        },
+        r_u_int32: vec![1, 2, 3],
+    };
+    {
+        let mut x = base.clone();
+        x.merge(overrides.clone());
+        assert_eq!(x, expected);
+    }
+    {
+        let mut x = base.clone();
+        x.merge_from(&overrides.bytes().unwrap()).unwrap();
+        assert_eq!(x, expected);
+    }
+    {
+        // 連結したバイト列は統合した結果と同じになる
+        let mut bytes = base.bytes().unwrap();
+        bytes.extend(overrides.bytes().unwrap());
+        assert_eq!(Config::parse(&bytes).unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_encoded_len() {
+    #[derive(Proto, Clone, Copy, PartialEq, Eq, Debug)]
+    enum TestEnum {
+        Value1,
+        Value2,
+        ValueOther(i32),
+    }
+    #[derive(Proto, Default, Clone, PartialEq)]
+    #[proto(transparent)]
+    struct Name(#[def(def_type = "string")] String);
+    #[derive(Proto, Default, Clone)]
+    struct Leaf {
+        #[def(field_num = 1, def_type = "double")]
+        d_double: f64,
+    }
+    #[derive(Proto, Default, Clone)]
+    struct Inner {
+        #[def(field_num = 1, def_type = "int32")]
+        i_int32: i32,
+        #[def(field_num = 2, def_type = "string")]
+        s_string: String,
+        #[def(field_num = 3, def_type = "embedded")]
+        l_leaf: Leaf,
+    }
+    #[derive(Proto, Default, Clone)]
+    struct Sample {
+        #[def(field_num = 1, def_type = "uint64")]
+        u_uint64: u64,
+        #[def(field_num = 2, def_type = "sint32")]
+        s_sint32: i32,
+        #[def(field_num = 3, def_type = "int64")]
+        i_int64: i64,
+        #[def(field_num = 4, def_type = "bool")]
+        b_bool: bool,
+        #[def(field_num = 5, def_type = "enum")]
+        t_test_enum: TestEnum,
+        #[def(field_num = 6, def_type = "sfixed64")]
+        s_sfixed64: i64,
+        #[def(field_num = 7, def_type = "float")]
+        f_float: f32,
+        #[def(field_num = 8, def_type = "bytes")]
+        b_bytes: Vec<u8>,
+        #[def(field_num = 9, def_type = "string")]
+        n_name: Name,
+        #[def(field_num = 10, def_type = "uint64", with = "user_id")]
+        u_user_id: String,
+        #[def(field_num = 2000, def_type = "embedded")]
+        i_inner: Inner,
+        #[def(field_num = 20, def_type = "sint64", repeated, packed)]
+        r_s_int64: Vec<i64>,
+    }
+    mod user_id {
+        pub fn to_wire(v: &str) -> protowirers::Result<u64> {
+            Ok(v.len() as u64)
+        }
+        pub fn from_wire(v: u64) -> protowirers::Result<String> {
+            Ok("x".repeat(v as usize))
+        }
+    }
+    let check = |x: &Sample| assert_eq!(x.encoded_len(), x.bytes().unwrap().len());
+
+    // すべて default 値の場合は何も書き出されない
+    let x = Sample::default();
+    assert_eq!(x.encoded_len(), 0);
+    check(&x);
+
+    let x = Sample {
+        u_uint64: u64::MAX,
+        s_sint32: -1000,
+        i_int64: -1,
+        b_bool: true,
+        t_test_enum: TestEnum::ValueOther(300),
+        s_sfixed64: -2,
+        f_float: 1.5,
+        b_bytes: vec![0; 300],
+        n_name: Name("name".to_string()),
+        u_user_id: "id".to_string(),
+        i_inner: Inner {
+            i_int32: 1,
+            s_string: "a".repeat(127),
+            l_leaf: Leaf { d_double: 0.5 },
+        },
+        r_s_int64: vec![0, -1, 1 << 40],
+    };
+    check(&x);
+
+    // default 値以外の enum は tag と値の 2 バイトになる
+    let x = Sample {
+        t_test_enum: TestEnum::Value2,
+        ..Sample::default()
+    };
+    assert_eq!(x.encoded_len(), 2);
+    check(&x);
+}
+
+#[test]
+fn test_encode_to() {
+    #[derive(Proto, Default, Clone)]
+    struct Inner {
+        #[def(field_num = 1, def_type = "sint32")]
+        s_sint32: i32,
+        #[def(field_num = 2, def_type = "uint32", repeated, packed)]
+        r_u_int32: Vec<u32>,
+    }
+    #[derive(Proto, Default, Clone)]
+    struct Outer {
+        #[def(field_num = 1, def_type = "string")]
+        s_string: String,
+        #[def(field_num = 2, def_type = "embedded")]
+        i_inner: Inner,
+        #[def(field_num = 3, def_type = "fixed32")]
+        f_fixed32: u32,
+    }
+    let x = Outer {
+        s_string: "ab".to_string(),
+        i_inner: Inner {
+            s_sint32: -1,
+            r_u_int32: vec![1, 300],
+        },
+        f_fixed32: 1,
+    };
+    let expected: Vec<u8> = vec![
+        0b00001010, 0b00000010, 0b01100001, 0b01100010, // s_string
+        0b00010010, 0b00000111, // i_inner
+        0b00001000, 0b00000001, // s_sint32
+        0b00010010, 0b00000011, 0b00000001, 0b10101100, 0b00000010, // r_u_int32
+        0b00011101, 0b00000001, 0b00000000, 0b00000000, 0b00000000, // f_fixed32
+    ];
+    assert_eq!(x.bytes().unwrap(), expected);
+
+    // 既存のバッファの末尾に追加される
+    let mut buf = vec![0xff, 0xfe];
+    x.encode_to(&mut buf).unwrap();
+    assert_eq!(buf[..2], [0xff, 0xfe]);
+    assert_eq!(buf[2..], expected[..]);
+    assert_eq!(x.encoded_len(), expected.len());
+}
+
+#[test]
+fn test_parse_unknown_field() {
+    #[derive(Proto, Debug, Default, PartialEq)]
+    struct Sample {
+        #[def(field_num = 2, def_type = "string")]
+        s_string: String,
+        #[def(field_num = 4, def_type = "uint32")]
+        u_uint32: u32,
+    }
+    let bytes: &[u8] = &[
+        0b00001000, 0b10101100, 0b00000010, // 1: varint (unknown)
+        0b00010010, 0b00000010, 0b01100001, 0b01100010, // 2: "ab"
+        0b00011101, 0b00000001, 0b00000000, 0b00000000, 0b00000000, // 3: fixed32 (unknown)
+        0b00100010, 0b00000001, 0b00000001, // 4: wire type が異なる
+        0b00101001, 0b00000010, 0b00000000, 0b00000000, 0b00000000, 0b00000000, 0b00000000,
+        0b00000000, 0b00000000, // 5: fixed64 (unknown)
+    ];
+    let x = Sample::parse(bytes).unwrap();
+    assert_eq!(
+        x,
+        Sample {
+            s_string: "ab".to_string(),
+            u_uint32: 0,
+        }
+    );
+
+    // 途中で途切れている
+    assert!(Sample::parse(&bytes[..5]).is_err());
+    assert!(Sample::parse(&bytes[..9]).is_err());
+}
+
+#[test]
+fn test_encode_without_clone() {
+    // encode は値を借用するため、Clone を実装していない型も encode できる
+    #[derive(Proto, Debug, Default, PartialEq)]
+    struct Inner {
+        #[def(field_num = 1, def_type = "bytes")]
+        b_bytes: Vec<u8>,
+    }
+    #[derive(Proto, Debug, Default, PartialEq)]
+    struct Outer<T> {
+        #[def(field_num = 1, def_type = "string")]
+        s_string: String,
+        #[def(field_num = 2, def_type = "embedded")]
+        i_inner: T,
+        #[def(field_num = 3, def_type = "uint32", repeated, packed)]
+        r_u_int32: Vec<u32>,
+    }
+    let x = Outer {
+        s_string: "abc".to_string(),
+        i_inner: Inner {
+            b_bytes: vec![1, 2, 3],
+        },
+        r_u_int32: vec![1, 300],
+    };
+    let bytes = x.bytes().unwrap();
+    assert_eq!(Outer::<Inner>::parse(&bytes).unwrap(), x);
+}
+
+#[test]
+fn test_delimited() {
+    use protowirers::delimited::DelimitedReader;
+
+    #[derive(Proto, Debug, Default, PartialEq)]
+    struct Record {
+        #[def(field_num = 1, def_type = "uint32")]
+        u_uint32: u32,
+        #[def(field_num = 2, def_type = "string")]
+        s_string: String,
+    }
+    let records = vec![
+        Record {
+            u_uint32: 1,
+            s_string: "a".to_string(),
+        },
+        // 空のメッセージは長さ 0 として書き込まれる
+        Record::default(),
+        Record {
+            u_uint32: 300,
+            s_string: "b".repeat(200),
+        },
+    ];
+    let mut buf = Vec::new();
+    for r in &records {
+        r.write_delimited(&mut buf).unwrap();
+    }
+    assert_eq!(buf[..6], [5, 0b00001000, 1, 0b00010010, 1, b'a']);
+    assert_eq!(buf[6], 0);
+
+    {
+        let mut r = buf.as_slice();
+        for expected in &records {
+            assert_eq!(
+                Record::read_delimited(&mut r).unwrap().as_ref(),
+                Some(expected)
+            );
+        }
+        // 入力の終わり
+        assert!(Record::read_delimited(&mut r).unwrap().is_none());
+    }
+    {
+        let got: Vec<Record> = DelimitedReader::new(buf.as_slice())
+            .collect::<protowirers::Result<_>>()
+            .unwrap();
+        assert_eq!(got, records);
+    }
+    {
+        // 値の途中で終わっている
+        let mut r = &buf[..4];
+        assert!(matches!(
+            Record::read_delimited(&mut r),
+            Err(Error::TruncatedRecord { want: 5, got: 3 })
+        ));
+        // 長さの途中で終わっている
+        let mut r = &buf[7..8];
+        assert!(matches!(
+            Record::read_delimited(&mut r),
+            Err(Error::UnexpectedFormat)
+        ));
+        // エラーの後は None を返す
+        let mut reader = DelimitedReader::<_, Record>::new(&buf[..buf.len() - 1]);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+}
+
+#[test]
+fn test_view() {
+    use protowirers::view::HasView;
+
+    mod unix_secs {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+        pub fn to_wire(v: &SystemTime) -> protowirers::Result<u64> {
+            Ok(v.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs())
+        }
+        pub fn from_wire(v: u64) -> protowirers::Result<SystemTime> {
+            Ok(UNIX_EPOCH + Duration::from_secs(v))
+        }
+    }
+
+    #[derive(Proto, Debug, Default, PartialEq, Clone)]
+    #[proto(transparent)]
+    struct Name(#[def(def_type = "string")] String);
+
+    #[derive(Proto, Debug, Default, PartialEq)]
+    #[proto(view)]
+    struct Inner {
+        #[def(field_num = 1, def_type = "int64")]
+        i_int64: i64,
+        #[def(field_num = 2, def_type = "bytes")]
+        b_bytes: Vec<u8>,
+    }
+
+    #[derive(Proto, Debug, PartialEq)]
+    #[proto(view)]
+    struct Sample {
+        #[def(field_num = 1, def_type = "string")]
+        s_string: String,
+        #[def(field_num = 2, def_type = "embedded")]
+        i_inner: Inner,
+        #[def(field_num = 3, def_type = "uint32", repeated, packed)]
+        r_u_int32: Vec<u32>,
+        #[def(field_num = 4, def_type = "double")]
+        d_double: f64,
+        #[def(field_num = 5, def_type = "fixed32")]
+        f_fixed32: u32,
+        #[def(field_num = 6, def_type = "string")]
+        name: Name,
+        #[def(field_num = 7, def_type = "uint64", with = "unix_secs")]
+        created_at: std::time::SystemTime,
+    }
+    let s = Sample {
+        s_string: "abc".to_string(),
+        i_inner: Inner {
+            i_int64: -1,
+            b_bytes: vec![1, 2, 3],
+        },
+        r_u_int32: vec![1, 300, 2],
+        d_double: 1.5,
+        f_fixed32: 7,
+        name: Name("n".to_string()),
+        created_at: std::time::UNIX_EPOCH + std::time::Duration::from_secs(60),
+    };
+    let bytes = s.bytes().unwrap();
+    let view = SampleView::new(&bytes).unwrap();
+    let s_string: &str = view.s_string().unwrap();
+    assert_eq!(s_string, "abc");
+    let inner: InnerView = view.i_inner().unwrap();
+    assert_eq!(inner.i_int64().unwrap(), -1);
+    assert_eq!(inner.b_bytes().unwrap(), &[1, 2, 3]);
+    assert_eq!(
+        view.r_u_int32()
+            .unwrap()
+            .collect::<protowirers::Result<Vec<u32>>>()
+            .unwrap(),
+        vec![1, 300, 2]
+    );
+    assert_eq!(view.d_double().unwrap(), 1.5);
+    assert_eq!(view.f_fixed32().unwrap(), 7);
+    assert_eq!(view.name().unwrap(), Name("n".to_string()));
+    assert_eq!(view.created_at().unwrap(), s.created_at);
+    assert_eq!(view.as_bytes(), bytes.as_slice());
+    assert_eq!(view.to_message().unwrap(), s);
+
+    // 値がない場合は default 値、embedded は空のメッセージの View になる
+    let view = <Sample as HasView>::View::new(&[]).unwrap();
+    assert_eq!(view.s_string().unwrap(), "");
+    assert_eq!(view.i_inner().unwrap().i_int64().unwrap(), 0);
+    assert_eq!(view.r_u_int32().unwrap().count(), 0);
+    assert_eq!(view.created_at().unwrap(), std::time::UNIX_EPOCH);
+
+    // 同じフィールドが複数回現れた場合、スカラーは最後の値、packed は連結した値になる
+    let bytes: &[u8] = &[
+        0b00001010, 0b00000001, b'a', // field_num = 1
+        0b00011010, 0b00000001, 0b00000001, // field_num = 3
+        0b00001010, 0b00000001, b'b', // field_num = 1
+        0b00011010, 0b00000001, 0b00000010, // field_num = 3
+    ];
+    let view = SampleView::new(bytes).unwrap();
+    assert_eq!(view.s_string().unwrap(), "b");
+    assert_eq!(
+        view.r_u_int32()
+            .unwrap()
+            .collect::<protowirers::Result<Vec<u32>>>()
+            .unwrap(),
+        vec![1, 2]
+    );
+
+    // embedded message が複数回現れた場合、parse と同様にすべての出現を統合した View になる
+    let bytes: &[u8] = &[
+        0b00010010, 0b00000101, // field_num = 2
+        0b00001000, 0b00000101, // i_int64 = 5
+        0b00010010, 0b00000001, 0b00000001, // b_bytes = [1]
+        0b00010010, 0b00000011, // field_num = 2
+        0b00010010, 0b00000001, 0b00000010, // b_bytes = [2]
+    ];
+    let s = Sample::parse(bytes).unwrap();
+    let inner = SampleView::new(bytes).unwrap().i_inner().unwrap();
+    assert_eq!(inner.i_int64().unwrap(), s.i_inner.i_int64);
+    assert_eq!(inner.b_bytes().unwrap(), s.i_inner.b_bytes.as_slice());
+    assert_eq!(inner.to_message().unwrap(), s.i_inner);
+    assert_eq!(s.i_inner.i_int64, 5);
+
+    // 不正な wire format は new の時点でエラーになる
+    assert!(SampleView::new(&[0b00001010, 0b00000011, b'a']).is_err());
+    // 不正な UTF-8 はアクセス時にエラーになる
+    let view = SampleView::new(&[0b00001010, 0b00000001, 0xff]).unwrap();
+    assert!(view.s_string().is_err());
+}
+
+#[test]
+fn test_lazy() {
+    use protowirers::lazy::Lazy;
+
+    #[derive(Proto, Debug, Default, PartialEq, Clone)]
+    struct Inner {
+        #[def(field_num = 1, def_type = "int64")]
+        i_int64: i64,
+        #[def(field_num = 2, def_type = "string")]
+        s_string: String,
+    }
+
+    #[derive(Proto, Debug, Default, PartialEq)]
+    struct Sample {
+        #[def(field_num = 1, def_type = "uint32")]
+        u_uint32: u32,
+        #[def(field_num = 14, def_type = "embedded")]
+        inner: Lazy<Inner>,
+    }
+
+    // 未知のフィールドや field number の順序も含めて、そのまま書き出される
+    let inner_bytes: &[u8] = &[
+        0b00010010, 0b00000001, b'a', // field_num = 2
+        0b00011000, 0b00000001, // field_num = 3 (unknown)
+        0b00001000, 0b00000001, // field_num = 1
+    ];
+    let mut bytes = vec![0b01110010, inner_bytes.len() as u8];
+    bytes.extend(inner_bytes);
+    let mut s = Sample::parse(&bytes).unwrap();
+    assert_eq!(s.inner.raw(), Some(inner_bytes));
+    assert_eq!(
+        s.inner.get().unwrap(),
+        &Inner {
+            i_int64: 1,
+            s_string: "a".to_string(),
+        }
+    );
+    assert_eq!(s.bytes().unwrap(), bytes);
+    assert_eq!(s.encoded_len(), bytes.len());
+
+    // 変更した場合は値から書き出される
+    s.u_uint32 = 1;
+    s.inner.get_mut().unwrap().i_int64 = 2;
+    assert_eq!(s.inner.raw(), None);
+    assert_eq!(
+        s.bytes().unwrap(),
+        vec![
+            0b00001000, 0b00000001, // field_num = 1
+            0b01110010, 0b00000101, // field_num = 14
+            0b00001000, 0b00000010, 0b00010010, 0b00000001, b'a',
+        ]
+    );
+
+    // Lazy::new で作った値は、parse 後も同じバイト列に書き出される
+    let s = Sample {
+        u_uint32: 0,
+        inner: Lazy::new(Inner {
+            i_int64: 1,
+            s_string: String::new(),
+        }),
+    };
+    let bytes = s.bytes().unwrap();
+    let s = Sample::parse(&bytes).unwrap();
+    assert_eq!(s.inner.get().unwrap().i_int64, 1);
+    assert_eq!(s.bytes().unwrap(), bytes);
+
+    // 同じフィールドが複数回現れた場合は、decode せずに連結する
+    let bytes: &[u8] = &[
+        0b01110010, 0b00000010, 0b00001000, 0b00000001, // field_num = 14
+        0b01110010, 0b00000011, 0b00010010, 0b00000001, b'b', // field_num = 14
+    ];
+    let s = Sample::parse(bytes).unwrap();
+    assert_eq!(
+        s.inner.into_inner().unwrap(),
+        Inner {
+            i_int64: 1,
+            s_string: "b".to_string(),
+        }
+    );
+
+    // 不正な値は get でエラーになる
+    let s = Sample::parse(&[0b01110010, 0b00000001, 0b00001000]).unwrap();
+    assert!(s.inner.get().is_err());
+
+    // 値がない場合は空のメッセージになり、書き出されない
+    let s = Sample::parse(&[]).unwrap();
+    assert_eq!(s.inner.get().unwrap(), &Inner::default());
+    assert!(s.bytes().unwrap().is_empty());
+}
+
+#[test]
+fn test_wire_message() {
+    use protowirers::message::WireMessage;
+
+    #[derive(Proto, Debug, Default, PartialEq, Clone)]
+    struct Inner {
+        #[def(field_num = 1, def_type = "string")]
+        s_string: String,
+    }
+
+    #[derive(Proto, Debug, Default, PartialEq)]
+    struct Sample {
+        #[def(field_num = 1, def_type = "uint32")]
+        u_uint32: u32,
+        #[def(field_num = 14, def_type = "embedded")]
+        i_inner: Inner,
+    }
+    let s = Sample {
+        u_uint32: 1,
+        i_inner: Inner {
+            s_string: "abc".to_string(),
+        },
+    };
+    // スキーマを使わずに、embedded message のフィールドだけを書き換える
+    let mut m = WireMessage::parse(&s.bytes().unwrap()).unwrap();
+    m.message_mut(14).unwrap().set(
+        1,
+        WireData::LengthDelimited(WireDataLengthDelimited::new(b"xyz".to_vec())),
+    );
+    let x = Sample::parse(&m.bytes().unwrap()).unwrap();
+    assert_eq!(x.u_uint32, 1);
+    assert_eq!(x.i_inner.s_string, "xyz");
+
+    // 書き換えていないフィールドはそのまま書き出される
+    // 1: 150, 2: { 1: 1 }
+    let bytes: &[u8] = &[
+        0b00001000, 0b10010110, 0b00000001, // 1
+        0b00010010, 0b00000010, 0b00001000, 0b00000001, // 2
+    ];
+    let mut m = WireMessage::parse(bytes).unwrap();
+    m.message_mut(2)
+        .unwrap()
+        .set(1, WireData::Varint(WireDataVarint::new(2)));
+    assert_eq!(
+        m.bytes().unwrap(),
+        vec![
+            0b00001000, 0b10010110, 0b00000001, // 1
+            0b00010010, 0b00000010, 0b00001000, 0b00000010, // 2
+        ]
+    );
+}
+
+#[test]
+fn test_query() {
+    use protowirers::query::{query, QueryValue};
+
+    // 14: { 2: 150 }
+    let bytes: &[u8] = &[0b01110010, 0b00000011, 0b00010000, 0b10010110, 0b00000001];
+    assert_eq!(
+        query(bytes, "14.2", TypeVairant::Uint32).unwrap(),
+        vec![QueryValue::Uint32(150)]
+    );
+    assert!(query(bytes, "14.1", TypeVairant::Uint32)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_deterministic() {
+    use protowirers::encode::EncodeOptions;
+    use protowirers::lazy::Lazy;
+
+    #[derive(Proto, Debug, Default, PartialEq, Clone)]
+    struct Inner {
+        #[def(field_num = 2, def_type = "string")]
+        s_string: String,
+        #[def(field_num = 1, def_type = "uint32")]
+        u_uint32: u32,
+    }
+
+    #[derive(Proto, Debug, Default, PartialEq)]
+    struct Sample {
+        #[def(field_num = 14, def_type = "embedded")]
+        i_inner: Inner,
+        #[def(field_num = 3, def_type = "embedded")]
+        l_inner: Lazy<Inner>,
+        #[def(field_num = 1, def_type = "sint64")]
+        s_int64: i64,
+    }
+    let inner = Inner {
+        s_string: "a".to_string(),
+        u_uint32: 1,
+    };
+    let s = Sample {
+        i_inner: inner.clone(),
+        l_inner: Lazy::new(inner.clone()),
+        s_int64: -1,
+    };
+    let options = EncodeOptions {
+        deterministic: true,
+        ..Default::default()
+    };
+    let canonical = vec![
+        0b00001000, 0b00000001, // field_num = 1
+        0b00011010, 0b00000101, 0b00001000, 0b00000001, 0b00010010, 0b00000001, b'a', // 3
+        0b01110010, 0b00000101, 0b00001000, 0b00000001, 0b00010010, 0b00000001, b'a', // 14
+    ];
+    assert_eq!(s.bytes_with_options(&options).unwrap(), canonical);
+    // 定義順で書き出される
+    assert_eq!(s.bytes().unwrap()[..2], [0b01110010, 0b00000101]);
+    assert_eq!(Sample::parse(&canonical).unwrap(), s);
+
+    // Lazy が保持している非正規なバイト列も encode し直す
+    let mut bytes = vec![0b01110010, 0b00000101, 0b00010010, 0b00000001, b'a'];
+    bytes.extend([0b00001000, 0b00000001, 0b00011010, 0b00000101]);
+    bytes.extend([0b00010010, 0b00000001, b'a', 0b00001000, 0b00000001]);
+    bytes.extend([0b00001000, 0b00000001]);
+    let x = Sample::parse(&bytes).unwrap();
+    assert_eq!(x.bytes_with_options(&options).unwrap(), canonical);
+    assert_ne!(x.bytes().unwrap(), canonical);
+    assert_eq!(x.fingerprint().unwrap(), s.fingerprint().unwrap());
+
+    let mut y = Sample::parse(&bytes).unwrap();
+    y.s_int64 = 2;
+    assert_ne!(y.fingerprint().unwrap(), s.fingerprint().unwrap());
+}
+
+#[test]
+fn test_encode_options() {
+    use protowirers::encode::{EncodeOptions, FieldOrder, Packing};
+    use protowirers::message::{WireMessage, WireValue};
+
+    #[derive(Proto, Debug, Default, PartialEq, Clone)]
+    #[proto(view)]
+    struct Inner {
+        #[def(field_num = 1, def_type = "uint32")]
+        u_uint32: u32,
+    }
+
+    #[derive(Proto, Debug, Default, PartialEq)]
+    #[proto(view)]
+    struct Sample {
+        #[def(field_num = 2, def_type = "int32")]
+        i_int32: i32,
+        #[def(field_num = 1, def_type = "string")]
+        s_string: String,
+        #[def(field_num = 3, def_type = "uint32", repeated, packed)]
+        r_packed: Vec<u32>,
+        #[def(field_num = 4, def_type = "sint64", repeated)]
+        r_unpacked: Vec<i64>,
+        #[def(field_num = 5, def_type = "double", repeated, packed)]
+        r_double: Vec<f64>,
+        #[def(field_num = 6, def_type = "string", repeated)]
+        r_string: Vec<String>,
+        #[def(field_num = 7, def_type = "embedded", repeated)]
+        r_inner: Vec<Inner>,
+        #[def(field_num = 8, def_type = "bytes", repeated)]
+        r_bytes: Vec<Vec<u8>>,
+    }
+
+    // emit_defaults の場合、値がゼロのフィールドも書き出す
+    let s = Sample::default();
+    assert_eq!(s.bytes().unwrap(), vec![]);
+    let emit_defaults = EncodeOptions {
+        emit_defaults: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        s.bytes_with_options(&emit_defaults).unwrap(),
+        vec![0b00010000, 0, 0b00001010, 0]
+    );
+    let by_field_number = EncodeOptions {
+        emit_defaults: true,
+        field_order: FieldOrder::FieldNumber,
+        ..Default::default()
+    };
+    assert_eq!(
+        s.bytes_with_options(&by_field_number).unwrap(),
+        vec![0b00001010, 0, 0b00010000, 0]
+    );
+    assert_eq!(
+        Sample::parse(&s.bytes_with_options(&emit_defaults).unwrap()).unwrap(),
+        s
+    );
+
+    // repeated の要素は、ゼロでも書き出す
+    let s = Sample {
+        r_packed: vec![0, 1],
+        r_unpacked: vec![0, -1],
+        ..Default::default()
+    };
+    let declared = vec![0b00011010, 2, 0, 1, 0b00100000, 0, 0b00100000, 1];
+    assert_eq!(s.bytes().unwrap(), declared);
+    assert_eq!(s.encoded_len(), declared.len());
+    let packed = EncodeOptions {
+        packing: Packing::Packed,
+        ..Default::default()
+    };
+    let got = s.bytes_with_options(&packed).unwrap();
+    assert_eq!(got, vec![0b00011010, 2, 0, 1, 0b00100010, 2, 0, 1]);
+    assert_eq!(Sample::parse(&got).unwrap(), s);
+    let unpacked = EncodeOptions {
+        packing: Packing::Unpacked,
+        ..Default::default()
+    };
+    let got = s.bytes_with_options(&unpacked).unwrap();
+    assert_eq!(
+        got,
+        vec![0b00011000, 0, 0b00011000, 1, 0b00100000, 0, 0b00100000, 1]
+    );
+    assert_eq!(Sample::parse(&got).unwrap(), s);
+
+    // fixed な型や length-delimited な型の repeated
+    let s = Sample {
+        r_double: vec![1.5, 0.0],
+        r_string: vec!["a".to_string(), String::new()],
+        r_inner: vec![Inner { u_uint32: 0 }, Inner { u_uint32: 1 }],
+        r_bytes: vec![vec![1], vec![]],
+        ..Default::default()
+    };
+    let bytes = s.bytes().unwrap();
+    assert_eq!(s.encoded_len(), bytes.len());
+    assert_eq!(Sample::parse(&bytes).unwrap(), s);
+    let got = s.bytes_with_options(&unpacked).unwrap();
+    assert_ne!(got, bytes);
+    assert_eq!(Sample::parse(&got).unwrap(), s);
+
+    let view = SampleView::new(&got).unwrap();
+    assert_eq!(
+        view.r_double()
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap(),
+        vec![1.5, 0.0]
+    );
+    assert_eq!(
+        view.r_string()
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap(),
+        vec!["a", ""]
+    );
+    let inner: Vec<u32> = view
+        .r_inner()
+        .unwrap()
+        .map(|v| v?.u_uint32())
+        .collect::<Result<_>>()
+        .unwrap();
+    assert_eq!(inner, vec![0, 1]);
+    assert_eq!(
+        view.r_bytes().unwrap().collect::<Result<Vec<_>>>().unwrap(),
+        vec![&[1][..], &[]]
+    );
+
+    // WireMessage は値がゼロのフィールドも書き出し、drop_defaults の場合のみ省略する
+    let mut m = WireMessage::new();
+    m.push(1, WireValue::Data(WireData::Varint(WireDataVarint::new(0))));
+    assert_eq!(m.bytes().unwrap(), vec![0b00001000, 0]);
+    assert_eq!(
+        m.bytes_with_options(&emit_defaults).unwrap(),
+        vec![0b00001000, 0]
+    );
+    let drop_defaults = EncodeOptions {
+        drop_defaults: true,
+        ..Default::default()
+    };
+    assert_eq!(m.bytes_with_options(&drop_defaults).unwrap(), vec![]);
+}
+
+#[test]
+fn test_decode_error() {
+    #[derive(Proto, Debug, Default)]
+    struct Inner {
+        #[def(field_num = 1, def_type = "uint32")]
+        u_uint32: u32,
+        #[def(field_num = 3, def_type = "sint64")]
+        s_int64: i64,
+    }
+
+    #[derive(Proto, Debug, Default)]
+    struct Sample {
+        #[def(field_num = 1, def_type = "string")]
+        s_string: String,
+        #[def(field_num = 14, def_type = "embedded")]
+        i_inner: Inner,
+        #[def(field_num = 2, def_type = "embedded", repeated)]
+        r_inner: Vec<Inner>,
+    }
+
+    // Inner の s_int64 の varint が終わらない
+    let bytes: &[u8] = &[
+        0b00001010, 2, b'a', b'b', // 1
+        0b01110010, 5, 0b00001000, 1, 0b00011000, 0xff, 0xff, // 14
+    ];
+    let e = Sample::parse(bytes).unwrap_err();
+    let Error::Decode(d) = &e else {
+        panic!("unexpected error: {:?}", e);
+    };
+    assert_eq!(d.path(), "Sample.i_inner(14).s_int64(3)");
+    assert_eq!(d.offset(), 8);
+    assert!(matches!(d.inner(), Error::UnexpectedFormat));
+    assert_eq!(
+        e.to_string(),
+        "failed to decode `Sample.i_inner(14).s_int64(3)` at byte 8: \
+         unexpected format. end came before MSB cleared"
+    );
+
+    // 最も外側のフィールド
+    let e = Sample::parse(&[0b00001010, 1, 0xff]).unwrap_err();
+    let Error::Decode(d) = &e else {
+        panic!("unexpected error: {:?}", e);
+    };
+    assert_eq!(d.path(), "Sample.s_string(1)");
+    assert_eq!(d.offset(), 0);
+    assert!(matches!(d.inner(), Error::Utf8(_)));
+    // cause で Decode で包まれる前の variant を match できる
+    assert!(matches!(e.cause(), Error::Utf8(_)));
+
+    // repeated な embedded message の要素
+    let bytes: &[u8] = &[
+        0b00010010, 2, 0b00001000, 1, // 2
+        0b00010010, 3, 0b00001000, 0xff, 0xff, // 2
+    ];
+    let e = Sample::parse(bytes).unwrap_err();
+    let Error::Decode(d) = &e else {
+        panic!("unexpected error: {:?}", e);
+    };
+    assert_eq!(d.path(), "Sample.r_inner(2).u_uint32(1)");
+    assert_eq!(d.offset(), 6);
+
+    // tag が読めない場合は型名のみ
+    let e = Sample::parse(&[0b00001010, 0, 0xff]).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "failed to decode `Sample` at byte 2: unexpected format. end came before MSB cleared"
+    );
+}
+
+#[test]
+fn test_diagnose() {
+    use protowirers::decode::{diagnose, diagnose_as};
+    use protowirers::descriptor::FieldType;
+    use protowirers::diagnose::DiagnosticKind;
+    use protowirers::lazy::Lazy;
+
+    #[derive(Proto, Debug, Default)]
+    struct Inner {
+        #[def(field_num = 1, def_type = "string")]
+        s_string: String,
+        #[def(field_num = 3, def_type = "sint64")]
+        s_int64: i64,
+    }
+
+    #[derive(Proto, Debug, Default)]
+    struct Sample {
+        #[def(field_num = 1, def_type = "uint32")]
+        u_uint32: u32,
+        #[def(field_num = 14, def_type = "embedded")]
+        i_inner: Inner,
+        #[def(field_num = 2, def_type = "embedded", repeated)]
+        r_inner: Vec<Lazy<Inner>>,
+        #[def(field_num = 4, def_type = "fixed32", repeated, packed)]
+        r_fixed32: Vec<u32>,
+    }
+
+    let d = Sample::descriptor().unwrap();
+    assert_eq!(d.name, "Sample");
+    let f = d.field(2).unwrap();
+    assert_eq!(
+        (f.name, f.ty, f.repeated),
+        ("r_inner", FieldType::Message, true)
+    );
+    assert_eq!(f.message.unwrap()().unwrap().name, "Inner");
+    assert!(d.field(4).unwrap().packed);
+
+    let bytes: &[u8] = &[
+        0b00001000, 0b10000001, 0, // 1: 冗長な varint
+        0b01110010, 5, 0b00001010, 1, 0xff, 0b00011000, 1, // 14: 1 は不正な UTF-8
+        0b00010010, 2, 0b00011101,
+        0, // 2: 3 は wire type が異なる（途中で終わる）
+        0b00100010, 3, 1, 2, 3,    // 4: fixed32 の packed が 4 バイトに満たない
+        0xff, // 末尾の tag が途中で終わる
+    ];
+    let found: Vec<String> = diagnose_as::<Sample>(bytes)
+        .iter()
+        .map(|d| d.to_string())
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            "at byte 1 in `Sample.u_uint32(1)`: non-canonical varint. len=2, canonical len=1",
+            "at byte 7 in `Sample.i_inner(14).s_string(1)`: invalid utf-8 after 0 bytes",
+            "at byte 12 in `Sample.r_inner(2).s_int64(3)`: unexpected wire type. got=5, want=0",
+            "at byte 13 in `Sample.r_inner(2).s_int64(3)`: truncated value. got=1, want=4",
+            "at byte 16 in `Sample.r_fixed32(4)`: truncated value. got=3, want=4",
+            "at byte 19 in `Sample`: 1 bytes of trailing garbage",
+        ]
+    );
+
+    // スキーマがない場合は length-delimited の中身を検査しない
+    let found = diagnose(bytes);
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].offset, 1);
+    assert!(matches!(
+        found[0].kind,
+        DiagnosticKind::NonCanonicalVarint { .. }
+    ));
+    assert_eq!(found[1].kind, DiagnosticKind::TrailingGarbage { len: 1 });
+
+    // 再帰する型でも、入れ子が深すぎる場合はそこで検査をやめる
+    #[derive(Proto, Debug, Default, PartialEq)]
+    struct Node {
+        #[def(field_num = 1, def_type = "embedded", repeated)]
+        children: Vec<Node>,
+    }
+    // 1: { 1: { ... } } を後ろから組み立てる
+    let mut buf = Vec::new();
+    for _ in 0..200_000 {
+        let len = buf.len();
+        protowirers::encode::push_variant_reversed(&mut buf, len as u128);
+        buf.push(0b00001010);
+    }
+    buf.reverse();
+    let found = diagnose_as::<Node>(&buf);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].kind, DiagnosticKind::NestedTooDeeply);
+}
+
+#[test]
+fn test_json() {
+    use protowirers::json::JsonValue;
+
+    #[derive(Proto, Clone, Copy, PartialEq, Eq, Debug)]
+    enum Color {
+        Red,
+        Green,
+        Other(i32),
+    }
+    #[derive(Proto, Debug, Default, PartialEq, Clone, Copy)]
+    #[proto(transparent)]
+    struct UserId(#[def(def_type = "uint64")] u64);
+
+    #[derive(Proto, Debug, Default, PartialEq)]
+    struct Inner {
+        #[def(field_num = 1, def_type = "sint32")]
+        s_int32: i32,
+    }
+
+    #[derive(Proto, Debug, Default, PartialEq)]
+    struct Sample {
+        #[def(field_num = 1, def_type = "int64")]
+        i_int64: i64,
+        #[def(field_num = 2, def_type = "uint32")]
+        u_uint32: u32,
+        #[def(field_num = 3, def_type = "double")]
+        d_double: f64,
+        #[def(field_num = 4, def_type = "float", repeated, packed)]
+        r_float: Vec<f32>,
+        #[def(field_num = 5, def_type = "bytes")]
+        b_bytes: Vec<u8>,
+        #[def(field_num = 6, def_type = "enum")]
+        e_color: Color,
+        #[def(field_num = 7, def_type = "string", json_name = "label")]
+        s_string: String,
+        #[def(field_num = 8, def_type = "embedded")]
+        i_inner: Inner,
+        #[def(field_num = 9, def_type = "embedded", repeated)]
+        r_inner: Vec<Inner>,
+        #[def(field_num = 10, def_type = "uint64")]
+        user_id: UserId,
+        #[def(field_num = 11, def_type = "bool")]
+        b_bool: bool,
+    }
+
+    let s = Sample {
+        i_int64: -9007199254740993,
+        u_uint32: 7,
+        d_double: 1.5,
+        r_float: vec![f32::NAN, f32::INFINITY, 0.0],
+        b_bytes: vec![0xfb, 0xff],
+        e_color: Color::Green,
+        s_string: "a\"b".to_string(),
+        i_inner: Inner { s_int32: -1 },
+        r_inner: vec![Inner { s_int32: 0 }, Inner { s_int32: 2 }],
+        user_id: UserId(u64::MAX),
+        b_bool: false,
+    };
+    let json = s.to_json().unwrap();
+    assert_eq!(
+        json,
+        r#"{"iInt64":"-9007199254740993","uUint32":7,"dDouble":1.5,"rFloat":["NaN","Infinity",0],"bBytes":"+/8=","eColor":"Green","label":"a\"b","iInner":{"sInt32":-1},"rInner":[{},{"sInt32":2}],"userId":"18446744073709551615"}"#
+    );
+    let got = Sample::from_json(&json).unwrap();
+    assert!(got.r_float[0].is_nan());
+    assert_eq!(got.r_float[1..], s.r_float[1..]);
+    assert_eq!(
+        Sample {
+            r_float: Vec::new(),
+            ..got
+        },
+        Sample {
+            r_float: Vec::new(),
+            ..s
+        }
+    );
+
+    // default 値のフィールドは省略する
+    assert_eq!(Sample::default().to_json().unwrap(), "{}");
+    // -0.0 は default 値ではないため省略しない
+    let json = Sample {
+        d_double: -0.0,
+        ..Default::default()
+    }
+    .to_json()
+    .unwrap();
+    assert_eq!(json, r#"{"dDouble":-0}"#);
+    assert!(Sample::from_json(&json)
+        .unwrap()
+        .d_double
+        .is_sign_negative());
+
+    // フィールド名、数値の整数、null、数値の enum、URL-safe な base64 も受け付ける
+    let got = Sample::from_json(
+        r#"{"i_int64": 3, "uUint32": "4", "s_string": "x", "eColor": 5, "bBytes": "-_8", "dDouble": null}"#,
+    )
+    .unwrap();
+    assert_eq!(got.i_int64, 3);
+    assert_eq!(got.u_uint32, 4);
+    assert_eq!(got.s_string, "x");
+    assert_eq!(got.e_color, Color::Other(5));
+    assert_eq!(got.b_bytes, vec![0xfb, 0xff]);
+    assert_eq!(got.d_double, 0.0);
+
+    // エラーはフィールドの JSON の名前のパスを持つ
+    let e = Sample::from_json(r#"{"rInner": [{}, {"sInt32": "x"}]}"#).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "invalid json value at `rInner.1.sInt32`. expected integer, got string"
+    );
+    let e = Sample::from_json(r#"{"eColor": "Blue"}"#).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "invalid json value at `eColor`. unknown enum value `Blue`"
+    );
+    let e = Sample::from_json(r#"{"unknown": 1}"#).unwrap_err();
+    assert_eq!(e.to_string(), "invalid json value. unknown field `unknown`");
+    assert!(matches!(
+        Sample::from_json(r#"{"uUint32": 1"#),
+        Err(Error::InvalidJson { offset: 13, .. })
+    ));
+    assert!(matches!(
+        Sample::from_json_value(&JsonValue::Array(Vec::new())),
+        Err(Error::InvalidJsonValue { .. })
+    ));
+}
+
+#[test]
+fn test_text() {
+    #[derive(Proto, Clone, Copy, PartialEq, Eq, Debug)]
+    enum Color {
+        Red,
+        Green,
+        Other(i32),
+    }
+
+    #[derive(Proto, Debug, Default, PartialEq)]
+    struct Inner {
+        #[def(field_num = 1, def_type = "int32")]
+        i_int32: i32,
+    }
+
+    #[derive(Proto, Debug, Default, PartialEq)]
+    struct Sample {
+        #[def(field_num = 1, def_type = "string")]
+        s_string: String,
+        #[def(field_num = 2, def_type = "embedded")]
+        i_inner: Inner,
+        #[def(field_num = 3, def_type = "uint32", repeated, packed)]
+        r_u_int32: Vec<u32>,
+        #[def(field_num = 4, def_type = "embedded", repeated)]
+        r_inner: Vec<Inner>,
+        #[def(field_num = 5, def_type = "bytes")]
+        b_bytes: Vec<u8>,
+        #[def(field_num = 6, def_type = "enum")]
+        e_color: Color,
+        #[def(field_num = 7, def_type = "double")]
+        d_double: f64,
+    }
+
+    let s = Sample {
+        s_string: "hi".to_string(),
+        i_inner: Inner { i_int32: 3 },
+        r_u_int32: vec![1, 2],
+        r_inner: vec![Inner { i_int32: 0 }, Inner { i_int32: -4 }],
+        b_bytes: vec![b'a', 0, 0xff],
+        e_color: Color::Green,
+        d_double: f64::NEG_INFINITY,
+    };
+    let text = s.to_text().unwrap();
+    assert_eq!(
+        text,
+        r#"s_string: "hi" i_inner { i_int32: 3 } r_u_int32: [1, 2] r_inner {} r_inner { i_int32: -4 } b_bytes: "a\000\377" e_color: Green d_double: -inf"#
+    );
+    assert_eq!(Sample::from_text(&text).unwrap(), s);
+
+    assert_eq!(
+        s.display_text().to_string(),
+        r#"s_string: "hi"
+i_inner {
+  i_int32: 3
+}
+r_u_int32: [1, 2]
+r_inner {}
+r_inner {
+  i_int32: -4
+}
+b_bytes: "a\000\377"
+e_color: Green
+d_double: -inf"#
+    );
+
+    // default 値のフィールドは省略する
+    assert_eq!(Sample::default().to_text().unwrap(), "");
+    // -0.0 は default 値ではないため省略しない
+    let text = Sample {
+        d_double: -0.0,
+        ..Default::default()
+    }
+    .to_text()
+    .unwrap();
+    assert_eq!(text, "d_double: -0.0");
+    assert!(Sample::from_text(&text)
+        .unwrap()
+        .d_double
+        .is_sign_negative());
+
+    // repeated は list と繰り返しのどちらも受け付け、末尾に追加する
+    let got = Sample::from_text(
+        "# config\nr_u_int32: 1\nr_u_int32: [2, 0x3]\ni_inner < i_int32: 5 >; e_color: 7",
+    )
+    .unwrap();
+    assert_eq!(got.r_u_int32, vec![1, 2, 3]);
+    assert_eq!(got.i_inner, Inner { i_int32: 5 });
+    assert_eq!(got.e_color, Color::Other(7));
+
+    // エラーは行と列を持つ
+    let e = Sample::from_text("s_string: \"x\"\ni_inner {\n  i_int32: \"y\"\n}").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "invalid text format at line 3, column 12. unexpected type. got=string, want=integer"
+    );
+    let e = Sample::from_text("r_u_int32: [1, -2]").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "invalid text format at line 1, column 16. integer out of range `-2`"
+    );
+    let e = Sample::from_text("e_color: Blue\nunknown: 1").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "invalid text format at line 1, column 10. unknown enum value `Blue`"
+    );
+    let e = Sample::from_text("\nunknown: 1").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "invalid text format at line 2, column 1. unknown field `unknown`"
+    );
+}
+
+#[test]
+fn test_enum_values() {
+    // 明示的な値を持つ列挙型は、その値で encode/decode される
+    #[derive(Proto, Clone, Copy, PartialEq, Eq, Debug)]
+    #[repr(i32)]
+    enum Status {
+        Unspecified = 0,
+        Deleted = -1,
+        Active = 5,
+        Archived,
+        Other(i32),
+    }
+    #[derive(Proto, Default, Debug, PartialEq)]
+    struct Sample {
+        #[def(field_num = 1, def_type = "enum")]
+        e_status: Status,
+        #[def(field_num = 2, def_type = "enum", repeated, packed)]
+        r_status: Vec<Status>,
+    }
+
+    assert_eq!(Status::default(), Status::Unspecified);
+    assert_eq!(i32::from(Status::Archived), 6);
+    assert_eq!(Status::from(5), Status::Active);
+    assert_eq!(Status::from(-1), Status::Deleted);
+    assert_eq!(Status::from(1), Status::Other(1));
+
+    let sample = Sample {
+        e_status: Status::Active,
+        r_status: vec![Status::Archived, Status::Other(2)],
+    };
+    let bytes = sample.bytes().unwrap();
+    assert_eq!(bytes, vec![0x08, 0x05, 0x12, 0x02, 0x06, 0x02]);
+    assert_eq!(Sample::parse(&bytes).unwrap(), sample);
+    assert_eq!(
+        sample.to_json().unwrap(),
+        r#"{"eStatus":"Active","rStatus":["Archived",2]}"#
+    );
+}
+
+#[test]
+fn test_lazy_encode_options() {
+    use protowirers::encode::{EncodeOptions, Packing};
+    use protowirers::lazy::Lazy;
+
+    #[derive(Proto, Debug, Default, PartialEq, Clone)]
+    struct Inner {
+        #[def(field_num = 1, def_type = "int32")]
+        i_int32: i32,
+        #[def(field_num = 2, def_type = "uint32", repeated, packed)]
+        r_packed: Vec<u32>,
+    }
+
+    #[derive(Proto, Debug, Default, PartialEq)]
+    struct Sample {
+        #[def(field_num = 1, def_type = "embedded")]
+        l_inner: Lazy<Inner>,
+    }
+
+    // 読み込んでいない Lazy も、読み込んだ Lazy と同じように options に従って書き出される
+    let inner = Inner {
+        i_int32: 0,
+        r_packed: vec![1, 2],
+    };
+    let untouched = Sample::parse(
+        &Sample {
+            l_inner: Lazy::new(inner.clone()),
+        }
+        .bytes()
+        .unwrap(),
+    )
+    .unwrap();
+    assert!(untouched.l_inner.raw().is_some());
+    let decoded = Sample {
+        l_inner: Lazy::new(inner),
+    };
+
+    let emit_defaults = EncodeOptions {
+        emit_defaults: true,
+        ..Default::default()
+    };
+    let want = vec![0b00001010, 6, 0b00001000, 0, 0b00010010, 2, 1, 2];
+    assert_eq!(untouched.bytes_with_options(&emit_defaults).unwrap(), want);
+    assert_eq!(decoded.bytes_with_options(&emit_defaults).unwrap(), want);
+
+    let unpacked = EncodeOptions {
+        packing: Packing::Unpacked,
+        ..Default::default()
+    };
+    let want = vec![0b00001010, 4, 0b00010000, 1, 0b00010000, 2];
+    assert_eq!(untouched.bytes_with_options(&unpacked).unwrap(), want);
+    assert_eq!(decoded.bytes_with_options(&unpacked).unwrap(), want);
+}